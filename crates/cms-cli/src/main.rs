@@ -0,0 +1,83 @@
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
+
+use cms_core::command::parse_access_mode;
+use cms_core::{ready_message, CmsFileSystem, CommandProcessor, IplOptions};
+
+const USAGE: &str = "Usage: cms [--home dir] [--disk dir fm ...] [--noprof]";
+
+/// The directory holding the A disk (`dir/a`) — `--home`, else $CMS_HOME,
+/// else ~/.cms — and what to do at IPL
+fn parse_args(args: &[String]) -> Result<(PathBuf, IplOptions), String> {
+    let mut home = None;
+    let mut ipl = IplOptions::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--home" => {
+                let value = iter.next().ok_or("--home requires a directory")?;
+                home = Some(PathBuf::from(value));
+            }
+            "--disk" => {
+                let (Some(dir), Some(fm)) = (iter.next(), iter.next()) else {
+                    return Err("--disk requires a directory and a filemode".into());
+                };
+                let (letter, access) = parse_access_mode(fm).map_err(|msg| msg.to_string())?;
+                ipl.disks.push((PathBuf::from(dir), letter, access));
+            }
+            "--noprof" => ipl.noprof = true,
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    let home = home
+        .or_else(|| std::env::var_os("CMS_HOME").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cms")))
+        .unwrap_or_else(|| PathBuf::from(".cms"));
+    Ok((home, ipl))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (home, ipl) = parse_args(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    });
+    let fs = CmsFileSystem::with_default_disk(&home).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    let mut cms = CommandProcessor::new(fs);
+    cms.set_console(|line| println!("{}", line));
+
+    println!("CMS {}", env!("CARGO_PKG_VERSION"));
+    // IPL: access the configured disks and run PROFILE EXEC
+    let start = Instant::now();
+    let output = cms.ipl(&ipl);
+    for text in &output.lines {
+        println!("{}", text);
+    }
+    println!("{}", ready_message(output.rc, start.elapsed()));
+
+    // Line-mode console: each command is followed by its output and a
+    // Ready; line with the return code
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let start = Instant::now();
+        let output = cms.execute(&line);
+        for text in &output.lines {
+            println!("{}", text);
+        }
+        if output.logoff {
+            break;
+        }
+        println!("{}", ready_message(output.rc, start.elapsed()));
+    }
+}
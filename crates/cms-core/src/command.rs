@@ -0,0 +1,430 @@
+use std::path::PathBuf;
+
+use crate::filespec::FileSpec;
+use crate::globalv::{self, GlobalvCommand};
+use crate::message::DmsMessage;
+use crate::minidisk::AccessMode;
+
+/// CMS commands
+#[derive(Debug, Clone, PartialEq)]
+pub enum CmsCommand {
+    /// ACCESS dir fm — mount a directory as a disk. Filemode digits 2-6
+    /// access it read-only.
+    Access {
+        path: PathBuf,
+        letter: char,
+        access: AccessMode,
+    },
+    /// COPYFILE fn ft fm fn2 [ft2 [fm2]] [(REPLACE] — `=` keeps the source's name
+    Copyfile {
+        from: FileSpec,
+        to: FileSpec,
+        replace: bool,
+    },
+    /// ERASE fn ft [fm] — wildcards erase every matching file
+    Erase(FileSpec),
+    /// EXEC name [args] — run `name EXEC` from the accessed disks
+    Exec {
+        name: String,
+        args: String,
+    },
+    /// GLOBALV [SELECT group] function ... — see `globalv`
+    Globalv(GlobalvCommand),
+    /// LISTFILE [fn [ft [fm]]] — defaults to `* * A`
+    Listfile(FileSpec),
+    /// LOGOFF — leave CMS
+    Logoff,
+    Query(QueryItem),
+    /// RELEASE fm — unmount a disk
+    Release(char),
+    /// RENAME fn ft fm fn2 [ft2 [fm2]]
+    Rename {
+        from: FileSpec,
+        to: FileSpec,
+    },
+    /// STATE fn ft [fm] — set the return code by whether a file exists
+    State(FileSpec),
+    /// TYPE fn ft [fm] — show a file's contents
+    Type(FileSpec),
+}
+
+impl CmsCommand {
+    /// The command's full name, as in the command table
+    pub fn name(&self) -> &'static str {
+        match self {
+            CmsCommand::Access { .. } => "ACCESS",
+            CmsCommand::Copyfile { .. } => "COPYFILE",
+            CmsCommand::Erase(_) => "ERASE",
+            CmsCommand::Exec { .. } => "EXEC",
+            CmsCommand::Globalv(_) => "GLOBALV",
+            CmsCommand::Listfile(_) => "LISTFILE",
+            CmsCommand::Logoff => "LOGOFF",
+            CmsCommand::Query(_) => "QUERY",
+            CmsCommand::Release(_) => "RELEASE",
+            CmsCommand::Rename { .. } => "RENAME",
+            CmsCommand::State(_) => "STATE",
+            CmsCommand::Type(_) => "TYPE",
+        }
+    }
+
+    /// The module code in this command's DMS message ids
+    pub fn module(&self) -> &'static str {
+        lookup_command(self.name()).map_or("CMS", |(_, module)| module)
+    }
+}
+
+/// What QUERY reports
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryItem {
+    /// QUERY DISK [fm|*] — accessed disks with their file counts
+    Disk(char),
+    /// QUERY SEARCH — accessed disks in search order
+    Search,
+}
+
+/// Command table: (full name, minimum abbreviation length, message module)
+const COMMAND_TABLE: &[(&str, usize, &str)] = &[
+    ("ACCESS", 3, "ACC"),   // ACC
+    ("COPYFILE", 4, "CPY"), // COPY
+    ("ERASE", 2, "ERS"),    // ER
+    ("EXEC", 2, "EXC"),     // EX
+    ("GLOBALV", 7, "GLV"),  // GLOBALV
+    ("LISTFILE", 1, "LST"), // L
+    ("LOGOFF", 3, "LGF"),   // LOG
+    ("QUERY", 1, "QRY"),    // Q
+    ("RELEASE", 3, "ARL"),  // REL
+    ("RENAME", 6, "RNM"),   // RENAME
+    ("STATE", 5, "STT"),    // STATE
+    ("TYPE", 1, "TYP"),     // T
+];
+
+/// Resolve a command word to its table entry (name, module)
+fn lookup_command(input: &str) -> Option<(&'static str, &'static str)> {
+    let input_upper = input.to_uppercase();
+    COMMAND_TABLE
+        .iter()
+        .find(|&&(name, min, _)| input_upper.len() >= min && name.starts_with(&input_upper))
+        .map(|&(name, _, module)| (name, module))
+}
+
+/// Split operands from CMS options, which follow an open parenthesis:
+/// `COPYFILE A B A C B A (REPLACE`. Options are uppercased.
+fn split_options(args: &str) -> (Vec<&str>, Vec<String>) {
+    let (operands, options) = match args.find('(') {
+        Some(pos) => (&args[..pos], &args[pos + 1..]),
+        None => (args, ""),
+    };
+    let options = options
+        .trim_end()
+        .trim_end_matches(')')
+        .split_whitespace()
+        .map(str::to_uppercase)
+        .collect();
+    (operands.split_whitespace().collect(), options)
+}
+
+/// A fileid of two or three words, with `default_fm` when the mode is left out
+fn parse_fileid(
+    words: &[&str],
+    default_fm: &str,
+    module: &'static str,
+) -> Result<FileSpec, DmsMessage> {
+    let (fname, ftype, fmode) = match *words {
+        [fname, ftype] => (fname, ftype, default_fm),
+        [fname, ftype, fmode] => (fname, ftype, fmode),
+        [] | [_] => return Err(DmsMessage::incomplete_fileid(module)),
+        [.., extra] => return Err(DmsMessage::invalid_parameter(module, extra)),
+    };
+    FileSpec::new(fname, ftype, fmode).map_err(|e| DmsMessage::from_error(module, &e))
+}
+
+/// The source and target fileids of COPYFILE and RENAME: a full source
+/// fileid, then one to three target words where `=` (or a word left out)
+/// repeats the source's
+fn parse_file_pair(
+    words: &[&str],
+    module: &'static str,
+) -> Result<(FileSpec, FileSpec), DmsMessage> {
+    if words.len() < 4 {
+        return Err(DmsMessage::incomplete_fileid(module));
+    }
+    let from = parse_fileid(&words[..3], "A", module)?;
+    let target = &words[3..];
+    if target.len() > 3 {
+        return Err(DmsMessage::invalid_parameter(module, target[3]));
+    }
+    let word = |i: usize, source: String| match target.get(i) {
+        None | Some(&"=") => source,
+        Some(word) => word.to_string(),
+    };
+    let to = FileSpec::new(
+        &word(0, from.filename().to_string()),
+        &word(1, from.filetype().to_string()),
+        &word(2, from.filemode()),
+    )
+    .map_err(|e| DmsMessage::from_error(module, &e))?;
+    Ok((from, to))
+}
+
+/// The filemode operand of ACCESS (`B`, `B2`): the disk letter and, from
+/// the mode number, its access mode
+pub fn parse_access_mode(fm: &str) -> Result<(char, AccessMode), DmsMessage> {
+    // Parse the mode through a FileSpec so it gets the same checks
+    let mode = FileSpec::new("X", "X", fm)
+        .ok()
+        .filter(|spec| spec.mode_letter() != '*')
+        .ok_or_else(|| DmsMessage::invalid_parameter("ACC", fm))?;
+    Ok((
+        mode.mode_letter(),
+        AccessMode::from_digit(mode.mode_number()),
+    ))
+}
+
+/// The filemode letter operand of RELEASE and QUERY DISK
+fn parse_mode_letter(word: &str, module: &'static str) -> Result<char, DmsMessage> {
+    let mut chars = word.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_alphabetic() || letter == '*' => {
+            Ok(letter.to_ascii_uppercase())
+        }
+        _ => Err(DmsMessage::invalid_parameter(module, word)),
+    }
+}
+
+/// Reject operands left over after a command's own
+fn no_more(words: &[&str], module: &'static str) -> Result<(), DmsMessage> {
+    match words.first() {
+        Some(word) => Err(DmsMessage::invalid_parameter(module, word)),
+        None => Ok(()),
+    }
+}
+
+/// Parse a CMS command line. Unknown commands give `None`; errors in a
+/// known command's operands give the DMS message CMS would show.
+pub fn parse_command(input: &str) -> Option<Result<CmsCommand, DmsMessage>> {
+    let input = input.trim();
+    let (cmd_word, args) = match input.find(char::is_whitespace) {
+        Some(pos) => (&input[..pos], input[pos..].trim()),
+        None => (input, ""),
+    };
+    let (name, module) = lookup_command(cmd_word)?;
+    if name == "EXEC" {
+        // Everything after the EXEC name, options included, is its argument string
+        let (exec_name, exec_args) = match args.find(char::is_whitespace) {
+            Some(pos) => (&args[..pos], args[pos..].trim()),
+            None => (args, ""),
+        };
+        if exec_name.is_empty() {
+            return Some(Err(DmsMessage::new(
+                module,
+                1,
+                'E',
+                "No EXEC name specified".into(),
+                24,
+            )));
+        }
+        return Some(Ok(CmsCommand::Exec {
+            name: exec_name.to_uppercase(),
+            args: exec_args.to_string(),
+        }));
+    }
+    if name == "GLOBALV" {
+        // Values may hold anything, parentheses included
+        return Some(globalv::parse(args).map(CmsCommand::Globalv));
+    }
+    let (words, options) = split_options(args);
+    Some(parse_operands(name, module, &words, &options))
+}
+
+fn parse_operands(
+    name: &str,
+    module: &'static str,
+    words: &[&str],
+    options: &[String],
+) -> Result<CmsCommand, DmsMessage> {
+    let replace = match options {
+        [] => false,
+        [opt] if name == "COPYFILE" && "REPLACE".starts_with(opt.as_str()) && opt.len() >= 3 => {
+            true
+        }
+        [opt, ..] => return Err(DmsMessage::invalid_parameter(module, opt)),
+    };
+    match name {
+        "ACCESS" => {
+            let [dir, fm] = *words else {
+                return Err(match words.get(2) {
+                    Some(extra) => DmsMessage::invalid_parameter(module, extra),
+                    None => DmsMessage::new(module, 17, 'E', "Invalid device address".into(), 24),
+                });
+            };
+            let (letter, access) = parse_access_mode(fm)?;
+            Ok(CmsCommand::Access {
+                path: PathBuf::from(dir),
+                letter,
+                access,
+            })
+        }
+        "COPYFILE" => {
+            let (from, to) = parse_file_pair(words, module)?;
+            Ok(CmsCommand::Copyfile { from, to, replace })
+        }
+        "RENAME" => {
+            let (from, to) = parse_file_pair(words, module)?;
+            Ok(CmsCommand::Rename { from, to })
+        }
+        "ERASE" => Ok(CmsCommand::Erase(parse_fileid(words, "A", module)?)),
+        "STATE" => Ok(CmsCommand::State(parse_fileid(words, "*", module)?)),
+        "TYPE" => Ok(CmsCommand::Type(parse_fileid(words, "*", module)?)),
+        "LISTFILE" => {
+            let mut parts = vec!["*", "*", "A"];
+            if words.len() > 3 {
+                return Err(DmsMessage::invalid_parameter(module, words[3]));
+            }
+            parts[..words.len()].copy_from_slice(words);
+            Ok(CmsCommand::Listfile(parse_fileid(&parts, "A", module)?))
+        }
+        "RELEASE" => match words {
+            [fm, rest @ ..] => {
+                no_more(rest, module)?;
+                Ok(CmsCommand::Release(parse_mode_letter(fm, module)?))
+            }
+            [] => Err(DmsMessage::new(
+                module,
+                28,
+                'E',
+                "No filemode specified".into(),
+                24,
+            )),
+        },
+        "QUERY" => {
+            let Some((item, rest)) = words.split_first() else {
+                return Err(DmsMessage::new(
+                    module,
+                    46,
+                    'E',
+                    "No option specified".into(),
+                    24,
+                ));
+            };
+            let item = item.to_uppercase();
+            if item == "DISK" {
+                let letter = match rest {
+                    [] => '*',
+                    [fm, rest @ ..] => {
+                        no_more(rest, module)?;
+                        parse_mode_letter(fm, module)?
+                    }
+                };
+                Ok(CmsCommand::Query(QueryItem::Disk(letter)))
+            } else if item.len() >= 6 && "SEARCH".starts_with(&item) {
+                no_more(rest, module)?;
+                Ok(CmsCommand::Query(QueryItem::Search))
+            } else {
+                Err(DmsMessage::invalid_parameter(module, &item))
+            }
+        }
+        "LOGOFF" => {
+            no_more(words, module)?;
+            Ok(CmsCommand::Logoff)
+        }
+        "EXEC" | "GLOBALV" => unreachable!("{} is parsed before its operands are split", name),
+        _ => unreachable!("command table entry without a parser: {}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<CmsCommand, DmsMessage> {
+        parse_command(input).expect("known command")
+    }
+
+    #[test]
+    fn abbreviations() {
+        assert_eq!(lookup_command("l"), Some(("LISTFILE", "LST")));
+        assert_eq!(lookup_command("listf"), Some(("LISTFILE", "LST")));
+        assert_eq!(lookup_command("copy"), Some(("COPYFILE", "CPY")));
+        assert_eq!(lookup_command("cop"), None);
+        assert_eq!(lookup_command("er"), Some(("ERASE", "ERS")));
+        assert_eq!(lookup_command("rel"), Some(("RELEASE", "ARL")));
+        assert_eq!(lookup_command("ren"), None);
+        assert_eq!(lookup_command("t"), Some(("TYPE", "TYP")));
+        assert_eq!(lookup_command("ex"), Some(("EXEC", "EXC")));
+        assert!(parse_command("FROBNICATE").is_none());
+    }
+
+    #[test]
+    fn parse_listfile_defaults() {
+        assert_eq!(
+            parse("LISTFILE").unwrap(),
+            CmsCommand::Listfile(FileSpec::parse("* * A").unwrap())
+        );
+        assert_eq!(
+            parse("l * exec *").unwrap(),
+            CmsCommand::Listfile(FileSpec::new("*", "EXEC", "*").unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_copyfile_with_equals_and_replace() {
+        let CmsCommand::Copyfile { from, to, replace } =
+            parse("COPYFILE PROFILE EXEC A = OLD B (REP").unwrap()
+        else {
+            panic!("expected COPYFILE");
+        };
+        assert_eq!(from.to_string(), "PROFILE EXEC A1");
+        assert_eq!(to.to_string(), "PROFILE OLD B1");
+        assert!(replace);
+
+        let CmsCommand::Rename { to, .. } = parse("RENAME A B C NEW").unwrap() else {
+            panic!("expected RENAME");
+        };
+        assert_eq!(to.to_string(), "NEW B C1");
+    }
+
+    #[test]
+    fn parse_exec_keeps_its_arguments() {
+        assert_eq!(
+            parse("exec backup Data File (NOTYPE").unwrap(),
+            CmsCommand::Exec {
+                name: "BACKUP".into(),
+                args: "Data File (NOTYPE".into()
+            }
+        );
+        assert!(parse("EXEC").is_err());
+    }
+
+    #[test]
+    fn parse_access_and_release() {
+        assert_eq!(
+            parse("ACC /tmp/disk B2").unwrap(),
+            CmsCommand::Access {
+                path: PathBuf::from("/tmp/disk"),
+                letter: 'B',
+                access: AccessMode::ReadOnly,
+            }
+        );
+        assert_eq!(parse("REL b").unwrap(), CmsCommand::Release('B'));
+        assert_eq!(
+            parse("Q DISK").unwrap(),
+            CmsCommand::Query(QueryItem::Disk('*'))
+        );
+        assert_eq!(
+            parse("Q SEARCH").unwrap(),
+            CmsCommand::Query(QueryItem::Search)
+        );
+    }
+
+    #[test]
+    fn parse_errors_are_dms_messages() {
+        let err = parse("STATE PROFILE").unwrap_err();
+        assert_eq!(err.to_string(), "DMSSTT054E Incomplete fileid specified");
+        assert_eq!(err.rc, 24);
+        let err = parse("ERASE A B C D").unwrap_err();
+        assert_eq!(err.to_string(), "DMSERS070E Invalid parameter D");
+        let err = parse("TYPE A B C (FOO").unwrap_err();
+        assert_eq!(err.to_string(), "DMSTYP070E Invalid parameter FOO");
+        assert!(parse("ACCESS /tmp *").is_err());
+    }
+}
@@ -39,4 +39,17 @@ impl From<io::Error> for CmsError {
     }
 }
 
+impl From<CmsError> for io::Error {
+    fn from(e: CmsError) -> Self {
+        let kind = match e {
+            CmsError::Io(inner) => return inner,
+            CmsError::FileNotFound(_) | CmsError::DiskNotAccessed(_) => io::ErrorKind::NotFound,
+            CmsError::FileExists(_) => io::ErrorKind::AlreadyExists,
+            CmsError::ReadOnly(_) => io::ErrorKind::PermissionDenied,
+            CmsError::InvalidFileSpec(_) => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, e.to_string())
+    }
+}
+
 pub type Result<T> = std::result::Result<T, CmsError>;
@@ -0,0 +1,286 @@
+//! REXX EXEC processor.
+//!
+//! `EXEC name args`, or a command line whose first word names a
+//! `name EXEC` file on an accessed disk, runs that file as a REXX program.
+//! Commands the program issues go back to the CMS command processor:
+//! under `ADDRESS CMS` (the default) they are resolved as the console
+//! resolves them, EXECs first; under `ADDRESS COMMAND` only CMS commands
+//! are run. The value of `EXIT n` is the EXEC's return code.
+//!
+//! As on CMS, commands issued from an EXEC write to the console as they
+//! run, so their output stays in order with the program's SAY output; an
+//! embedding without a console (`CommandProcessor::set_console`) gets
+//! their lines back ahead of the EXEC's own.
+//!
+//! GLOBALV GET and PUT set and store the EXEC's own variables (see
+//! `globalv`).
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use patch_rexx::env::Environment;
+use patch_rexx::error::{RexxDiagnostic, RexxError};
+use patch_rexx::eval::{Evaluator, ExecSignal};
+use patch_rexx::lexer::Lexer;
+use patch_rexx::parser::Parser;
+use patch_rexx::value::RexxValue;
+
+use crate::error::CmsError;
+use crate::filesystem::CmsFileSystem;
+use crate::message::DmsMessage;
+use crate::processor::{CommandOutput, CommandProcessor};
+
+/// Run the EXEC at `path` with `args` as its argument string.
+pub fn run_exec(cms: &mut CommandProcessor, name: &str, path: &Path, args: &str) -> CommandOutput {
+    let fileid = format!("{} EXEC", name.to_uppercase());
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => return DmsMessage::from_error("EXC", &CmsError::Io(e)).into(),
+    };
+    let program = match Lexer::new(&source)
+        .tokenize()
+        .and_then(|tokens| Parser::new(tokens).parse())
+    {
+        Ok(program) => program,
+        Err(diag) => return rexx_error(&fileid, &diag),
+    };
+
+    let mut env = Environment::new();
+    env.set_address("CMS");
+    env.set_source_path(path.to_path_buf());
+
+    // The command handler must be 'static, so the processor is moved into
+    // an Rc<RefCell> for the run and an empty one left in its place
+    let mut placeholder = CommandProcessor::new(CmsFileSystem::new());
+    std::mem::swap(cms, &mut placeholder);
+    let shared = Rc::new(RefCell::new(placeholder));
+    let command_lines = Rc::new(RefCell::new(Vec::new()));
+
+    let result = {
+        let mut evaluator = Evaluator::new(&mut env, &program);
+        if !args.is_empty() {
+            evaluator.set_main_args(vec![RexxValue::new(args)]);
+        }
+        let handle = Rc::clone(&shared);
+        let shown = Rc::clone(&command_lines);
+        evaluator.set_command_handler_with_env(Box::new(move |address, command, vars| {
+            let mut cms = handle.borrow_mut();
+            let output = match address.to_uppercase().as_str() {
+                "CMS" => cms.execute_in(command, vars),
+                "COMMAND" => cms.execute_command_in(command, vars),
+                _ => return None,
+            };
+            match cms.console() {
+                Some(console) => output.lines.iter().for_each(|line| console(line)),
+                None => shown.borrow_mut().extend(output.lines),
+            }
+            Some(output.rc)
+        }));
+        evaluator.exec()
+    };
+    let Ok(shared) = Rc::try_unwrap(shared) else {
+        unreachable!("the command handler is dropped with the evaluator");
+    };
+    *cms = shared.into_inner();
+
+    let mut output = match result {
+        Ok(ExecSignal::Exit(Some(value)) | ExecSignal::Return(Some(value))) => {
+            match value.as_str().trim().parse() {
+                Ok(rc) => CommandOutput {
+                    rc,
+                    ..CommandOutput::default()
+                },
+                // The result of a command must be a return code
+                Err(_) => {
+                    let diag = RexxDiagnostic::new(RexxError::InvalidWholeNumber)
+                        .with_detail(format!("'{}' is not a return code", value.as_str()));
+                    rexx_error(&fileid, &diag)
+                }
+            }
+        }
+        Ok(_) => CommandOutput::default(),
+        Err(diag) => rexx_error(&fileid, &diag),
+    };
+    output.lines.splice(0..0, command_lines.take());
+    output
+}
+
+/// DMSREX460E for a REXX error; the return code is 20000 plus the error number
+fn rexx_error(fileid: &str, diag: &RexxDiagnostic) -> CommandOutput {
+    let number = diag.error.number();
+    let line = diag
+        .location
+        .as_ref()
+        .map(|loc| format!(", line {}", loc.line))
+        .unwrap_or_default();
+    let mut text = format!(
+        "Error {} running {}{}: {}",
+        number,
+        fileid,
+        line,
+        diag.error.message()
+    );
+    if let Some(detail) = &diag.detail {
+        text.push_str(&format!(" ({})", detail));
+    }
+    DmsMessage::new("REX", 460, 'E', text, 20000 + number as i32).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filesystem::CmsFileSystem;
+    use crate::minidisk::AccessMode;
+    use crate::processor::{CommandProcessor, IplOptions};
+    use tempfile::TempDir;
+
+    fn processor() -> (TempDir, CommandProcessor) {
+        let dir = TempDir::new().unwrap();
+        let fs = CmsFileSystem::with_default_disk(dir.path()).unwrap();
+        (dir, CommandProcessor::new(fs))
+    }
+
+    fn write(dir: &TempDir, name: &str, source: &str) {
+        std::fs::write(dir.path().join("a").join(name), source).unwrap();
+    }
+
+    #[test]
+    fn exec_runs_cms_commands_and_returns_rc() {
+        let (dir, mut cms) = processor();
+        write(&dir, "data.file", "x\n");
+        write(
+            &dir,
+            "backup.exec",
+            "/* */\nparse arg fn ft\n'COPYFILE' fn ft 'A = BACKUP'\nif rc <> 0 then exit rc\n'STATE NOSUCH FILE'\nexit rc + 1\n",
+        );
+
+        let out = cms.execute("EXEC BACKUP DATA FILE");
+        assert_eq!(out.rc, 29);
+        // Output of the commands it issued comes back with the EXEC's
+        assert_eq!(out.lines, vec!["DMSSTT002E File NOSUCH FILE *1 not found"]);
+        assert!(dir.path().join("a/data.backup").exists());
+
+        // Implicit EXEC: the command word names the EXEC
+        let out = cms.execute("backup data file");
+        assert_eq!(out.rc, 28);
+    }
+
+    #[test]
+    fn implicit_exec_comes_before_commands_but_not_under_address_command() {
+        let (dir, mut cms) = processor();
+        write(&dir, "erase.exec", "exit 7\n");
+        write(&dir, "gone.data", "x\n");
+        write(&dir, "calls.exec", "'ERASE GONE DATA'\nfirst = rc\naddress command 'ERASE GONE DATA'\nexit first * 100 + rc\n");
+
+        assert_eq!(cms.execute("ERASE GONE DATA").rc, 7);
+        assert_eq!(cms.execute("CALLS").rc, 700);
+        assert!(!dir.path().join("a/gone.data").exists());
+    }
+
+    #[test]
+    fn exec_errors() {
+        let (dir, mut cms) = processor();
+        let out = cms.execute("EXEC NOSUCH");
+        assert_eq!(out.lines, vec!["DMSEXC002E File NOSUCH EXEC * not found"]);
+        assert_eq!(out.rc, 28);
+
+        write(&dir, "bad.exec", "say 'hi'\nsignal nowhere\n");
+        let out = cms.execute("BAD");
+        assert_eq!(
+            out.lines,
+            vec![
+                "DMSREX460E Error 16 running BAD EXEC: Label not found (label 'NOWHERE' not found)"
+            ]
+        );
+        assert_eq!(out.rc, 20016);
+
+        write(&dir, "word.exec", "exit 'done'\n");
+        let out = cms.execute("WORD");
+        assert_eq!(
+            out.lines,
+            vec![
+                "DMSREX460E Error 26 running WORD EXEC: Invalid whole number ('done' is not a return code)"
+            ]
+        );
+        assert_eq!(out.rc, 20026);
+    }
+
+    #[test]
+    fn execs_share_globalv_variables() {
+        let (dir, mut cms) = processor();
+        write(
+            &dir,
+            "remember.exec",
+            "parse arg file
+lastfile = file
+'GLOBALV SELECT EDIT PUTP LASTFILE'
+if rc <> 0 then exit rc
+'RECALL'
+exit rc
+",
+        );
+        write(
+            &dir,
+            "recall.exec",
+            "'GLOBALV SELECT EDIT GET LASTFILE'
+if lastfile = 'notes script' then exit 1
+exit 2
+",
+        );
+
+        // PUT stores the value at once, so an EXEC it calls can GET it
+        assert_eq!(cms.execute("REMEMBER notes script").rc, 1);
+        assert_eq!(cms.globals().get("EDIT", "LASTFILE"), Some("notes script"));
+        assert_eq!(cms.execute("RECALL").rc, 1);
+
+        // The lasting value outlives the session
+        let fs = CmsFileSystem::with_default_disk(dir.path()).unwrap();
+        let mut next = CommandProcessor::new(fs);
+        assert_eq!(next.execute("RECALL").rc, 1);
+        assert_eq!(next.execute("GLOBALV GET LASTFILE").rc, 24);
+    }
+
+    #[test]
+    fn ipl_accesses_disks_and_runs_profile() {
+        let (dir, mut cms) = processor();
+        let tools = dir.path().join("tools");
+        std::fs::create_dir(&tools).unwrap();
+        std::fs::write(tools.join("hello.exec"), "exit 3\n").unwrap();
+        write(&dir, "profile.exec", "'GLOBALV SETS STARTED yes'\n");
+
+        let options = IplOptions {
+            disks: vec![(tools.clone(), 'C', AccessMode::ReadOnly)],
+            noprof: true,
+        };
+        let out = cms.ipl(&options);
+        assert_eq!(out.rc, 0);
+        assert!(cms.filesystem().disk('C').is_some());
+        assert_eq!(cms.globals().get("UNNAMED", "STARTED"), None);
+
+        let (dir, mut cms) = processor();
+        write(
+            &dir,
+            "profile.exec",
+            "'GLOBALV SETS STARTED yes'\n'HELLO'\nexit rc\n",
+        );
+        let options = IplOptions {
+            disks: vec![
+                (tools, 'C', AccessMode::ReadWrite),
+                (
+                    dir.path().join("a/profile.exec"),
+                    'D',
+                    AccessMode::ReadWrite,
+                ),
+            ],
+            noprof: false,
+        };
+        let out = cms.ipl(&options);
+        assert_eq!(
+            out.lines,
+            vec!["DMSACC104S Error File exists (os error 17)"]
+        );
+        // PROFILE EXEC runs after the disks are accessed, and its rc is last
+        assert_eq!(out.rc, 3);
+        assert_eq!(cms.globals().get("UNNAMED", "STARTED"), Some("yes"));
+    }
+}
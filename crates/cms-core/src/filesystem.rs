@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 use std::io::BufRead;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::error::{CmsError, Result};
 use crate::filespec::FileSpec;
@@ -34,6 +34,7 @@ fn is_regular_file(path: &Path) -> bool {
 ///
 /// Manages a set of minidisks (A-Z) and provides CMS-style file operations.
 /// When a filemode letter is `*`, disks are searched in A-Z order.
+#[derive(Debug, Clone)]
 pub struct CmsFileSystem {
     disks: BTreeMap<char, Minidisk>,
 }
@@ -84,6 +85,49 @@ impl CmsFileSystem {
         self.disks.get(&letter.to_ascii_uppercase())
     }
 
+    /// Accessed disks in search order (A-Z).
+    pub fn disks(&self) -> impl Iterator<Item = &Minidisk> {
+        self.disks.values()
+    }
+
+    /// The disk a host path is on, if it is directly in a disk's directory.
+    pub fn disk_for_path(&self, path: &Path) -> Option<&Minidisk> {
+        let parent = path.parent()?;
+        self.disks.values().find(|disk| disk.path() == parent)
+    }
+
+    /// Find a file to edit: the first disk in search order that has it,
+    /// or else where it would be created (disk A when the filemode is `*`).
+    /// Returns the file's spec with its actual filemode, and its path.
+    pub fn locate_file(&self, spec: &FileSpec) -> Result<(FileSpec, PathBuf)> {
+        if spec.has_wildcards() {
+            return Err(CmsError::InvalidFileSpec(
+                "Cannot edit a wildcard filespec".into(),
+            ));
+        }
+        match self.resolve_spec(spec) {
+            Ok(found) => {
+                let path = self.resolve_file(&found)?;
+                Ok((found, path))
+            }
+            Err(CmsError::FileNotFound(_)) => {
+                let letter = match spec.mode_letter() {
+                    '*' => 'A',
+                    letter => letter,
+                };
+                let disk = self.disk(letter).ok_or(CmsError::DiskNotAccessed(letter))?;
+                let spec = FileSpec::new(
+                    spec.filename(),
+                    spec.filetype(),
+                    &format!("{}{}", letter, spec.mode_number()),
+                )?;
+                let path = disk.file_path(spec.filename(), spec.filetype());
+                Ok((spec, path))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Read file contents. If the filemode is `*`, search disks A-Z.
     pub fn read_file(&self, spec: &FileSpec) -> Result<String> {
         let path = self.resolve_file(spec)?;
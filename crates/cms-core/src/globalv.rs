@@ -0,0 +1,514 @@
+//! GLOBALV: named groups of variables that outlive the programs that set them.
+//!
+//! `GLOBALV [SELECT group] function ...` works on one group, `UNNAMED`
+//! when no group is selected. SET and SETS values last for the session;
+//! SETL values are also written to `LASTING GLOBALV A` and read back by
+//! the next session, one variable per line, so a lasting value cannot hold
+//! a line end. SETP, SETSP and SETLP take name/value pairs.
+//!
+//! PUT, PUTS and PUTP store a calling program's variables in the group,
+//! and GET sets them from it, so they only mean something inside an EXEC
+//! or XEDIT macro. A variable the program never set is stored as null,
+//! dropping it; GET sets a variable the group lacks to null.
+
+use std::collections::BTreeMap;
+
+use xedit_core::host::Variables;
+
+use crate::error::CmsError;
+use crate::filespec::FileSpec;
+use crate::filesystem::CmsFileSystem;
+use crate::message::DmsMessage;
+
+/// The group used when none is selected
+pub const DEFAULT_GROUP: &str = "UNNAMED";
+
+const MODULE: &str = "GLV";
+
+/// How long a variable lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Until LOGOFF
+    Session,
+    /// Across sessions, in `LASTING GLOBALV A`
+    Lasting,
+}
+
+/// What a GLOBALV command does to its group
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlobalvAction {
+    /// SET/SETS/SETL name value, SETP/SETSP/SETLP name value ... — an
+    /// empty value drops the variable
+    Set {
+        scope: Scope,
+        pairs: Vec<(String, String)>,
+    },
+    /// PUT/PUTS/PUTP name ... — store the calling program's variables
+    Put { scope: Scope, names: Vec<String> },
+    /// GET name ... — set the calling program's variables
+    Get(Vec<String>),
+    /// LIST [name ...] — show variables, all of the group's by default
+    List(Vec<String>),
+    /// PURGE — drop every variable in the group
+    Purge,
+}
+
+/// A parsed GLOBALV command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalvCommand {
+    pub group: String,
+    pub action: GlobalvAction,
+}
+
+/// Parse the operands of GLOBALV. Values keep their case; group and
+/// variable names are uppercased.
+pub fn parse(args: &str) -> Result<GlobalvCommand, DmsMessage> {
+    let (mut word, mut rest) = next_word(args);
+    let mut group = DEFAULT_GROUP.to_string();
+    if word.eq_ignore_ascii_case("SELECT") {
+        let (name, after) = next_word(rest);
+        if name.is_empty() {
+            return Err(DmsMessage::new(
+                MODULE,
+                2,
+                'E',
+                "No group name specified".into(),
+                24,
+            ));
+        }
+        group = name.to_uppercase();
+        (word, rest) = next_word(after);
+    }
+    let names = || -> Vec<String> { rest.split_whitespace().map(str::to_uppercase).collect() };
+    let action = match word.to_uppercase().as_str() {
+        "" => {
+            return Err(DmsMessage::new(
+                MODULE,
+                1,
+                'E',
+                "No function specified".into(),
+                24,
+            ))
+        }
+        function @ ("SET" | "SETS" | "SETL") => {
+            let (name, value) = next_word(rest);
+            if name.is_empty() {
+                return Err(no_names());
+            }
+            GlobalvAction::Set {
+                scope: set_scope(function),
+                pairs: vec![(name.to_uppercase(), value.to_string())],
+            }
+        }
+        function @ ("SETP" | "SETSP" | "SETLP") => {
+            let words: Vec<&str> = rest.split_whitespace().collect();
+            if words.is_empty() {
+                return Err(no_names());
+            }
+            let pairs = words
+                .chunks(2)
+                .map(|pair| {
+                    let value = pair.get(1).copied().unwrap_or_default();
+                    (pair[0].to_uppercase(), value.to_string())
+                })
+                .collect();
+            GlobalvAction::Set {
+                scope: set_scope(function.trim_end_matches('P')),
+                pairs,
+            }
+        }
+        function @ ("PUT" | "PUTS" | "PUTP") => {
+            let names = names();
+            if names.is_empty() {
+                return Err(no_names());
+            }
+            let scope = match function {
+                "PUTP" => Scope::Lasting,
+                _ => Scope::Session,
+            };
+            GlobalvAction::Put { scope, names }
+        }
+        "GET" => {
+            let names = names();
+            if names.is_empty() {
+                return Err(no_names());
+            }
+            GlobalvAction::Get(names)
+        }
+        "LIST" => GlobalvAction::List(names()),
+        "PURGE" => {
+            if let Some(extra) = rest.split_whitespace().next() {
+                return Err(DmsMessage::invalid_parameter(MODULE, extra));
+            }
+            GlobalvAction::Purge
+        }
+        _ => return Err(DmsMessage::invalid_parameter(MODULE, word)),
+    };
+    Ok(GlobalvCommand { group, action })
+}
+
+/// The first blank-delimited word and the text after its delimiting blank
+fn next_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(pos) => (&text[..pos], &text[pos + 1..]),
+        None => (text, ""),
+    }
+}
+
+fn set_scope(function: &str) -> Scope {
+    match function {
+        "SETL" => Scope::Lasting,
+        _ => Scope::Session,
+    }
+}
+
+fn no_names() -> DmsMessage {
+    DmsMessage::new(MODULE, 3, 'E', "No variable name specified".into(), 24)
+}
+
+fn lasting_file() -> FileSpec {
+    FileSpec::new("LASTING", "GLOBALV", "A").expect("valid fileid")
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    value: String,
+    scope: Scope,
+}
+
+/// The GLOBALV variables of a session
+#[derive(Debug, Clone, Default)]
+pub struct GlobalVars {
+    groups: BTreeMap<String, BTreeMap<String, Variable>>,
+    /// `LASTING GLOBALV A` has been read; it is read on first use, so a
+    /// session may still ACCESS its A disk before then
+    loaded: bool,
+}
+
+impl GlobalVars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A variable's value
+    pub fn get(&self, group: &str, name: &str) -> Option<&str> {
+        self.groups
+            .get(&group.to_uppercase())?
+            .get(&name.to_uppercase())
+            .map(|var| var.value.as_str())
+    }
+
+    /// Set a variable in memory; an empty value drops it
+    pub fn set(&mut self, group: &str, name: &str, value: &str, scope: Scope) {
+        let group = group.to_uppercase();
+        let name = name.to_uppercase();
+        if value.is_empty() {
+            if let Some(vars) = self.groups.get_mut(&group) {
+                vars.remove(&name);
+                if vars.is_empty() {
+                    self.groups.remove(&group);
+                }
+            }
+        } else {
+            let var = Variable {
+                value: value.to_string(),
+                scope,
+            };
+            self.groups.entry(group).or_default().insert(name, var);
+        }
+    }
+
+    /// Run a GLOBALV command; `program` is the calling EXEC's or macro's
+    /// variables, for GET and PUT. Changes to lasting variables are saved
+    /// to `LASTING GLOBALV A` straight away.
+    pub fn execute(
+        &mut self,
+        cmd: &GlobalvCommand,
+        fs: &CmsFileSystem,
+        program: Option<&mut dyn Variables>,
+    ) -> Result<Vec<String>, DmsMessage> {
+        self.load(fs);
+        let group = cmd.group.as_str();
+        let before = self.lasting_records();
+        match &cmd.action {
+            GlobalvAction::Set { scope, pairs } => {
+                check_values(*scope, pairs)?;
+                for (name, value) in pairs {
+                    self.set(group, name, value, *scope);
+                }
+            }
+            GlobalvAction::Put { scope, names } => {
+                let program = program.ok_or_else(no_program)?;
+                let pairs: Vec<(String, String)> = names
+                    .iter()
+                    .map(|name| (name.clone(), program.get(name).unwrap_or_default()))
+                    .collect();
+                check_values(*scope, &pairs)?;
+                for (name, value) in &pairs {
+                    self.set(group, name, value, *scope);
+                }
+            }
+            GlobalvAction::Get(names) => {
+                let program = program.ok_or_else(no_program)?;
+                for name in names {
+                    program.set(name, self.get(group, name).unwrap_or_default());
+                }
+            }
+            GlobalvAction::List(names) => {
+                let vars = self.groups.get(group);
+                let lines = match names.as_slice() {
+                    [] => vars
+                        .into_iter()
+                        .flatten()
+                        .map(|(name, var)| format!("{:<8} {}", name, var.value))
+                        .collect(),
+                    names => names
+                        .iter()
+                        .map(|name| {
+                            let value = self.get(group, name).unwrap_or_default();
+                            format!("{:<8} {}", name, value)
+                        })
+                        .collect(),
+                };
+                return Ok(lines);
+            }
+            GlobalvAction::Purge => {
+                self.groups.remove(group);
+            }
+        }
+        if self.lasting_records() != before {
+            self.save(fs)?;
+        }
+        Ok(Vec::new())
+    }
+
+    /// Read `LASTING GLOBALV A` once per session. Its records are
+    /// `group name value`.
+    fn load(&mut self, fs: &CmsFileSystem) {
+        if self.loaded {
+            return;
+        }
+        self.loaded = true;
+        let Ok(content) = fs.read_file(&lasting_file()) else {
+            return;
+        };
+        for record in content.lines() {
+            let (group, rest) = next_word(record);
+            let (name, value) = next_word(rest);
+            if !name.is_empty() {
+                self.set(group, name, value, Scope::Lasting);
+            }
+        }
+    }
+
+    /// The contents of `LASTING GLOBALV A`
+    fn lasting_records(&self) -> String {
+        let mut records = String::new();
+        for (group, vars) in &self.groups {
+            for (name, var) in vars {
+                if var.scope == Scope::Lasting {
+                    records.push_str(&format!("{} {} {}\n", group, name, var.value));
+                }
+            }
+        }
+        records
+    }
+
+    fn save(&self, fs: &CmsFileSystem) -> Result<(), DmsMessage> {
+        let records = self.lasting_records();
+        let file = lasting_file();
+        let result = if records.is_empty() {
+            match fs.erase(&file) {
+                Err(CmsError::FileNotFound(_)) => Ok(()),
+                result => result,
+            }
+        } else {
+            fs.write_file(&file, &records)
+        };
+        result.map_err(|e| DmsMessage::from_error(MODULE, &e))
+    }
+}
+
+/// Refuse a lasting value that would not read back as one record
+fn check_values(scope: Scope, pairs: &[(String, String)]) -> Result<(), DmsMessage> {
+    if scope != Scope::Lasting {
+        return Ok(());
+    }
+    match pairs.iter().find(|(_, value)| value.contains(['\n', '\r'])) {
+        Some((name, _)) => Err(DmsMessage::new(
+            MODULE,
+            5,
+            'E',
+            format!(
+                "Lasting value of {} contains a line end",
+                name.to_uppercase()
+            ),
+            24,
+        )),
+        None => Ok(()),
+    }
+}
+
+fn no_program() -> DmsMessage {
+    DmsMessage::new(
+        MODULE,
+        4,
+        'E',
+        "GET and PUT are valid only from an EXEC or macro".into(),
+        24,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    /// A program's variable pool
+    #[derive(Default)]
+    struct Pool(HashMap<String, String>);
+
+    impl Variables for Pool {
+        fn get(&self, name: &str) -> Option<String> {
+            self.0.get(name).cloned()
+        }
+
+        fn set(&mut self, name: &str, value: &str) {
+            self.0.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    fn run(
+        vars: &mut GlobalVars,
+        fs: &CmsFileSystem,
+        line: &str,
+    ) -> Result<Vec<String>, DmsMessage> {
+        vars.execute(&parse(line)?, fs, None)
+    }
+
+    fn run_in(
+        vars: &mut GlobalVars,
+        fs: &CmsFileSystem,
+        line: &str,
+        program: &mut Pool,
+    ) -> Result<Vec<String>, DmsMessage> {
+        vars.execute(&parse(line)?, fs, Some(program))
+    }
+
+    #[test]
+    fn parse_functions() {
+        let cmd = parse("select Prof setl lastfile Profile Exec A").unwrap();
+        assert_eq!(cmd.group, "PROF");
+        assert_eq!(
+            cmd.action,
+            GlobalvAction::Set {
+                scope: Scope::Lasting,
+                pairs: vec![("LASTFILE".into(), "Profile Exec A".into())],
+            }
+        );
+        let cmd = parse("SETLP a 1 b").unwrap();
+        assert_eq!(cmd.group, DEFAULT_GROUP);
+        assert_eq!(
+            cmd.action,
+            GlobalvAction::Set {
+                scope: Scope::Lasting,
+                pairs: vec![("A".into(), "1".into()), ("B".into(), String::new())],
+            }
+        );
+        assert_eq!(
+            parse("PUTP x y").unwrap().action,
+            GlobalvAction::Put {
+                scope: Scope::Lasting,
+                names: vec!["X".into(), "Y".into()],
+            }
+        );
+
+        assert_eq!(
+            parse("").unwrap_err().to_string(),
+            "DMSGLV001E No function specified"
+        );
+        assert_eq!(parse("SELECT").unwrap_err().number, 2);
+        assert_eq!(parse("GET").unwrap_err().number, 3);
+        assert_eq!(
+            parse("FROB X").unwrap_err().to_string(),
+            "DMSGLV070E Invalid parameter FROB"
+        );
+    }
+
+    #[test]
+    fn session_and_lasting_values() {
+        let dir = TempDir::new().unwrap();
+        let fs = CmsFileSystem::with_default_disk(dir.path()).unwrap();
+        let mut vars = GlobalVars::new();
+        run(&mut vars, &fs, "SETS COLOR blue").unwrap();
+        run(&mut vars, &fs, "SELECT EDIT SETL LASTFILE notes script").unwrap();
+        assert_eq!(vars.get("edit", "lastfile"), Some("notes script"));
+        assert_eq!(
+            run(&mut vars, &fs, "SELECT EDIT LIST").unwrap(),
+            vec!["LASTFILE notes script"]
+        );
+        let file = dir.path().join("a/lasting.globalv");
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "EDIT LASTFILE notes script\n"
+        );
+
+        // A new session sees only the lasting value
+        let mut next = GlobalVars::new();
+        assert_eq!(
+            run(&mut next, &fs, "LIST COLOR").unwrap(),
+            vec!["COLOR    "]
+        );
+        assert_eq!(next.get("EDIT", "LASTFILE"), Some("notes script"));
+
+        // Each lasting value is one line of the file
+        let err = run(&mut next, &fs, "SETL NOTE two\nlines").unwrap_err();
+        assert_eq!(err.number, 5);
+        assert_eq!(next.get(DEFAULT_GROUP, "NOTE"), None);
+        run(&mut next, &fs, "SETS NOTE two\nlines").unwrap();
+
+        run(&mut next, &fs, "SELECT EDIT PURGE").unwrap();
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn get_and_put_use_the_programs_variables() {
+        let dir = TempDir::new().unwrap();
+        let fs = CmsFileSystem::with_default_disk(dir.path()).unwrap();
+        let mut vars = GlobalVars::new();
+        assert_eq!(run(&mut vars, &fs, "GET X").unwrap_err().number, 4);
+
+        run(&mut vars, &fs, "SETP SHARED one").unwrap();
+        run(&mut vars, &fs, "SELECT OTHER SETP SHARED two").unwrap();
+        let mut program = Pool::default();
+        program.set("STALE", "old");
+        run_in(
+            &mut vars,
+            &fs,
+            "SELECT OTHER GET SHARED STALE",
+            &mut program,
+        )
+        .unwrap();
+        assert_eq!(program.get("SHARED").as_deref(), Some("two"));
+        assert_eq!(program.get("STALE").as_deref(), Some(""));
+
+        program.set("ONLY", "changed");
+        run_in(&mut vars, &fs, "SELECT OTHER PUTP ONLY UNSET", &mut program).unwrap();
+        assert_eq!(vars.get("OTHER", "ONLY"), Some("changed"));
+        assert_eq!(vars.get("OTHER", "UNSET"), None);
+        assert_eq!(vars.get(DEFAULT_GROUP, "ONLY"), None);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a/lasting.globalv")).unwrap(),
+            "OTHER ONLY changed\n"
+        );
+
+        // Nothing is stored if any lasting value has a line end
+        program.set("ONLY", "again");
+        program.set("LIST", "one\ntwo");
+        let err = run_in(&mut vars, &fs, "SELECT OTHER PUTP ONLY LIST", &mut program);
+        assert_eq!(err.unwrap_err().number, 5);
+        assert_eq!(vars.get("OTHER", "ONLY"), Some("changed"));
+    }
+}
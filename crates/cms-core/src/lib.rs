@@ -1,9 +1,20 @@
+pub mod command;
 pub mod error;
+#[cfg(feature = "rexx")]
+pub mod exec;
 pub mod filespec;
 pub mod filesystem;
+pub mod globalv;
+pub mod message;
 pub mod minidisk;
+pub mod processor;
+pub mod xedit;
 
+pub use command::{CmsCommand, QueryItem};
 pub use error::{CmsError, Result};
 pub use filespec::FileSpec;
 pub use filesystem::{CmsFileSystem, FileInfo};
+pub use globalv::GlobalVars;
+pub use message::DmsMessage;
 pub use minidisk::{AccessMode, Minidisk};
+pub use processor::{ready_message, CommandOutput, CommandProcessor, IplOptions};
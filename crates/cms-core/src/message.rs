@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::error::CmsError;
+
+/// An IBM-style CMS message: `DMSLST002E File not found`.
+///
+/// The message id is `DMS`, a three-letter module code for the command,
+/// a message number and a severity letter (I, W, E, S). Error messages
+/// carry the command's return code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmsMessage {
+    pub module: &'static str,
+    pub number: u16,
+    pub severity: char,
+    pub text: String,
+    pub rc: i32,
+}
+
+impl DmsMessage {
+    pub fn new(module: &'static str, number: u16, severity: char, text: String, rc: i32) -> Self {
+        DmsMessage {
+            module,
+            number,
+            severity,
+            text,
+            rc,
+        }
+    }
+
+    /// An informational message (return code 0)
+    pub fn info(module: &'static str, number: u16, text: String) -> Self {
+        Self::new(module, number, 'I', text, 0)
+    }
+
+    /// DMSxxx070E Invalid parameter
+    pub fn invalid_parameter(module: &'static str, parameter: &str) -> Self {
+        Self::new(
+            module,
+            70,
+            'E',
+            format!("Invalid parameter {}", parameter),
+            24,
+        )
+    }
+
+    /// DMSxxx054E Incomplete fileid specified
+    pub fn incomplete_fileid(module: &'static str) -> Self {
+        Self::new(module, 54, 'E', "Incomplete fileid specified".into(), 24)
+    }
+
+    /// DMSxxx002E File not found
+    pub fn not_found(module: &'static str, fileid: &str) -> Self {
+        Self::new(module, 2, 'E', format!("File {} not found", fileid), 28)
+    }
+
+    /// Report a file system error as the message CMS gives for it
+    pub fn from_error(module: &'static str, error: &CmsError) -> Self {
+        match error {
+            CmsError::InvalidFileSpec(msg) => Self::new(module, 62, 'E', msg.clone(), 20),
+            CmsError::FileNotFound(name) => Self::not_found(module, name),
+            CmsError::DiskNotAccessed(letter) => Self::new(
+                module,
+                69,
+                'E',
+                format!("Filemode {} not accessed", letter),
+                36,
+            ),
+            CmsError::FileExists(name) => {
+                Self::new(module, 24, 'E', format!("File {} already exists", name), 28)
+            }
+            CmsError::ReadOnly(letter) => Self::new(
+                module,
+                37,
+                'E',
+                format!("Filemode {} is accessed as read/only", letter),
+                36,
+            ),
+            CmsError::Io(e) => Self::new(module, 104, 'S', format!("Error {}", e), 100),
+        }
+    }
+}
+
+impl fmt::Display for DmsMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DMS{}{:03}{} {}",
+            self.module, self.number, self.severity, self.text
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_ids() {
+        let msg = DmsMessage::from_error("CPY", &CmsError::ReadOnly('B'));
+        assert_eq!(
+            msg.to_string(),
+            "DMSCPY037E Filemode B is accessed as read/only"
+        );
+        assert_eq!(msg.rc, 36);
+        let msg = DmsMessage::not_found("STT", "NOFILE DATA A1");
+        assert_eq!(msg.to_string(), "DMSSTT002E File NOFILE DATA A1 not found");
+        assert_eq!(msg.rc, 28);
+    }
+}
@@ -0,0 +1,483 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use xedit_core::host::Variables;
+
+use crate::command::{parse_command, CmsCommand, QueryItem};
+use crate::filespec::FileSpec;
+use crate::filesystem::CmsFileSystem;
+use crate::globalv::GlobalVars;
+use crate::message::DmsMessage;
+use crate::minidisk::{AccessMode, Minidisk};
+
+/// What a command line produced: lines for the console and a return code
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    pub lines: Vec<String>,
+    pub rc: i32,
+    /// LOGOFF was entered
+    pub logoff: bool,
+}
+
+impl From<DmsMessage> for CommandOutput {
+    fn from(msg: DmsMessage) -> Self {
+        CommandOutput {
+            lines: vec![msg.to_string()],
+            rc: msg.rc,
+            logoff: false,
+        }
+    }
+}
+
+/// What IPL CMS does before the first Ready;
+#[derive(Debug, Clone, Default)]
+pub struct IplOptions {
+    /// Disks to access (directory, filemode letter and access mode), in order
+    pub disks: Vec<(PathBuf, char, AccessMode)>,
+    /// NOPROF: skip PROFILE EXEC
+    pub noprof: bool,
+}
+
+/// The CMS command processor: runs command lines against the accessed disks.
+#[derive(Debug)]
+pub struct CommandProcessor {
+    fs: CmsFileSystem,
+    globals: GlobalVars,
+    /// Where commands issued from an EXEC write as they run
+    console: Option<fn(&str)>,
+}
+
+impl CommandProcessor {
+    pub fn new(fs: CmsFileSystem) -> Self {
+        CommandProcessor {
+            fs,
+            globals: GlobalVars::new(),
+            console: None,
+        }
+    }
+
+    /// Write the output of commands issued from an EXEC to `console` as
+    /// they run, in order with the EXEC's SAY output. Without a console it
+    /// is returned with the EXEC's own output.
+    pub fn set_console(&mut self, console: fn(&str)) {
+        self.console = Some(console);
+    }
+
+    #[cfg(feature = "rexx")]
+    pub(crate) fn console(&self) -> Option<fn(&str)> {
+        self.console
+    }
+
+    pub fn filesystem(&self) -> &CmsFileSystem {
+        &self.fs
+    }
+
+    pub fn filesystem_mut(&mut self) -> &mut CmsFileSystem {
+        &mut self.fs
+    }
+
+    /// The session's GLOBALV variables
+    pub fn globals(&self) -> &GlobalVars {
+        &self.globals
+    }
+
+    /// Run one command line. A first word naming an EXEC on an accessed
+    /// disk runs that EXEC; otherwise it is a CMS command. Unknown commands
+    /// give return code -3.
+    pub fn execute(&mut self, line: &str) -> CommandOutput {
+        self.execute_with(line, None)
+    }
+
+    /// Run a command line issued by an EXEC or XEDIT macro, whose
+    /// variables GLOBALV GET and PUT use
+    pub fn execute_in(&mut self, line: &str, program: &mut dyn Variables) -> CommandOutput {
+        self.execute_with(line, Some(program))
+    }
+
+    fn execute_with(&mut self, line: &str, program: Option<&mut dyn Variables>) -> CommandOutput {
+        #[cfg(feature = "rexx")]
+        {
+            let line = line.trim();
+            let (word, args) = match line.find(char::is_whitespace) {
+                Some(pos) => (&line[..pos], line[pos..].trim()),
+                None => (line, ""),
+            };
+            if let Some(path) = self.find_exec(word) {
+                return crate::exec::run_exec(self, word, &path, args);
+            }
+        }
+        self.execute_command_with(line, program)
+    }
+
+    /// Run a CMS command without looking for an EXEC of the same name,
+    /// as REXX `ADDRESS COMMAND` does
+    pub fn execute_command(&mut self, line: &str) -> CommandOutput {
+        self.execute_command_with(line, None)
+    }
+
+    /// `execute_command` for an EXEC, whose variables GLOBALV GET and PUT use
+    pub fn execute_command_in(&mut self, line: &str, program: &mut dyn Variables) -> CommandOutput {
+        self.execute_command_with(line, Some(program))
+    }
+
+    fn execute_command_with(
+        &mut self,
+        line: &str,
+        program: Option<&mut dyn Variables>,
+    ) -> CommandOutput {
+        let cmd = match parse_command(line) {
+            None => {
+                return CommandOutput {
+                    lines: vec!["Unknown CP/CMS command".to_string()],
+                    rc: -3,
+                    logoff: false,
+                }
+            }
+            Some(Err(msg)) => return msg.into(),
+            Some(Ok(CmsCommand::Exec { name, args })) => return self.exec(&name, &args),
+            Some(Ok(cmd)) => cmd,
+        };
+        match self.run(&cmd, program) {
+            Ok(lines) => CommandOutput {
+                lines,
+                rc: 0,
+                logoff: cmd == CmsCommand::Logoff,
+            },
+            Err(msg) => msg.into(),
+        }
+    }
+
+    /// Find `name EXEC` on the accessed disks, in search order
+    pub fn find_exec(&self, name: &str) -> Option<PathBuf> {
+        let spec = FileSpec::new(name, "EXEC", "*").ok()?;
+        let (_spec, path) = self.fs.locate_file(&spec).ok()?;
+        path.is_file().then_some(path)
+    }
+
+    fn exec(&mut self, name: &str, args: &str) -> CommandOutput {
+        match self.find_exec(name) {
+            Some(path) => self.run_exec(name, &path, args),
+            None => DmsMessage::not_found("EXC", &format!("{} EXEC *", name)).into(),
+        }
+    }
+
+    #[cfg(feature = "rexx")]
+    fn run_exec(&mut self, name: &str, path: &Path, args: &str) -> CommandOutput {
+        crate::exec::run_exec(self, name, path, args)
+    }
+
+    #[cfg(not(feature = "rexx"))]
+    fn run_exec(&mut self, _name: &str, _path: &Path, _args: &str) -> CommandOutput {
+        DmsMessage::new("EXC", 1, 'E', "REXX support is not built in".into(), -3).into()
+    }
+
+    /// Start the session as IPL CMS does: ACCESS each of `options.disks`,
+    /// then run `PROFILE EXEC A` if there is one, unless NOPROF. The
+    /// return code is that of the last step that failed.
+    pub fn ipl(&mut self, options: &IplOptions) -> CommandOutput {
+        let mut output = CommandOutput::default();
+        let mut step = |out: CommandOutput| {
+            output.lines.extend(out.lines);
+            if out.rc != 0 {
+                output.rc = out.rc;
+            }
+        };
+        for (path, letter, access) in &options.disks {
+            step(match self.access(path, *letter, *access) {
+                Ok(lines) => CommandOutput {
+                    lines,
+                    ..CommandOutput::default()
+                },
+                Err(msg) => msg.into(),
+            });
+        }
+        if !options.noprof {
+            let spec = FileSpec::new("PROFILE", "EXEC", "A").expect("valid fileid");
+            if let Ok((_spec, path)) = self.fs.locate_file(&spec) {
+                if path.is_file() {
+                    step(self.run_exec("PROFILE", &path, ""));
+                }
+            }
+        }
+        output
+    }
+
+    /// ACCESS: mount `path` as disk `letter`
+    fn access(
+        &mut self,
+        path: &Path,
+        letter: char,
+        access: AccessMode,
+    ) -> Result<Vec<String>, DmsMessage> {
+        self.fs
+            .access_disk(letter, path, access)
+            .map_err(|e| DmsMessage::from_error("ACC", &e))?;
+        let disk = self.fs.disk(letter).expect("disk just accessed");
+        if disk.is_writable() {
+            Ok(Vec::new())
+        } else {
+            let msg = format!("{} ({}) R/O", disk.letter(), disk.path().display());
+            Ok(vec![DmsMessage::info("ACC", 723, msg).to_string()])
+        }
+    }
+
+    fn run(
+        &mut self,
+        cmd: &CmsCommand,
+        program: Option<&mut dyn Variables>,
+    ) -> Result<Vec<String>, DmsMessage> {
+        let module = cmd.module();
+        let fail = |e| DmsMessage::from_error(module, &e);
+        match cmd {
+            CmsCommand::Access {
+                path,
+                letter,
+                access,
+            } => self.access(path, *letter, *access),
+            CmsCommand::Globalv(cmd) => self.globals.execute(cmd, &self.fs, program),
+            CmsCommand::Release(letter) => {
+                if self.fs.disk(*letter).is_none() {
+                    return Err(fail(crate::CmsError::DiskNotAccessed(*letter)));
+                }
+                self.fs.release_disk(*letter);
+                Ok(Vec::new())
+            }
+            CmsCommand::Copyfile { from, to, replace } => {
+                if !replace && self.fs.state(to).is_ok() {
+                    return Err(fail(crate::CmsError::FileExists(to.to_string())));
+                }
+                self.fs.copyfile(from, to).map_err(fail)?;
+                Ok(Vec::new())
+            }
+            CmsCommand::Rename { from, to } => {
+                self.fs.rename(from, to).map_err(fail)?;
+                Ok(Vec::new())
+            }
+            CmsCommand::Erase(spec) => {
+                if !spec.has_wildcards() {
+                    self.fs.erase(spec).map_err(fail)?;
+                    return Ok(Vec::new());
+                }
+                let files = self.fs.listfile(spec).map_err(fail)?;
+                if files.is_empty() {
+                    return Err(DmsMessage::not_found(module, &spec.to_string()));
+                }
+                for file in files {
+                    self.fs.erase(&file.spec).map_err(fail)?;
+                }
+                Ok(Vec::new())
+            }
+            CmsCommand::Listfile(pattern) => {
+                let files = self.fs.listfile(pattern).map_err(fail)?;
+                if files.is_empty() {
+                    return Err(DmsMessage::not_found(module, &pattern.to_string()));
+                }
+                Ok(files.iter().map(|file| listfile_line(&file.spec)).collect())
+            }
+            CmsCommand::State(spec) => {
+                self.fs.state(spec).map_err(fail)?;
+                Ok(Vec::new())
+            }
+            CmsCommand::Type(spec) => {
+                let content = self.fs.read_file(spec).map_err(fail)?;
+                Ok(content.lines().map(String::from).collect())
+            }
+            CmsCommand::Query(QueryItem::Disk(letter)) => {
+                let disks: Vec<&Minidisk> = self
+                    .fs
+                    .disks()
+                    .filter(|disk| *letter == '*' || disk.letter() == *letter)
+                    .collect();
+                if disks.is_empty() {
+                    return Err(fail(crate::CmsError::DiskNotAccessed(*letter)));
+                }
+                let mut lines = vec!["MODE  STAT  FILES  DIRECTORY".to_string()];
+                for disk in disks {
+                    let pattern =
+                        FileSpec::new("*", "*", &disk.letter().to_string()).expect("valid pattern");
+                    let files = self.fs.listfile(&pattern).map_or(0, |files| files.len());
+                    lines.push(format!(
+                        "{}     {}  {:>5}  {}",
+                        disk.letter(),
+                        disk_status(disk),
+                        files,
+                        disk.path().display()
+                    ));
+                }
+                Ok(lines)
+            }
+            CmsCommand::Query(QueryItem::Search) => Ok(self
+                .fs
+                .disks()
+                .map(|disk| {
+                    format!(
+                        "{}     {}  {}",
+                        disk.letter(),
+                        disk_status(disk),
+                        disk.path().display()
+                    )
+                })
+                .collect()),
+            CmsCommand::Logoff | CmsCommand::Exec { .. } => Ok(Vec::new()),
+        }
+    }
+}
+
+/// A LISTFILE line: `PROFILE  EXEC     A1`
+fn listfile_line(spec: &FileSpec) -> String {
+    format!(
+        "{:<8} {:<8} {}",
+        spec.filename(),
+        spec.filetype(),
+        spec.filemode()
+    )
+}
+
+fn disk_status(disk: &Minidisk) -> &'static str {
+    if disk.is_writable() {
+        "R/W"
+    } else {
+        "R/O"
+    }
+}
+
+/// The console's prompt after a command: `Ready; T=0.01/0.01`, or
+/// `Ready(00028); T=0.01/0.01` when the return code is not zero. The
+/// virtual and total CPU times are both the command's elapsed time.
+pub fn ready_message(rc: i32, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64().max(0.01);
+    let times = format!("T={:.2}/{:.2}", secs, secs);
+    match rc {
+        0 => format!("Ready; {}", times),
+        rc if rc < 0 => format!("Ready(-{:04}); {}", -rc, times),
+        rc => format!("Ready({:05}); {}", rc, times),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minidisk::AccessMode;
+    use tempfile::TempDir;
+
+    fn processor() -> (TempDir, CommandProcessor) {
+        let dir = TempDir::new().unwrap();
+        let fs = CmsFileSystem::with_default_disk(dir.path()).unwrap();
+        (dir, CommandProcessor::new(fs))
+    }
+
+    #[test]
+    fn ready_prompt() {
+        assert_eq!(ready_message(0, Duration::ZERO), "Ready; T=0.01/0.01");
+        assert_eq!(
+            ready_message(28, Duration::from_millis(1234)),
+            "Ready(00028); T=1.23/1.23"
+        );
+        assert_eq!(
+            ready_message(-3, Duration::ZERO),
+            "Ready(-0003); T=0.01/0.01"
+        );
+    }
+
+    #[test]
+    fn file_commands() {
+        let (dir, mut cms) = processor();
+        std::fs::write(dir.path().join("a/profile.exec"), "/* */\nsay 'hi'\n").unwrap();
+
+        let out = cms.execute("listf * * a");
+        assert_eq!(out.lines, vec!["PROFILE  EXEC     A1"]);
+        assert_eq!(out.rc, 0);
+
+        assert_eq!(cms.execute("COPY PROFILE EXEC A = OLD").rc, 0);
+        let out = cms.execute("COPYFILE PROFILE EXEC A = OLD");
+        assert_eq!(
+            out.lines,
+            vec!["DMSCPY024E File PROFILE OLD A1 already exists"]
+        );
+        assert_eq!(out.rc, 28);
+        assert_eq!(cms.execute("COPYFILE PROFILE EXEC A = OLD (REPLACE").rc, 0);
+
+        assert_eq!(cms.execute("RENAME PROFILE OLD A = SAVED").rc, 0);
+        assert_eq!(cms.execute("STATE PROFILE OLD").rc, 28);
+        assert_eq!(
+            cms.execute("TYPE PROFILE SAVED").lines,
+            vec!["/* */", "say 'hi'"]
+        );
+
+        assert_eq!(cms.execute("ERASE * SAVED").rc, 0);
+        let out = cms.execute("STATE PROFILE SAVED");
+        assert_eq!(
+            out.lines,
+            vec!["DMSSTT002E File PROFILE SAVED *1 not found"]
+        );
+        assert_eq!(out.rc, 28);
+    }
+
+    #[test]
+    fn disk_commands() {
+        let (dir, mut cms) = processor();
+        let out = cms.execute(&format!("ACCESS {} C2", dir.path().join("c").display()));
+        assert_eq!(out.rc, 0);
+        assert!(out.lines[0].starts_with("DMSACC723I C ("));
+        assert_eq!(cms.execute("Q SEARCH").lines.len(), 2);
+
+        let out = cms.execute("COPYFILE NONE DATA A = = C");
+        assert_eq!(out.rc, 28);
+        std::fs::write(dir.path().join("a/some.data"), "x\n").unwrap();
+        let out = cms.execute("COPYFILE SOME DATA A = = C");
+        assert_eq!(
+            out.lines,
+            vec!["DMSCPY037E Filemode C is accessed as read/only"]
+        );
+        assert_eq!(out.rc, 36);
+
+        let out = cms.execute("QUERY DISK A");
+        assert_eq!(out.lines.len(), 2);
+        assert!(out.lines[1].starts_with("A     R/W      1  "));
+
+        assert_eq!(cms.execute("RELEASE C").rc, 0);
+        let out = cms.execute("REL C");
+        assert_eq!(out.lines, vec!["DMSARL069E Filemode C not accessed"]);
+        assert_eq!(out.rc, 36);
+        assert!(cms.filesystem().disk('C').is_none());
+        assert_eq!(
+            cms.filesystem().disk('A').unwrap().access(),
+            AccessMode::ReadWrite
+        );
+    }
+
+    #[test]
+    fn ipl_noprof_accesses_disks_by_path() {
+        let (dir, mut cms) = processor();
+        std::fs::write(dir.path().join("a/profile.exec"), "exit 5\n").unwrap();
+        let tools = dir.path().join("my tools");
+        let blocked = dir.path().join("blocked");
+        std::fs::write(&blocked, "not a directory\n").unwrap();
+
+        let options = IplOptions {
+            disks: vec![
+                (tools.clone(), 'C', AccessMode::ReadOnly),
+                (blocked, 'D', AccessMode::ReadWrite),
+                (tools.clone(), 'E', AccessMode::ReadWrite),
+            ],
+            noprof: true,
+        };
+        let out = cms.ipl(&options);
+        assert_eq!(out.lines.len(), 2);
+        assert!(out.lines[0].starts_with("DMSACC723I C ("));
+        assert_eq!(out.lines[1], "DMSACC104S Error File exists (os error 17)");
+        assert_ne!(out.rc, 0);
+        assert_eq!(cms.filesystem().disk('C').unwrap().path(), tools.as_path());
+        assert!(cms.filesystem().disk('D').is_none());
+        assert!(cms.filesystem().disk('E').is_some());
+    }
+
+    #[test]
+    fn unknown_command_and_logoff() {
+        let (_dir, mut cms) = processor();
+        let out = cms.execute("FROB");
+        assert_eq!(out.lines, vec!["Unknown CP/CMS command"]);
+        assert_eq!(out.rc, -3);
+        assert!(cms.execute("LOGOFF").logoff);
+    }
+}
@@ -0,0 +1,288 @@
+//! CMS minidisks as XEDIT's file system.
+//!
+//! `XEDIT fn ft fm` opens the file on that disk; with no filemode every
+//! accessed disk is searched in A-Z order, and a new file goes on disk A.
+//! Saving is refused on read-only disks; a file named by a host path off
+//! the accessed disks is saved as any host file is. Macros are found as
+//! `name XEDIT` on any accessed disk.
+//!
+//! The command processor is the editor's `Host`: a macro's `ADDRESS CMS`
+//! commands run as they would at the console, and GLOBALV GET and PUT
+//! work on the macro's variables as they do on an EXEC's. Giving the
+//! editor `SharedDisks` over the same processor lets it see the disks its
+//! macros ACCESS and RELEASE.
+
+use std::cell::RefCell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use xedit_core::filesystem::{FileId, FileSystem, NativeFs};
+use xedit_core::host::{Host, Variables};
+
+use crate::error::CmsError;
+use crate::filespec::FileSpec;
+use crate::filesystem::CmsFileSystem;
+use crate::processor::CommandProcessor;
+
+impl FileSystem for CmsFileSystem {
+    fn locate(&self, fileid: &str) -> io::Result<FileId> {
+        let spec = match fileid.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [fname, ftype] => FileSpec::new(fname, ftype, "*"),
+            _ => FileSpec::parse(fileid),
+        }?;
+        let (spec, path) = self.locate_file(&spec)?;
+        Ok(FileId {
+            path,
+            filename: spec.filename().to_string(),
+            filetype: spec.filetype().to_string(),
+            filemode: spec.filemode(),
+        })
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        match self.disk_for_path(path) {
+            Some(disk) if !disk.is_writable() => Err(CmsError::ReadOnly(disk.letter()).into()),
+            _ => std::fs::write(path, data),
+        }
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        match self.disk_for_path(path) {
+            Some(disk) => disk.is_writable(),
+            None => NativeFs.is_writable(path),
+        }
+    }
+
+    fn find_macro(&self, name: &str) -> Option<PathBuf> {
+        let spec = FileSpec::new(name, "XEDIT", "*").ok()?;
+        let (_spec, path) = self.locate_file(&spec).ok()?;
+        path.is_file().then_some(path)
+    }
+}
+
+/// A command processor's disks as a file system, as the processor has
+/// them when each call is made
+#[derive(Debug, Clone)]
+pub struct SharedDisks(Rc<RefCell<CommandProcessor>>);
+
+impl SharedDisks {
+    pub fn new(cms: Rc<RefCell<CommandProcessor>>) -> Self {
+        SharedDisks(cms)
+    }
+}
+
+impl FileSystem for SharedDisks {
+    fn locate(&self, fileid: &str) -> io::Result<FileId> {
+        self.0.borrow().filesystem().locate(fileid)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        FileSystem::read(self.0.borrow().filesystem(), path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        FileSystem::write(self.0.borrow().filesystem(), path, data)
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        self.0.borrow().filesystem().is_writable(path)
+    }
+
+    fn find_macro(&self, name: &str) -> Option<PathBuf> {
+        self.0.borrow().filesystem().find_macro(name)
+    }
+}
+
+impl Host for CommandProcessor {
+    fn command(&mut self, line: &str, vars: &mut dyn Variables) -> (Vec<String>, i32) {
+        let output = self.execute_in(line, vars);
+        (output.lines, output.rc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minidisk::AccessMode;
+    use tempfile::TempDir;
+    use xedit_core::command::parse_command;
+    use xedit_core::editor::{Editor, LoadOptions};
+    use xedit_core::ring::Ring;
+
+    fn input(ed: &mut Editor, text: &str) {
+        let cmd = parse_command(&format!("INPUT {}", text)).unwrap();
+        ed.execute(&cmd).unwrap();
+    }
+
+    fn two_disks() -> (TempDir, CmsFileSystem) {
+        let dir = TempDir::new().unwrap();
+        let mut fs = CmsFileSystem::new();
+        fs.access_disk('A', dir.path().join("a"), AccessMode::ReadWrite)
+            .unwrap();
+        fs.access_disk('B', dir.path().join("b"), AccessMode::ReadOnly)
+            .unwrap();
+        (dir, fs)
+    }
+
+    #[test]
+    fn locate_searches_disks_in_order() {
+        let (dir, fs) = two_disks();
+        std::fs::write(dir.path().join("b/profile.exec"), "/* B */\n").unwrap();
+
+        let id = FileSystem::locate(&fs, "profile exec").unwrap();
+        assert_eq!(id.path, dir.path().join("b/profile.exec"));
+        assert_eq!(id.filemode, "B1");
+
+        std::fs::write(dir.path().join("a/profile.exec"), "/* A */\n").unwrap();
+        let id = FileSystem::locate(&fs, "PROFILE EXEC").unwrap();
+        assert_eq!(id.filemode, "A1");
+
+        // An explicit filemode looks only on that disk
+        let id = FileSystem::locate(&fs, "PROFILE EXEC B").unwrap();
+        assert_eq!(id.path, dir.path().join("b/profile.exec"));
+
+        // New files go on disk A unless a disk is named
+        let id = FileSystem::locate(&fs, "NEW DATA").unwrap();
+        assert_eq!(id.path, dir.path().join("a/new.data"));
+        assert_eq!(id.filemode, "A1");
+        assert!(FileSystem::locate(&fs, "NEW DATA C").is_err());
+        assert!(FileSystem::locate(&fs, "notes.txt").is_err());
+    }
+
+    #[test]
+    fn xedit_opens_by_filespec_and_saves_on_disk() {
+        let (dir, fs) = two_disks();
+        std::fs::write(dir.path().join("b/ro.data"), "read only\n").unwrap();
+        std::fs::write(dir.path().join("a/rw.data"), "one\n").unwrap();
+        let mut ring = Ring::with_filesystem(Rc::new(fs));
+
+        let id = ring.locate("RW DATA").unwrap();
+        let ed = ring.open_file(&id, &LoadOptions::default()).unwrap();
+        assert_eq!(
+            (ed.filename(), ed.filetype(), ed.filemode()),
+            ("RW", "DATA", "A1")
+        );
+        input(ed, "two");
+        ed.save_file().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a/rw.data")).unwrap(),
+            "one\ntwo\n"
+        );
+
+        let id = ring.locate("RO DATA *").unwrap();
+        let ed = ring.open_file(&id, &LoadOptions::default()).unwrap();
+        assert_eq!(ed.filemode(), "B1");
+        input(ed, "more");
+        assert!(ed.save_file().is_err());
+
+        // A host file off the disks, as named on the command line, saves
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "text\n").unwrap();
+        let ed = ring.add_file_with(&path, &LoadOptions::default()).unwrap();
+        input(ed, "more");
+        ed.save_file().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "text\nmore\n");
+    }
+
+    #[test]
+    fn xedit_new_file_starts_empty() {
+        let (dir, fs) = two_disks();
+        let mut ring = Ring::with_filesystem(Rc::new(fs));
+
+        let id = ring.locate("NEW DATA A").unwrap();
+        let ed = ring.open_file(&id, &LoadOptions::default()).unwrap();
+        assert!(ed.buffer().is_empty());
+        assert_eq!(ed.message(), Some("New file"));
+        assert_eq!(ed.filemode(), "A1");
+        input(ed, "first");
+        ed.save_file().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a/new.data")).unwrap(),
+            "first\n"
+        );
+    }
+
+    #[test]
+    fn undo_file_only_on_writable_disks() {
+        let (dir, fs) = two_disks();
+        std::fs::write(dir.path().join("a/notes.data"), "one\n").unwrap();
+        let mut ring = Ring::with_filesystem(Rc::new(fs));
+        let undofile = parse_command("SET UNDOFILE ON").unwrap();
+
+        let id = ring.locate("NOTES DATA A").unwrap();
+        let ed = ring.open_file(&id, &LoadOptions::default()).unwrap();
+        ed.execute(&undofile).unwrap();
+        input(ed, "two");
+        ed.save_file().unwrap();
+        assert!(dir.path().join("a/.notes.data.undo").exists());
+
+        // The same file and history on a read-only disk are left alone
+        for name in ["notes.data", ".notes.data.undo"] {
+            std::fs::copy(
+                dir.path().join("a").join(name),
+                dir.path().join("b").join(name),
+            )
+            .unwrap();
+        }
+        let id = ring.locate("NOTES DATA B").unwrap();
+        let ed = ring.open_file(&id, &LoadOptions::default()).unwrap();
+        ed.execute(&undofile).unwrap();
+        assert!(ed.execute(&parse_command("UNDO").unwrap()).is_err());
+    }
+
+    #[test]
+    fn xedit_sees_disks_the_processor_accesses() {
+        let (dir, fs) = two_disks();
+        std::fs::create_dir(dir.path().join("c")).unwrap();
+        std::fs::write(dir.path().join("c/tools.data"), "tool\n").unwrap();
+        let cms = Rc::new(RefCell::new(CommandProcessor::new(fs)));
+        let ring = Ring::with_filesystem(Rc::new(SharedDisks::new(cms.clone())));
+        assert!(ring.locate("TOOLS DATA C").is_err());
+
+        let access = format!("ACCESS {} C", dir.path().join("c").display());
+        assert_eq!(cms.borrow_mut().execute(&access).rc, 0);
+        let id = ring.locate("TOOLS DATA C").unwrap();
+        assert_eq!(id.path, dir.path().join("c/tools.data"));
+
+        cms.borrow_mut().execute("RELEASE C");
+        assert!(ring.locate("TOOLS DATA C").is_err());
+    }
+
+    #[cfg(feature = "rexx")]
+    #[test]
+    fn macros_address_cms() {
+        let (dir, fs) = two_disks();
+        std::fs::write(
+            dir.path().join("a/last.xedit"),
+            "'EXTRACT /FNAME/FTYPE/'\nlastfile = fname.1 ftype.1\n\
+             address cms 'GLOBALV SELECT EDIT PUTP LASTFILE'\n\
+             address cms 'LISTFILE NOSUCH FILE'\nexit rc\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("a/notes.script"), "text\n").unwrap();
+        let cms = Rc::new(RefCell::new(CommandProcessor::new(fs.clone())));
+        let mut ring = Ring::with_filesystem(Rc::new(fs));
+        ring.set_host(cms.clone());
+
+        let id = ring.locate("NOTES SCRIPT").unwrap();
+        let ed = ring.open_file(&id, &LoadOptions::default()).unwrap();
+        ed.execute(&parse_command("MACRO LAST").unwrap()).unwrap();
+        assert_eq!(
+            ed.message(),
+            Some("DMSLST002E File NOSUCH FILE A1 not found")
+        );
+        assert_eq!(
+            cms.borrow().globals().get("EDIT", "LASTFILE"),
+            Some("NOTES SCRIPT")
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a/lasting.globalv")).unwrap(),
+            "EDIT LASTFILE NOTES SCRIPT\n"
+        );
+    }
+}
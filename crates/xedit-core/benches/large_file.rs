@@ -0,0 +1,115 @@
+//! Editing a multi-million-line file: LOCATE, CHANGE * and block moves.
+//!
+//! Run with `cargo bench -p xedit-core`.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion, SamplingMode};
+use tempfile::TempDir;
+use xedit_core::buffer::Buffer;
+use xedit_core::command::parse_command;
+use xedit_core::editor::Editor;
+
+const LINES: usize = 2_000_000;
+
+/// A log file with an ERROR every tenth line and a marker on the last one
+fn large_editor() -> (TempDir, Editor) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("server.log");
+    let mut content = String::with_capacity(LINES * 48);
+    for n in 1..=LINES {
+        let level = if n % 10 == 0 { "ERROR" } else { "INFO " };
+        writeln!(
+            content,
+            "2026-10-16 12:00:00 {} request {} served",
+            level, n
+        )
+        .unwrap();
+    }
+    content.push_str("NEEDLE\n");
+    std::fs::write(&path, content).unwrap();
+
+    let mut ed = Editor::new();
+    ed.load_file(&path).unwrap();
+    // Keep the benchmark about the buffer, not about growing undo history
+    run(&mut ed, "SET UNDO 0");
+    (dir, ed)
+}
+
+/// Whole-file operations take a while: ten single-iteration samples each
+fn large_file_group(c: &mut Criterion) -> BenchmarkGroup<'_, WallTime> {
+    let mut group = c.benchmark_group("large_file");
+    group
+        .sample_size(10)
+        .sampling_mode(SamplingMode::Flat)
+        .warm_up_time(Duration::from_millis(500))
+        .measurement_time(Duration::from_secs(20));
+    group
+}
+
+fn run(ed: &mut Editor, cmd: &str) {
+    ed.execute(&parse_command(cmd).unwrap()).unwrap();
+}
+
+fn locate(c: &mut Criterion) {
+    let (_dir, mut ed) = large_editor();
+    let mut group = large_file_group(c);
+    group.bench_function("locate_last_line", |b| {
+        b.iter(|| {
+            run(&mut ed, "TOP");
+            run(&mut ed, "LOCATE /NEEDLE/");
+        })
+    });
+    group.finish();
+}
+
+fn change_star(c: &mut Criterion) {
+    let (_dir, mut ed) = large_editor();
+    let mut group = large_file_group(c);
+    // Each iteration changes every tenth line and then changes it back
+    group.bench_function("change_star_round_trip", |b| {
+        b.iter(|| {
+            run(&mut ed, "TOP");
+            run(&mut ed, "CHANGE /ERROR/FAULT/ *");
+            run(&mut ed, "TOP");
+            run(&mut ed, "CHANGE /FAULT/ERROR/ *");
+        })
+    });
+    group.finish();
+}
+
+fn block_move(c: &mut Criterion) {
+    let (_dir, mut ed) = large_editor();
+    let mut group = large_file_group(c);
+    // Rotates the first million lines to the end of the file
+    group.bench_function("move_million_lines", |b| {
+        b.iter(|| {
+            run(&mut ed, "LOCATE :1");
+            run(&mut ed, &format!("MOVE {} :{}", LINES / 2, LINES + 1));
+        })
+    });
+    group.finish();
+}
+
+fn insert_delete_middle(c: &mut Criterion) {
+    let lines = (0..LINES).map(|n| format!("line {}", n)).collect();
+    let mut buffer = Buffer::from_lines(lines);
+    c.bench_function("buffer_insert_delete_middle", |b| {
+        b.iter(|| {
+            buffer.insert_after(LINES / 2, "inserted");
+            buffer.delete(LINES / 2 + 1);
+            buffer.take_deltas();
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    locate,
+    change_star,
+    block_move,
+    insert_delete_middle
+);
+criterion_main!(benches);
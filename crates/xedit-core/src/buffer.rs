@@ -1,3 +1,9 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::line_index::LineIndex;
+use crate::rope::LineRope;
+
 /// Record format of the file
 #[derive(Debug, Clone, PartialEq)]
 pub enum RecordFormat {
@@ -8,14 +14,19 @@ pub enum RecordFormat {
 }
 
 /// A single line in the buffer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Line {
     text: String,
+    /// SET SELECT level
+    level: usize,
 }
 
 impl Line {
     pub fn new(text: impl Into<String>) -> Self {
-        Self { text: text.into() }
+        Self {
+            text: text.into(),
+            level: 0,
+        }
     }
 
     pub fn text(&self) -> &str {
@@ -35,101 +46,506 @@ impl Line {
     }
 }
 
+/// One reversible change to the buffer, recorded for undo.
+///
+/// Positions are 0-based line indexes. Applying a delta's inverse yields
+/// the delta that reverses it again, so undo and redo share one mechanism.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delta {
+    /// `count` lines were inserted at `at`
+    Insert { at: usize, count: usize },
+    /// `lines` (with their selection levels) were removed from `at`, with
+    /// the SET POINT names that were on them (by offset into `lines`)
+    Delete {
+        at: usize,
+        lines: Vec<Line>,
+        points: Vec<(usize, String)>,
+    },
+    /// The line at `at` previously held `old`
+    Replace { at: usize, old: String },
+    /// SET POINT names were moved to other lines (SORT); `old` holds
+    /// each moved name and its 1-based line before
+    Renumber { old: Vec<(String, usize)> },
+}
+
 /// The text buffer containing all lines of a file.
 ///
 /// Line numbers are 1-based. Line 0 represents "Top of File" (TOF),
-/// a virtual position before all lines.
+/// a virtual position before all lines. Lines are kept in a `LineRope`,
+/// so edits in a multi-million-line file do not shift every line. A
+/// browsed file is read through a `LineIndex` instead, and only loaded
+/// into the rope if it is changed.
 #[derive(Debug)]
 pub struct Buffer {
-    lines: Vec<Line>,
+    lines: LineRope,
+    /// BROWSE: lines still on disk, read as they are looked at
+    index: Option<LineIndex>,
+    /// Why the indexed file could not be read in for a change
+    read_error: Option<io::Error>,
     recfm: RecordFormat,
     lrecl: usize,
     modified: bool,
+    /// Changes since the undo log last collected them
+    deltas: Vec<Delta>,
+    /// SET POINT names and the 1-based lines they name; they follow their
+    /// lines as lines are inserted and deleted above them
+    points: HashMap<String, usize>,
+    /// Whether any line may have a SET SELECT level other than 0
+    selecting: bool,
+    /// SET SELECT levels of an indexed buffer's lines, by 0-based index,
+    /// kept here so selecting lines does not read the file in; lines not
+    /// in the map are at level 0
+    index_levels: HashMap<usize, usize>,
 }
 
 impl Buffer {
     pub fn new() -> Self {
         Self {
-            lines: Vec::new(),
+            lines: LineRope::new(),
+            index: None,
+            read_error: None,
             recfm: RecordFormat::Variable,
             lrecl: 80,
             modified: false,
+            deltas: Vec::new(),
+            points: HashMap::new(),
+            selecting: false,
+            index_levels: HashMap::new(),
         }
     }
 
     pub fn from_lines(lines: Vec<String>) -> Self {
         let max_len = lines.iter().map(|l| l.len()).max().unwrap_or(80);
         Self {
-            lines: lines.into_iter().map(Line::new).collect(),
+            lines: LineRope::from_lines(lines.into_iter().map(Line::new).collect()),
+            index: None,
+            read_error: None,
             recfm: RecordFormat::Variable,
             lrecl: max_len.max(80),
             modified: false,
+            deltas: Vec::new(),
+            points: HashMap::new(),
+            selecting: false,
+            index_levels: HashMap::new(),
+        }
+    }
+
+    /// Build a RECFM F buffer from records already split at `lrecl`
+    pub fn from_records(records: Vec<String>, lrecl: usize) -> Self {
+        Self {
+            lines: LineRope::from_lines(records.into_iter().map(Line::new).collect()),
+            index: None,
+            read_error: None,
+            recfm: RecordFormat::Fixed,
+            lrecl,
+            modified: false,
+            deltas: Vec::new(),
+            points: HashMap::new(),
+            selecting: false,
+            index_levels: HashMap::new(),
+        }
+    }
+
+    /// Browse a file through a line index without reading it all in
+    pub fn from_index(index: LineIndex, recfm: RecordFormat, lrecl: usize) -> Self {
+        Self {
+            lines: LineRope::new(),
+            index: Some(index),
+            read_error: None,
+            recfm,
+            lrecl,
+            modified: false,
+            deltas: Vec::new(),
+            points: HashMap::new(),
+            selecting: false,
+            index_levels: HashMap::new(),
         }
     }
 
     pub fn len(&self) -> usize {
-        self.lines.len()
+        match &self.index {
+            Some(index) => index.len(),
+            None => self.lines.len(),
+        }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.len() == 0
+    }
+
+    /// Whether lines are still being read from disk on demand
+    pub fn is_indexed(&self) -> bool {
+        self.index.is_some()
     }
 
     /// Get a line by 1-based line number
     pub fn get(&self, line_num: usize) -> Option<&Line> {
-        if line_num == 0 || line_num > self.lines.len() {
-            None
-        } else {
-            Some(&self.lines[line_num - 1])
+        let at = line_num.checked_sub(1)?;
+        match &self.index {
+            Some(index) => index.get(at),
+            None => self.lines.get(at),
+        }
+    }
+
+    /// Call `f` with the text of a line. Unlike `get`, an indexed buffer
+    /// does not keep the line's block in memory, so searches through the
+    /// whole file stay small.
+    pub fn with_text<R>(&self, line_num: usize, f: impl FnOnce(&str) -> R) -> Option<R> {
+        let at = line_num.checked_sub(1)?;
+        match &self.index {
+            Some(index) => index.with_text(at, f),
+            None => self.lines.get(at).map(|l| f(l.text())),
         }
     }
 
-    /// Get a mutable line by 1-based line number
+    /// Let an indexed buffer drop the lines it read that are not near
+    /// `line_num`
+    pub fn trim_cache(&mut self, line_num: usize) {
+        if let Some(index) = &mut self.index {
+            index.trim(line_num.saturating_sub(1));
+        }
+    }
+
+    /// Read an indexed file into memory so it can be changed. If it cannot
+    /// be read the buffer stays indexed, the error is kept for
+    /// `take_read_error` and false is returned: the change must not be made.
+    fn materialize(&mut self) -> bool {
+        let Some(index) = &self.index else {
+            return true;
+        };
+        match index.read_all() {
+            Ok(lines) => {
+                self.lines = LineRope::from_lines(lines);
+                self.index = None;
+                for (at, level) in std::mem::take(&mut self.index_levels) {
+                    if let Some(line) = self.lines.get_mut(at) {
+                        line.level = level;
+                    }
+                }
+                true
+            }
+            Err(e) => {
+                self.read_error = Some(e);
+                false
+            }
+        }
+    }
+
+    /// The last error reading an indexed file since the previous call;
+    /// lines that could not be read are missing until they can be
+    pub fn take_read_error(&mut self) -> Option<io::Error> {
+        let index_error = self.index.as_ref().and_then(LineIndex::take_error);
+        self.read_error.take().or(index_error)
+    }
+
+    /// Get a mutable line by 1-based line number. The line's text is
+    /// recorded for undo whether or not it is then changed.
+    #[deprecated(note = "use `set_line_text`, which records only real changes")]
     pub fn get_mut(&mut self, line_num: usize) -> Option<&mut Line> {
-        if line_num == 0 || line_num > self.lines.len() {
-            None
-        } else {
-            self.modified = true;
-            Some(&mut self.lines[line_num - 1])
+        let at = line_num.checked_sub(1)?;
+        let old = self.line_text(line_num)?.to_string();
+        if !self.materialize() {
+            return None;
+        }
+        self.record_replace(at, old);
+        self.modified = true;
+        self.lines.get_mut(at)
+    }
+
+    /// Replace the text of a line by 1-based line number. Returns false,
+    /// recording nothing, if there is no such line or the text is the same.
+    pub fn set_line_text(&mut self, line_num: usize, text: impl Into<String>) -> bool {
+        let text = text.into();
+        let Some(at) = line_num.checked_sub(1) else {
+            return false;
+        };
+        if self.line_text(line_num).is_none_or(|old| old == text) {
+            return false;
+        }
+        if !self.materialize() {
+            return false;
+        }
+        let Some(line) = self.lines.get_mut(at) else {
+            return false;
+        };
+        let old = std::mem::replace(&mut line.text, text);
+        self.record_replace(at, old);
+        self.modified = true;
+        true
+    }
+
+    /// Record the old text of a replaced line; only the oldest text
+    /// matters when a line is changed repeatedly
+    fn record_replace(&mut self, at: usize, old: String) {
+        let repeat = matches!(self.deltas.last(), Some(Delta::Replace { at: a, .. }) if *a == at);
+        if !repeat {
+            self.deltas.push(Delta::Replace { at, old });
         }
     }
 
     /// Insert a line after the given 1-based line number (0 = insert at top)
     pub fn insert_after(&mut self, after_line: usize, text: impl Into<String>) {
+        if !self.materialize() {
+            return;
+        }
         let idx = after_line.min(self.lines.len());
-        self.lines.insert(idx, Line::new(text));
+        let line = Line {
+            text: text.into(),
+            level: self.inserted_level(idx),
+        };
+        self.lines.insert(idx, vec![line]);
         self.modified = true;
+        self.shift_points(idx, 1);
+        self.deltas.push(Delta::Insert { at: idx, count: 1 });
     }
 
     /// Insert multiple lines after the given position
     pub fn insert_lines_after(&mut self, after_line: usize, texts: Vec<String>) {
-        let idx = after_line.min(self.lines.len());
-        for (i, text) in texts.into_iter().enumerate() {
-            self.lines.insert(idx + i, Line::new(text));
+        if !self.materialize() {
+            return;
         }
-        if !self.lines.is_empty() {
+        let idx = after_line.min(self.lines.len());
+        let count = texts.len();
+        let level = self.inserted_level(idx);
+        self.lines.insert(
+            idx,
+            texts.into_iter().map(|text| Line { text, level }).collect(),
+        );
+        if count > 0 {
             self.modified = true;
+            self.shift_points(idx, count);
+            self.deltas.push(Delta::Insert { at: idx, count });
         }
     }
 
     /// Delete a line by 1-based line number
     pub fn delete(&mut self, line_num: usize) -> Option<Line> {
-        if line_num == 0 || line_num > self.lines.len() {
-            None
-        } else {
-            self.modified = true;
-            Some(self.lines.remove(line_num - 1))
-        }
+        self.delete_range(line_num, line_num).pop()
     }
 
     /// Delete a range of lines (inclusive, 1-based)
     pub fn delete_range(&mut self, from: usize, to: usize) -> Vec<Line> {
-        if from == 0 || from > self.lines.len() || to < from {
+        if !self.materialize() || from == 0 || from > self.lines.len() || to < from {
             return Vec::new();
         }
         let to = to.min(self.lines.len());
         self.modified = true;
-        self.lines.drain((from - 1)..to).collect()
+        let removed = self.lines.remove((from - 1)..to);
+        let points = self.take_points(from - 1, removed.len());
+        self.deltas.push(Delta::Delete {
+            at: from - 1,
+            lines: removed.clone(),
+            points,
+        });
+        removed
+    }
+
+    /// Hand over the changes recorded since the last call
+    pub fn take_deltas(&mut self) -> Vec<Delta> {
+        std::mem::take(&mut self.deltas)
+    }
+
+    /// Reverse a recorded change, returning the delta that re-applies it.
+    /// The reversal itself is not recorded. Only a changed buffer has
+    /// deltas, and a changed buffer is never indexed.
+    pub fn revert(&mut self, delta: Delta) -> Delta {
+        self.modified = true;
+        match delta {
+            Delta::Insert { at, count } => {
+                let lines = self.lines.remove(at..at + count);
+                let points = self.take_points(at, lines.len());
+                Delta::Delete { at, lines, points }
+            }
+            Delta::Delete { at, lines, points } => {
+                let count = lines.len();
+                let at = at.min(self.lines.len());
+                self.selecting |= lines.iter().any(|l| l.level > 0);
+                self.lines.insert(at, lines);
+                self.shift_points(at, count);
+                for (offset, name) in points {
+                    self.points.insert(name, at + offset + 1);
+                }
+                Delta::Insert { at, count }
+            }
+            Delta::Replace { at, old } => match self.lines.get_mut(at) {
+                Some(line) => Delta::Replace {
+                    at,
+                    old: std::mem::replace(&mut line.text, old),
+                },
+                None => Delta::Replace { at, old },
+            },
+            Delta::Renumber { old } => Delta::Renumber {
+                old: self.restore_points(old),
+            },
+        }
+    }
+
+    /// Name line `line_num` (SET POINT); a name can only name one line
+    pub fn set_point(&mut self, name: &str, line_num: usize) {
+        self.points.insert(name.to_string(), line_num);
+    }
+
+    /// Remove a point name; false if there was no such name
+    pub fn remove_point(&mut self, name: &str) -> bool {
+        self.points.remove(name).is_some()
+    }
+
+    /// The line a point name is on
+    pub fn point(&self, name: &str) -> Option<usize> {
+        self.points.get(name).copied()
+    }
+
+    /// Every point as `(line, name)`, in line order
+    pub fn points(&self) -> Vec<(usize, &str)> {
+        let mut points: Vec<(usize, &str)> = self
+            .points
+            .iter()
+            .map(|(name, &line)| (line, name.as_str()))
+            .collect();
+        points.sort_unstable();
+        points
+    }
+
+    /// Names of line `line_num`, sorted
+    pub fn points_at(&self, line_num: usize) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .points
+            .iter()
+            .filter(|&(_, &line)| line == line_num)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Move points down past `count` lines inserted at 0-based index `at`
+    fn shift_points(&mut self, at: usize, count: usize) {
+        for line in self.points.values_mut() {
+            if *line > at {
+                *line += count;
+            }
+        }
+    }
+
+    /// Remove the points on `count` deleted lines from 0-based index `at`,
+    /// returning them by offset into the deleted lines, and move the ones
+    /// below them up
+    fn take_points(&mut self, at: usize, count: usize) -> Vec<(usize, String)> {
+        let mut taken = Vec::new();
+        self.points.retain(|name, &mut line| {
+            let deleted = line > at && line <= at + count;
+            if deleted {
+                taken.push((line - at - 1, name.clone()));
+            }
+            !deleted
+        });
+        for line in self.points.values_mut() {
+            if *line > at + count {
+                *line -= count;
+            }
+        }
+        taken.sort_unstable();
+        taken
+    }
+
+    /// Move every point with `renumber`, which maps a 1-based line to the
+    /// line it has moved to; recorded for undo
+    pub fn renumber_points(&mut self, renumber: impl Fn(usize) -> usize) {
+        let mut old = Vec::new();
+        for (name, line) in self.points.iter_mut() {
+            let moved = renumber(*line);
+            if moved != *line {
+                old.push((name.clone(), std::mem::replace(line, moved)));
+            }
+        }
+        if !old.is_empty() {
+            old.sort_unstable();
+            self.deltas.push(Delta::Renumber { old });
+        }
+    }
+
+    /// Put points back on the lines in `old`, returning where they were
+    fn restore_points(&mut self, old: Vec<(String, usize)>) -> Vec<(String, usize)> {
+        old.into_iter()
+            .filter_map(|(name, line)| {
+                let current = self.points.get_mut(&name)?;
+                Some((name, std::mem::replace(current, line)))
+            })
+            .collect()
+    }
+
+    /// Selection level of a line (SET SELECT); TOF and EOF are at 0
+    pub fn select_level(&self, line_num: usize) -> usize {
+        if !self.selecting {
+            return 0;
+        }
+        if self.is_indexed() {
+            return line_num
+                .checked_sub(1)
+                .and_then(|at| self.index_levels.get(&at))
+                .copied()
+                .unwrap_or(0);
+        }
+        self.get(line_num).map_or(0, |line| line.level)
+    }
+
+    pub fn set_select_level(&mut self, line_num: usize, level: usize) {
+        if line_num == 0 || line_num > self.len() {
+            return;
+        }
+        if !self.selecting && level == 0 {
+            return;
+        }
+        if self.is_indexed() {
+            if level == 0 {
+                self.index_levels.remove(&(line_num - 1));
+            } else {
+                self.index_levels.insert(line_num - 1, level);
+                self.selecting = true;
+            }
+            return;
+        }
+        if let Some(line) = self.lines.get_mut(line_num - 1) {
+            line.level = level;
+            self.selecting = true;
+        }
+    }
+
+    /// Put every line back at selection level 0
+    pub fn clear_select_levels(&mut self) {
+        if self.selecting {
+            self.lines.iter_mut().for_each(|line| line.level = 0);
+            self.index_levels.clear();
+            self.selecting = false;
+        }
+    }
+
+    /// Whether any line may have a selection level other than 0
+    pub fn has_select_levels(&self) -> bool {
+        self.selecting
+    }
+
+    /// Highest selection level of any line
+    pub fn max_select_level(&self) -> usize {
+        if !self.selecting {
+            return 0;
+        }
+        if self.is_indexed() {
+            return self.index_levels.values().copied().max().unwrap_or(0);
+        }
+        self.lines.iter().map(|line| line.level).max().unwrap_or(0)
+    }
+
+    /// Selection level for lines inserted at 0-based index `at`: that of
+    /// the line above them (the line below at the top of the file), so
+    /// lines added among displayed lines are displayed too
+    fn inserted_level(&self, at: usize) -> usize {
+        if !self.selecting {
+            return 0;
+        }
+        self.lines
+            .get(at.saturating_sub(1))
+            .map_or(0, |line| line.level)
     }
 
     pub fn is_modified(&self) -> bool {
@@ -148,8 +564,40 @@ impl Buffer {
         self.lrecl
     }
 
-    pub fn lines(&self) -> &[Line] {
-        &self.lines
+    pub fn set_recfm(&mut self, recfm: RecordFormat) {
+        self.recfm = recfm;
+    }
+
+    pub fn set_lrecl(&mut self, lrecl: usize) {
+        self.lrecl = lrecl;
+    }
+
+    /// Maximum line length in characters, if the record format imposes one
+    pub fn record_limit(&self) -> Option<usize> {
+        match self.recfm {
+            RecordFormat::Fixed => Some(self.lrecl),
+            RecordFormat::Variable => None,
+        }
+    }
+
+    /// A line as a fixed-length record: truncated or blank-padded to LRECL
+    pub fn fixed_record(&self, line_num: usize) -> Option<String> {
+        let text = self.line_text(line_num)?;
+        let mut record: String = text.chars().take(self.lrecl).collect();
+        let len = record.chars().count();
+        record.extend(std::iter::repeat_n(' ', self.lrecl - len));
+        Some(record)
+    }
+
+    /// All lines, first to last. This replaces `lines()`: lines are no
+    /// longer stored contiguously, so there is no slice of them to borrow.
+    pub fn iter(&self) -> impl Iterator<Item = &Line> {
+        // An indexed buffer has nothing in the rope
+        let indexed = self
+            .index
+            .iter()
+            .flat_map(|index| (0..index.len()).filter_map(|i| index.get(i)));
+        self.lines.iter().chain(indexed)
     }
 
     /// Get line text by 1-based line number
@@ -218,4 +666,101 @@ mod tests {
         assert_eq!(buf.line_text(1), Some("a"));
         assert_eq!(buf.line_text(2), Some("e"));
     }
+
+    #[test]
+    fn fixed_record_pads_and_truncates() {
+        let mut buf = Buffer::from_records(vec!["ab".into(), "abcdefgh".into()], 5);
+        assert_eq!(buf.recfm(), &RecordFormat::Fixed);
+        assert_eq!(buf.record_limit(), Some(5));
+        assert_eq!(buf.fixed_record(1).as_deref(), Some("ab   "));
+        assert_eq!(buf.fixed_record(2).as_deref(), Some("abcde"));
+        buf.set_recfm(RecordFormat::Variable);
+        assert_eq!(buf.record_limit(), None);
+    }
+
+    #[test]
+    fn unchanged_text_records_nothing() {
+        let mut buf = Buffer::from_lines(vec!["a".into()]);
+        assert!(!buf.set_line_text(1, "a"));
+        assert!(!buf.set_line_text(2, "b"));
+        assert!(buf.take_deltas().is_empty());
+        assert!(!buf.is_modified());
+    }
+
+    #[test]
+    fn deltas_revert_and_reapply() {
+        let mut buf = Buffer::from_lines(vec!["a".into(), "b".into(), "c".into()]);
+        buf.insert_after(1, "x");
+        buf.set_line_text(1, "A");
+        buf.set_line_text(1, "AA");
+        buf.delete_range(3, 4);
+        let deltas = buf.take_deltas();
+        assert_eq!(deltas.len(), 3);
+        assert!(buf.take_deltas().is_empty());
+        let texts = |b: &Buffer| b.iter().map(|l| l.text().to_string()).collect::<Vec<_>>();
+        assert_eq!(texts(&buf), ["AA", "x"]);
+
+        let redo: Vec<Delta> = deltas.into_iter().rev().map(|d| buf.revert(d)).collect();
+        assert_eq!(texts(&buf), ["a", "b", "c"]);
+        assert!(buf.take_deltas().is_empty());
+
+        for d in redo.into_iter().rev() {
+            buf.revert(d);
+        }
+        assert_eq!(texts(&buf), ["AA", "x"]);
+    }
+
+    #[test]
+    fn points_follow_their_lines() {
+        let mut buf = Buffer::from_lines((1..=6).map(|i| i.to_string()).collect());
+        buf.set_point("A", 2);
+        buf.set_point("B", 5);
+        buf.insert_lines_after(1, vec!["x".into(), "y".into()]);
+        assert_eq!(buf.point("A"), Some(4));
+        assert_eq!(buf.point("B"), Some(7));
+
+        buf.delete_range(4, 5);
+        assert_eq!(buf.point("A"), None);
+        assert_eq!(buf.point("B"), Some(5));
+        assert_eq!(buf.line_text(5), Some("5"));
+
+        buf.set_point("C", 1);
+        buf.set_point("B", 1);
+        assert_eq!(buf.points(), vec![(1, "B"), (1, "C")]);
+        assert_eq!(buf.points_at(1), vec!["B", "C"]);
+        assert!(buf.remove_point("C"));
+        assert!(!buf.remove_point("C"));
+
+        // Undoing an insert above a point moves it back up
+        buf.take_deltas();
+        buf.insert_after(0, "top");
+        let insert = buf.take_deltas().pop().unwrap();
+        assert_eq!(buf.point("B"), Some(2));
+        buf.revert(insert);
+        assert_eq!(buf.point("B"), Some(1));
+    }
+
+    #[test]
+    fn select_levels_follow_their_lines() {
+        let mut buf = Buffer::from_lines((1..=4).map(|i| i.to_string()).collect());
+        assert!(!buf.has_select_levels());
+        buf.set_select_level(2, 0);
+        assert!(!buf.has_select_levels());
+        buf.set_select_level(2, 3);
+        buf.set_select_level(4, 1);
+        assert_eq!(buf.max_select_level(), 3);
+
+        // Inserted lines take the level of the line above
+        buf.insert_lines_after(2, vec!["x".into(), "y".into()]);
+        let levels: Vec<usize> = (1..=6).map(|n| buf.select_level(n)).collect();
+        assert_eq!(levels, vec![0, 3, 3, 3, 0, 1]);
+
+        buf.delete_range(1, 3);
+        let levels: Vec<usize> = (1..=3).map(|n| buf.select_level(n)).collect();
+        assert_eq!(levels, vec![3, 0, 1]);
+        assert_eq!(buf.select_level(0), 0);
+
+        buf.clear_select_levels();
+        assert_eq!(buf.select_level(1), 0);
+    }
 }
@@ -0,0 +1,294 @@
+use std::sync::OnceLock;
+
+/// Character encoding of a file on disk.
+///
+/// The EBCDIC code pages are permutations of Latin-1, so every byte decodes
+/// to a character in U+0000..=U+00FF and back again without loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codepage {
+    /// UTF-8 text; binary fixed records hold one Latin-1 character per byte
+    #[default]
+    Utf8,
+    /// EBCDIC US/Canada
+    Cp037,
+    /// EBCDIC Latin-1 open systems (z/OS UNIX, z/VM OpenExtensions)
+    Cp1047,
+    /// EBCDIC International
+    Cp500,
+}
+
+impl Codepage {
+    /// Parse a code page name: `CP037`, `IBM-1047`, `500`, `UTF8`, ...
+    pub fn parse(name: &str) -> Option<Self> {
+        let upper = name.trim().to_uppercase();
+        let number = upper
+            .trim_start_matches("IBM")
+            .trim_start_matches("CP")
+            .trim_start_matches('-');
+        match number {
+            "37" | "037" => Some(Codepage::Cp037),
+            "1047" => Some(Codepage::Cp1047),
+            "500" => Some(Codepage::Cp500),
+            "UTF8" | "UTF-8" | "ASCII" => Some(Codepage::Utf8),
+            _ => None,
+        }
+    }
+
+    /// Name shown by QUERY CODEPAGE and on the ID line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codepage::Utf8 => "UTF8",
+            Codepage::Cp037 => "CP037",
+            Codepage::Cp1047 => "CP1047",
+            Codepage::Cp500 => "CP500",
+        }
+    }
+
+    pub fn is_ebcdic(&self) -> bool {
+        *self != Codepage::Utf8
+    }
+
+    fn table(&self) -> Option<&'static [char; 256]> {
+        match self {
+            Codepage::Utf8 => None,
+            Codepage::Cp037 => Some(&CP037),
+            Codepage::Cp1047 => Some(&CP1047),
+            Codepage::Cp500 => Some(&CP500),
+        }
+    }
+
+    /// Decode one byte of a binary record
+    pub fn decode_byte(&self, b: u8) -> char {
+        match self.table() {
+            Some(table) => table[b as usize],
+            None => char::from(b),
+        }
+    }
+
+    /// Encode one character into a binary record byte, if representable
+    pub fn encode_char(&self, c: char) -> Option<u8> {
+        let latin1 = u8::try_from(c).ok()?;
+        match self {
+            Codepage::Utf8 => Some(latin1),
+            Codepage::Cp037 => Some(reverse(&CP037_REVERSE, &CP037)[latin1 as usize]),
+            Codepage::Cp1047 => Some(reverse(&CP1047_REVERSE, &CP1047)[latin1 as usize]),
+            Codepage::Cp500 => Some(reverse(&CP500_REVERSE, &CP500)[latin1 as usize]),
+        }
+    }
+
+    /// Decode a byte string one character per byte
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        bytes.iter().map(|&b| self.decode_byte(b)).collect()
+    }
+
+    /// Encode a string one byte per character; `Err` holds the first
+    /// character the code page cannot represent
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        text.chars().map(|c| self.encode_char(c).ok_or(c)).collect()
+    }
+
+    /// Two hex digits per character: its EBCDIC byte, or for UTF-8 its
+    /// Latin-1 code point. That is the byte a binary record holds, but not
+    /// what a UTF-8 text line holds for a character past U+007F (`é` is
+    /// `E9` here and C3 A9 on disk). Characters past U+00FF show as `??`.
+    pub fn to_hex(&self, text: &str) -> String {
+        text.chars()
+            .map(|c| match self.encode_char(c) {
+                Some(b) => format!("{:02X}", b),
+                None => "??".to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Parse a hex string literal `X'C1C2'` into its bytes
+pub fn hex_literal(s: &str) -> Option<Vec<u8>> {
+    let digits = s
+        .strip_prefix(['X', 'x'])?
+        .strip_prefix('\'')?
+        .strip_suffix('\'')?;
+    if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+static CP037_REVERSE: OnceLock<[u8; 256]> = OnceLock::new();
+static CP1047_REVERSE: OnceLock<[u8; 256]> = OnceLock::new();
+static CP500_REVERSE: OnceLock<[u8; 256]> = OnceLock::new();
+
+/// Latin-1 code point -> EBCDIC byte, built once from the decode table
+fn reverse(cell: &'static OnceLock<[u8; 256]>, table: &[char; 256]) -> &'static [u8; 256] {
+    cell.get_or_init(|| {
+        let mut rev = [0u8; 256];
+        for (byte, &c) in table.iter().enumerate() {
+            rev[c as usize] = byte as u8;
+        }
+        rev
+    })
+}
+
+#[rustfmt::skip]
+const CP037: [char; 256] = [
+    '\u{00}', '\u{01}', '\u{02}', '\u{03}', '\u{9C}', '\u{09}', '\u{86}', '\u{7F}',
+    '\u{97}', '\u{8D}', '\u{8E}', '\u{0B}', '\u{0C}', '\u{0D}', '\u{0E}', '\u{0F}',
+    '\u{10}', '\u{11}', '\u{12}', '\u{13}', '\u{9D}', '\u{85}', '\u{08}', '\u{87}',
+    '\u{18}', '\u{19}', '\u{92}', '\u{8F}', '\u{1C}', '\u{1D}', '\u{1E}', '\u{1F}',
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{0A}', '\u{17}', '\u{1B}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{05}', '\u{06}', '\u{07}',
+    '\u{90}', '\u{91}', '\u{16}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{04}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{14}', '\u{15}', '\u{9E}', '\u{1A}',
+    '\u{20}', '\u{A0}', '\u{E2}', '\u{E4}', '\u{E0}', '\u{E1}', '\u{E3}', '\u{E5}',
+    '\u{E7}', '\u{F1}', '\u{A2}', '\u{2E}', '\u{3C}', '\u{28}', '\u{2B}', '\u{7C}',
+    '\u{26}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{E8}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{EC}', '\u{DF}', '\u{21}', '\u{24}', '\u{2A}', '\u{29}', '\u{3B}', '\u{AC}',
+    '\u{2D}', '\u{2F}', '\u{C2}', '\u{C4}', '\u{C0}', '\u{C1}', '\u{C3}', '\u{C5}',
+    '\u{C7}', '\u{D1}', '\u{A6}', '\u{2C}', '\u{25}', '\u{5F}', '\u{3E}', '\u{3F}',
+    '\u{F8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{C8}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{CC}', '\u{60}', '\u{3A}', '\u{23}', '\u{40}', '\u{27}', '\u{3D}', '\u{22}',
+    '\u{D8}', '\u{61}', '\u{62}', '\u{63}', '\u{64}', '\u{65}', '\u{66}', '\u{67}',
+    '\u{68}', '\u{69}', '\u{AB}', '\u{BB}', '\u{F0}', '\u{FD}', '\u{FE}', '\u{B1}',
+    '\u{B0}', '\u{6A}', '\u{6B}', '\u{6C}', '\u{6D}', '\u{6E}', '\u{6F}', '\u{70}',
+    '\u{71}', '\u{72}', '\u{AA}', '\u{BA}', '\u{E6}', '\u{B8}', '\u{C6}', '\u{A4}',
+    '\u{B5}', '\u{7E}', '\u{73}', '\u{74}', '\u{75}', '\u{76}', '\u{77}', '\u{78}',
+    '\u{79}', '\u{7A}', '\u{A1}', '\u{BF}', '\u{D0}', '\u{DD}', '\u{DE}', '\u{AE}',
+    '\u{5E}', '\u{A3}', '\u{A5}', '\u{B7}', '\u{A9}', '\u{A7}', '\u{B6}', '\u{BC}',
+    '\u{BD}', '\u{BE}', '\u{5B}', '\u{5D}', '\u{AF}', '\u{A8}', '\u{B4}', '\u{D7}',
+    '\u{7B}', '\u{41}', '\u{42}', '\u{43}', '\u{44}', '\u{45}', '\u{46}', '\u{47}',
+    '\u{48}', '\u{49}', '\u{AD}', '\u{F4}', '\u{F6}', '\u{F2}', '\u{F3}', '\u{F5}',
+    '\u{7D}', '\u{4A}', '\u{4B}', '\u{4C}', '\u{4D}', '\u{4E}', '\u{4F}', '\u{50}',
+    '\u{51}', '\u{52}', '\u{B9}', '\u{FB}', '\u{FC}', '\u{F9}', '\u{FA}', '\u{FF}',
+    '\u{5C}', '\u{F7}', '\u{53}', '\u{54}', '\u{55}', '\u{56}', '\u{57}', '\u{58}',
+    '\u{59}', '\u{5A}', '\u{B2}', '\u{D4}', '\u{D6}', '\u{D2}', '\u{D3}', '\u{D5}',
+    '\u{30}', '\u{31}', '\u{32}', '\u{33}', '\u{34}', '\u{35}', '\u{36}', '\u{37}',
+    '\u{38}', '\u{39}', '\u{B3}', '\u{DB}', '\u{DC}', '\u{D9}', '\u{DA}', '\u{9F}',
+];
+
+/// CP1047 moves `[ ] ^ ¬ Ý ¨` relative to CP037
+#[rustfmt::skip]
+const CP1047: [char; 256] = [
+    '\u{00}', '\u{01}', '\u{02}', '\u{03}', '\u{9C}', '\u{09}', '\u{86}', '\u{7F}',
+    '\u{97}', '\u{8D}', '\u{8E}', '\u{0B}', '\u{0C}', '\u{0D}', '\u{0E}', '\u{0F}',
+    '\u{10}', '\u{11}', '\u{12}', '\u{13}', '\u{9D}', '\u{85}', '\u{08}', '\u{87}',
+    '\u{18}', '\u{19}', '\u{92}', '\u{8F}', '\u{1C}', '\u{1D}', '\u{1E}', '\u{1F}',
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{0A}', '\u{17}', '\u{1B}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{05}', '\u{06}', '\u{07}',
+    '\u{90}', '\u{91}', '\u{16}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{04}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{14}', '\u{15}', '\u{9E}', '\u{1A}',
+    '\u{20}', '\u{A0}', '\u{E2}', '\u{E4}', '\u{E0}', '\u{E1}', '\u{E3}', '\u{E5}',
+    '\u{E7}', '\u{F1}', '\u{A2}', '\u{2E}', '\u{3C}', '\u{28}', '\u{2B}', '\u{7C}',
+    '\u{26}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{E8}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{EC}', '\u{DF}', '\u{21}', '\u{24}', '\u{2A}', '\u{29}', '\u{3B}', '\u{5E}',
+    '\u{2D}', '\u{2F}', '\u{C2}', '\u{C4}', '\u{C0}', '\u{C1}', '\u{C3}', '\u{C5}',
+    '\u{C7}', '\u{D1}', '\u{A6}', '\u{2C}', '\u{25}', '\u{5F}', '\u{3E}', '\u{3F}',
+    '\u{F8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{C8}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{CC}', '\u{60}', '\u{3A}', '\u{23}', '\u{40}', '\u{27}', '\u{3D}', '\u{22}',
+    '\u{D8}', '\u{61}', '\u{62}', '\u{63}', '\u{64}', '\u{65}', '\u{66}', '\u{67}',
+    '\u{68}', '\u{69}', '\u{AB}', '\u{BB}', '\u{F0}', '\u{FD}', '\u{FE}', '\u{B1}',
+    '\u{B0}', '\u{6A}', '\u{6B}', '\u{6C}', '\u{6D}', '\u{6E}', '\u{6F}', '\u{70}',
+    '\u{71}', '\u{72}', '\u{AA}', '\u{BA}', '\u{E6}', '\u{B8}', '\u{C6}', '\u{A4}',
+    '\u{B5}', '\u{7E}', '\u{73}', '\u{74}', '\u{75}', '\u{76}', '\u{77}', '\u{78}',
+    '\u{79}', '\u{7A}', '\u{A1}', '\u{BF}', '\u{D0}', '\u{5B}', '\u{DE}', '\u{AE}',
+    '\u{AC}', '\u{A3}', '\u{A5}', '\u{B7}', '\u{A9}', '\u{A7}', '\u{B6}', '\u{BC}',
+    '\u{BD}', '\u{BE}', '\u{DD}', '\u{A8}', '\u{AF}', '\u{5D}', '\u{B4}', '\u{D7}',
+    '\u{7B}', '\u{41}', '\u{42}', '\u{43}', '\u{44}', '\u{45}', '\u{46}', '\u{47}',
+    '\u{48}', '\u{49}', '\u{AD}', '\u{F4}', '\u{F6}', '\u{F2}', '\u{F3}', '\u{F5}',
+    '\u{7D}', '\u{4A}', '\u{4B}', '\u{4C}', '\u{4D}', '\u{4E}', '\u{4F}', '\u{50}',
+    '\u{51}', '\u{52}', '\u{B9}', '\u{FB}', '\u{FC}', '\u{F9}', '\u{FA}', '\u{FF}',
+    '\u{5C}', '\u{F7}', '\u{53}', '\u{54}', '\u{55}', '\u{56}', '\u{57}', '\u{58}',
+    '\u{59}', '\u{5A}', '\u{B2}', '\u{D4}', '\u{D6}', '\u{D2}', '\u{D3}', '\u{D5}',
+    '\u{30}', '\u{31}', '\u{32}', '\u{33}', '\u{34}', '\u{35}', '\u{36}', '\u{37}',
+    '\u{38}', '\u{39}', '\u{B3}', '\u{DB}', '\u{DC}', '\u{D9}', '\u{DA}', '\u{9F}',
+];
+
+#[rustfmt::skip]
+const CP500: [char; 256] = [
+    '\u{00}', '\u{01}', '\u{02}', '\u{03}', '\u{9C}', '\u{09}', '\u{86}', '\u{7F}',
+    '\u{97}', '\u{8D}', '\u{8E}', '\u{0B}', '\u{0C}', '\u{0D}', '\u{0E}', '\u{0F}',
+    '\u{10}', '\u{11}', '\u{12}', '\u{13}', '\u{9D}', '\u{85}', '\u{08}', '\u{87}',
+    '\u{18}', '\u{19}', '\u{92}', '\u{8F}', '\u{1C}', '\u{1D}', '\u{1E}', '\u{1F}',
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{0A}', '\u{17}', '\u{1B}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{05}', '\u{06}', '\u{07}',
+    '\u{90}', '\u{91}', '\u{16}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{04}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{14}', '\u{15}', '\u{9E}', '\u{1A}',
+    '\u{20}', '\u{A0}', '\u{E2}', '\u{E4}', '\u{E0}', '\u{E1}', '\u{E3}', '\u{E5}',
+    '\u{E7}', '\u{F1}', '\u{5B}', '\u{2E}', '\u{3C}', '\u{28}', '\u{2B}', '\u{21}',
+    '\u{26}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{E8}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{EC}', '\u{DF}', '\u{5D}', '\u{24}', '\u{2A}', '\u{29}', '\u{3B}', '\u{5E}',
+    '\u{2D}', '\u{2F}', '\u{C2}', '\u{C4}', '\u{C0}', '\u{C1}', '\u{C3}', '\u{C5}',
+    '\u{C7}', '\u{D1}', '\u{A6}', '\u{2C}', '\u{25}', '\u{5F}', '\u{3E}', '\u{3F}',
+    '\u{F8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{C8}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{CC}', '\u{60}', '\u{3A}', '\u{23}', '\u{40}', '\u{27}', '\u{3D}', '\u{22}',
+    '\u{D8}', '\u{61}', '\u{62}', '\u{63}', '\u{64}', '\u{65}', '\u{66}', '\u{67}',
+    '\u{68}', '\u{69}', '\u{AB}', '\u{BB}', '\u{F0}', '\u{FD}', '\u{FE}', '\u{B1}',
+    '\u{B0}', '\u{6A}', '\u{6B}', '\u{6C}', '\u{6D}', '\u{6E}', '\u{6F}', '\u{70}',
+    '\u{71}', '\u{72}', '\u{AA}', '\u{BA}', '\u{E6}', '\u{B8}', '\u{C6}', '\u{A4}',
+    '\u{B5}', '\u{7E}', '\u{73}', '\u{74}', '\u{75}', '\u{76}', '\u{77}', '\u{78}',
+    '\u{79}', '\u{7A}', '\u{A1}', '\u{BF}', '\u{D0}', '\u{DD}', '\u{DE}', '\u{AE}',
+    '\u{A2}', '\u{A3}', '\u{A5}', '\u{B7}', '\u{A9}', '\u{A7}', '\u{B6}', '\u{BC}',
+    '\u{BD}', '\u{BE}', '\u{AC}', '\u{7C}', '\u{AF}', '\u{A8}', '\u{B4}', '\u{D7}',
+    '\u{7B}', '\u{41}', '\u{42}', '\u{43}', '\u{44}', '\u{45}', '\u{46}', '\u{47}',
+    '\u{48}', '\u{49}', '\u{AD}', '\u{F4}', '\u{F6}', '\u{F2}', '\u{F3}', '\u{F5}',
+    '\u{7D}', '\u{4A}', '\u{4B}', '\u{4C}', '\u{4D}', '\u{4E}', '\u{4F}', '\u{50}',
+    '\u{51}', '\u{52}', '\u{B9}', '\u{FB}', '\u{FC}', '\u{F9}', '\u{FA}', '\u{FF}',
+    '\u{5C}', '\u{F7}', '\u{53}', '\u{54}', '\u{55}', '\u{56}', '\u{57}', '\u{58}',
+    '\u{59}', '\u{5A}', '\u{B2}', '\u{D4}', '\u{D6}', '\u{D2}', '\u{D3}', '\u{D5}',
+    '\u{30}', '\u{31}', '\u{32}', '\u{33}', '\u{34}', '\u{35}', '\u{36}', '\u{37}',
+    '\u{38}', '\u{39}', '\u{B3}', '\u{DB}', '\u{DC}', '\u{D9}', '\u{DA}', '\u{9F}',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_names() {
+        assert_eq!(Codepage::parse("cp037"), Some(Codepage::Cp037));
+        assert_eq!(Codepage::parse("IBM-1047"), Some(Codepage::Cp1047));
+        assert_eq!(Codepage::parse("500"), Some(Codepage::Cp500));
+        assert_eq!(Codepage::parse("utf8"), Some(Codepage::Utf8));
+        assert_eq!(Codepage::parse("cp1252"), None);
+    }
+
+    #[test]
+    fn decode_ebcdic_text() {
+        let hello = [0xC8, 0x85, 0x93, 0x93, 0x96, 0x40, 0xF1, 0xF2];
+        assert_eq!(Codepage::Cp037.decode(&hello), "Hello 12");
+        assert_eq!(Codepage::Cp1047.decode(&[0xAD, 0xBD]), "[]");
+        assert_eq!(Codepage::Cp037.decode(&[0xBA, 0xBB]), "[]");
+        assert_eq!(Codepage::Cp500.decode(&[0x4A, 0x5A]), "[]");
+    }
+
+    #[test]
+    fn round_trip_every_byte() {
+        for cp in [Codepage::Cp037, Codepage::Cp1047, Codepage::Cp500] {
+            let bytes: Vec<u8> = (0..=255).collect();
+            let text = cp.decode(&bytes);
+            assert_eq!(cp.encode(&text).unwrap(), bytes, "{}", cp.name());
+        }
+    }
+
+    #[test]
+    fn encode_rejects_unrepresentable() {
+        assert_eq!(Codepage::Cp037.encode("A\u{20AC}"), Err('\u{20AC}'));
+        assert_eq!(Codepage::Cp037.encode(" A").unwrap(), vec![0x40, 0xC1]);
+    }
+
+    #[test]
+    fn hex_literals() {
+        assert_eq!(hex_literal("X'C1c2'"), Some(vec![0xC1, 0xC2]));
+        assert_eq!(hex_literal("x'0'"), None);
+        assert_eq!(hex_literal("X''"), None);
+        assert_eq!(hex_literal("X'ZZ'"), None);
+        assert_eq!(hex_literal("C1"), None);
+    }
+
+    #[test]
+    fn hex_display() {
+        assert_eq!(Codepage::Cp037.to_hex("A 1"), "C140F1");
+        assert_eq!(Codepage::Utf8.to_hex("A \u{20AC}"), "4120??");
+    }
+}
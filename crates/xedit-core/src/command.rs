@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 
-use crate::target::Target;
+use crate::buffer::RecordFormat;
+use crate::codepage::Codepage;
+use crate::target::{parse_point_name, starts_with_search_keyword, ColumnTarget, Target};
 
 /// XEDIT command line commands
 #[derive(Debug, Clone)]
@@ -17,16 +19,34 @@ pub enum Command {
 
     // Search and replace
     Locate(Target),
+    /// CHANGE /from/to/ [target|count [occurrences [first]]]
     Change {
         from: String,
         to: String,
         target: Option<Target>,
         count: Option<usize>,
+        /// Occurrences changed per line (`*` is `usize::MAX`)
+        occurrences: usize,
+        /// Occurrence on each line at which changing starts (1-based)
+        first: usize,
     },
 
     // Editing
     Input(Option<String>),
     Delete(Option<Target>),
+    Replace(Option<String>),
+    Add(usize),
+    Overlay(String),
+    Copy(Target, Target), // COPY target1 target2 — lines up to target1, placed after target2
+    Move(Target, Target), // MOVE target1 target2
+
+    // Column pointer
+    CLocate(ColumnTarget),
+    CDelete(ColumnTarget), // CDELETE [column-target] — default 1
+    CInsert(String),
+    CAppend(String),
+    CReplace(String),
+    COverlay(String),
 
     // File operations
     File,
@@ -34,10 +54,12 @@ pub enum Command {
     Quit,
     QQuit,
     Get(String),
+    Xedit(Option<String>), // XEDIT [fn ft [fm]] — add/switch file in the ring
 
     // Settings
     Set(SetCommand),
     Query(String),
+    Extract(String), // EXTRACT /item/item/ — operands kept verbatim
 
     // Macros
     #[cfg(feature = "rexx")]
@@ -45,6 +67,7 @@ pub enum Command {
 
     // Undo
     Undo,
+    Redo,
 
     // Data stack
     Stack(usize), // STACK n — push n lines onto data stack (LIFO)
@@ -70,19 +93,79 @@ pub enum Command {
     Nop,
 }
 
+impl Command {
+    /// Name of a command that changes the file; these are refused while
+    /// the file is read-only (BROWSE)
+    pub fn modifying_name(&self) -> Option<&'static str> {
+        match self {
+            Command::Change { .. } => Some("CHANGE"),
+            Command::Input(_) => Some("INPUT"),
+            Command::Delete(_) => Some("DELETE"),
+            Command::Replace(_) => Some("REPLACE"),
+            Command::Add(_) => Some("ADD"),
+            Command::Overlay(_) => Some("OVERLAY"),
+            Command::Copy(..) => Some("COPY"),
+            Command::Move(..) => Some("MOVE"),
+            Command::CDelete(_) => Some("CDELETE"),
+            Command::CInsert(_) => Some("CINSERT"),
+            Command::CAppend(text) if !text.is_empty() => Some("CAPPEND"),
+            Command::CReplace(_) => Some("CREPLACE"),
+            Command::COverlay(_) => Some("COVERLAY"),
+            Command::File => Some("FILE"),
+            Command::Save => Some("SAVE"),
+            Command::Get(_) => Some("GET"),
+            Command::Undo => Some("UNDO"),
+            Command::Redo => Some("REDO"),
+            Command::Sort { .. } => Some("SORT"),
+            _ => None,
+        }
+    }
+}
+
 /// SET subcommands
 #[derive(Debug, Clone)]
 pub enum SetCommand {
     Trunc(usize),
     Zone(usize, usize),
+    /// SET RECFM F|V
+    Recfm(RecordFormat),
+    /// SET LRECL n
+    Lrecl(usize),
+    /// SET CODEPAGE name: encoding used when the file is saved
+    Codepage(Codepage),
     Number(bool),
     Prefix(bool),
+    /// SET PREFIX SYNONYM new old: `new` in the prefix area runs `old`
+    PrefixSynonym(String, String),
+    /// SET PENDING BLOCK name | OFF: mark (or clear) a pending user block
+    Pending(Option<String>),
     Scale(bool),
     CurLine(CurLinePosition),
-    Case(CaseSetting),
+    /// SET CASE Mixed|Upper [Respect|Ignore] (either operand may come alone)
+    Case(CaseSetting, Option<CaseSetting>),
+    /// SET SPILL OFF|ON|WORD
+    Spill(SpillSetting),
+    /// SET POINT .name [OFF]: name the current line, or drop the name
+    Point(String, bool),
+    /// SET SELECT [+|-]n [target]: selection level of the lines in target
+    Select {
+        level: i64,
+        relative: bool,
+        target: Option<Target>,
+    },
+    /// SET DISPLAY n [m|*]: selection levels shown (`*` is `usize::MAX`)
+    Display(usize, usize),
+    /// SET SCOPE DISPLAY|ALL: whether commands act on lines not displayed
+    Scope(ScopeSetting),
     Wrap(bool),
     Hex(bool),
     Stay(bool),
+    /// SET ARBCHAR ON|OFF [char]
+    Arbchar(bool, Option<char>),
+    /// SET UNDO n: number of changes kept for UNDO (0 = off)
+    Undo(usize),
+    /// SET UNDOFILE ON|OFF: keep undo history in a sidecar file
+    UndoFile(bool),
     MsgLine(usize),
     Verify(usize, usize),
     Shadow(bool),
@@ -106,6 +189,28 @@ pub enum CaseSetting {
     Ignore,
 }
 
+/// What happens to text pushed past the truncation column
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpillSetting {
+    /// The text is lost
+    #[default]
+    Off,
+    /// The text moves onto a new line
+    On,
+    /// Whole words move onto a new line
+    Word,
+}
+
+/// Lines that commands and targets act on
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScopeSetting {
+    /// Only lines whose selection level is displayed
+    #[default]
+    Display,
+    /// Every line, displayed or not
+    All,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum CurLinePosition {
     Row(usize),
@@ -136,6 +241,8 @@ pub enum CursorTarget {
 pub struct CommandResult {
     pub action: CommandAction,
     pub message: Option<String>,
+    /// Return code seen by macros when the command succeeds (3 = Truncated)
+    pub rc: i32,
 }
 
 #[derive(Debug, PartialEq)]
@@ -144,6 +251,8 @@ pub enum CommandAction {
     Quit,
     EnterInput,
     Refresh,
+    /// Host should edit another file in the ring (None = next file)
+    Edit(Option<String>),
 }
 
 impl CommandResult {
@@ -151,6 +260,7 @@ impl CommandResult {
         Self {
             action: CommandAction::Continue,
             message: None,
+            rc: 0,
         }
     }
 
@@ -158,6 +268,7 @@ impl CommandResult {
         Self {
             action: CommandAction::Continue,
             message: Some(msg.into()),
+            rc: 0,
         }
     }
 
@@ -165,6 +276,7 @@ impl CommandResult {
         Self {
             action: CommandAction::Quit,
             message: None,
+            rc: 0,
         }
     }
 
@@ -172,6 +284,7 @@ impl CommandResult {
         Self {
             action: CommandAction::EnterInput,
             message: None,
+            rc: 0,
         }
     }
 
@@ -179,6 +292,25 @@ impl CommandResult {
         Self {
             action: CommandAction::Refresh,
             message: None,
+            rc: 0,
+        }
+    }
+
+    /// Mark data as truncated by SET TRUNC: "Truncated" and RC=3
+    pub fn truncated(mut self) -> Self {
+        self.message = Some(match self.message {
+            Some(msg) => format!("{}; Truncated", msg),
+            None => "Truncated".to_string(),
+        });
+        self.rc = 3;
+        self
+    }
+
+    pub fn edit(spec: Option<String>) -> Self {
+        Self {
+            action: CommandAction::Edit(spec),
+            message: None,
+            rc: 0,
         }
     }
 }
@@ -187,13 +319,22 @@ impl CommandResult {
 // Each entry: (full_name, minimum_abbreviation_length)
 // Follows IBM XEDIT abbreviation conventions.
 const COMMAND_TABLE: &[(&str, usize)] = &[
+    ("ADD", 1),      // A
     ("ALL", 3),      // ALL
     ("BACKWARD", 1), // B
     ("BOTTOM", 2),   // BO
+    ("CAPPEND", 2),  // CA
+    ("CDELETE", 2),  // CD
     ("CHANGE", 1),   // C
+    ("CINSERT", 2),  // CI
+    ("CLOCATE", 2),  // CL
+    ("COPY", 4),     // COPY
+    ("COVERLAY", 2), // CO
+    ("CREPLACE", 2), // CR
     ("CURSOR", 3),   // CUR
     ("DELETE", 3),   // DEL
     ("DOWN", 2),     // DO
+    ("EXTRACT", 3),  // EXT
     ("FILE", 4),     // FILE
     ("FORWARD", 1),  // F
     ("GET", 3),      // GET
@@ -202,12 +343,16 @@ const COMMAND_TABLE: &[(&str, usize)] = &[
     ("LEFT", 2),     // LE
     ("LOCATE", 1),   // L (but see disambiguation below)
     ("MACRO", 5),    // MACRO
+    ("MOVE", 4),     // MOVE
     ("NEXT", 1),     // N
+    ("OVERLAY", 1),  // O
     ("QQUIT", 2),    // QQ
     ("QUERY", 2),    // QU
     ("QUEUE", 3),    // QUE (avoids conflict with QUERY at QU)
     ("QUIT", 4),     // QUIT
+    ("REDO", 4),     // REDO
     ("REFRESH", 3),  // REF
+    ("REPLACE", 1),  // R
     ("RIGHT", 2),    // RI
     ("SAVE", 2),     // SA
     ("SET", 3),      // SET
@@ -216,6 +361,7 @@ const COMMAND_TABLE: &[(&str, usize)] = &[
     ("TOP", 1),      // T
     ("UNDO", 4),     // UNDO
     ("UP", 1),       // U
+    ("XEDIT", 1),    // X
 ];
 
 fn lookup_command(input: &str) -> Option<&'static str> {
@@ -251,8 +397,12 @@ pub fn parse_command(input: &str) -> Result<Command, String> {
         return Ok(Command::Nop);
     }
 
-    // /target/ is a LOCATE shorthand
-    if input.starts_with('/') {
+    // :n, .name, /target/, -/target/, ~/target/, RE/target/ and
+    // WORD/target/ are LOCATE shorthands
+    if input.starts_with([':', '.', '/', '~', '¬'])
+        || input.starts_with("-/")
+        || starts_with_search_keyword(input)
+    {
         let target = Target::parse(input)?;
         return Ok(Command::Locate(target));
     }
@@ -292,6 +442,50 @@ pub fn parse_command(input: &str) -> Result<Command, String> {
                 Ok(Command::Delete(Some(Target::parse(args)?)))
             }
         }
+        "REPLACE" => {
+            if args.is_empty() {
+                Ok(Command::Replace(None))
+            } else {
+                Ok(Command::Replace(Some(
+                    text_operand(input, cmd_word).to_string(),
+                )))
+            }
+        }
+        "ADD" => Ok(Command::Add(parse_optional_count(args)?)),
+        "OVERLAY" => Ok(Command::Overlay(text_operand(input, cmd_word).to_string())),
+        "CLOCATE" => {
+            if args.is_empty() {
+                return Err("CLOCATE requires a column target".to_string());
+            }
+            Ok(Command::CLocate(ColumnTarget::parse(args)?))
+        }
+        "CDELETE" => {
+            if args.is_empty() {
+                Ok(Command::CDelete(ColumnTarget::Relative(1)))
+            } else {
+                Ok(Command::CDelete(ColumnTarget::parse(args)?))
+            }
+        }
+        "CAPPEND" => Ok(Command::CAppend(text_operand(input, cmd_word).to_string())),
+        "CINSERT" | "CREPLACE" | "COVERLAY" => {
+            let text = text_operand(input, cmd_word).to_string();
+            if text.is_empty() {
+                return Err(format!("{} requires text", cmd_name));
+            }
+            Ok(match cmd_name {
+                "CINSERT" => Command::CInsert(text),
+                "CREPLACE" => Command::CReplace(text),
+                _ => Command::COverlay(text),
+            })
+        }
+        "COPY" => {
+            let (from, to) = parse_two_targets(args, "COPY")?;
+            Ok(Command::Copy(from, to))
+        }
+        "MOVE" => {
+            let (from, to) = parse_two_targets(args, "MOVE")?;
+            Ok(Command::Move(from, to))
+        }
         "FILE" => Ok(Command::File),
         "SAVE" => Ok(Command::Save),
         "QUIT" => Ok(Command::Quit),
@@ -303,8 +497,22 @@ pub fn parse_command(input: &str) -> Result<Command, String> {
                 Ok(Command::Get(args.to_string()))
             }
         }
+        "XEDIT" => {
+            if args.is_empty() {
+                Ok(Command::Xedit(None))
+            } else {
+                Ok(Command::Xedit(Some(args.to_string())))
+            }
+        }
         "SET" => parse_set_args(args),
         "QUERY" => Ok(Command::Query(args.to_string())),
+        "EXTRACT" => {
+            if args.is_empty() {
+                Err("EXTRACT requires /item/ operands".to_string())
+            } else {
+                Ok(Command::Extract(args.to_string()))
+            }
+        }
         #[cfg(feature = "rexx")]
         "MACRO" => {
             if args.is_empty() {
@@ -324,6 +532,7 @@ pub fn parse_command(input: &str) -> Result<Command, String> {
         "STACK" => Ok(Command::Stack(parse_optional_count(args)?)),
         "QUEUE" => Ok(Command::Queue(parse_optional_count(args)?)),
         "UNDO" => Ok(Command::Undo),
+        "REDO" => Ok(Command::Redo),
         "REFRESH" => Ok(Command::Refresh),
         "HELP" => Ok(Command::Help),
         _ => Err(format!("Unknown command: {}", cmd_word)),
@@ -337,6 +546,36 @@ fn split_first_word(input: &str) -> (&str, &str) {
     }
 }
 
+/// Text operand of REPLACE/OVERLAY: everything after the command word and
+/// one separating blank, so leading blanks in the text are kept.
+fn text_operand<'a>(input: &'a str, cmd_word: &str) -> &'a str {
+    let rest = &input[cmd_word.len()..];
+    rest.strip_prefix(' ').unwrap_or(rest)
+}
+
+/// Split off the first target of `args`, keeping `/.../` strings
+/// (which may contain blanks) intact.
+fn split_target(args: &str) -> (&str, &str) {
+    let mut in_delim = false;
+    for (i, c) in args.char_indices() {
+        if c == '/' {
+            in_delim = !in_delim;
+        } else if c.is_whitespace() && !in_delim {
+            return (&args[..i], args[i..].trim());
+        }
+    }
+    (args, "")
+}
+
+/// Parse `target1 target2` operands (COPY, MOVE)
+fn parse_two_targets(args: &str, cmd: &str) -> Result<(Target, Target), String> {
+    let (first, rest) = split_target(args);
+    if first.is_empty() || rest.is_empty() {
+        return Err(format!("{} requires two targets", cmd));
+    }
+    Ok((Target::parse(first)?, Target::parse(rest)?))
+}
+
 fn parse_optional_count(args: &str) -> Result<usize, String> {
     if args.is_empty() {
         Ok(1)
@@ -350,6 +589,9 @@ fn parse_change_args(args: &str) -> Result<Command, String> {
     if args.is_empty() {
         return Err("CHANGE requires /old/new/ arguments".to_string());
     }
+    if let Some(hex) = parse_hex_change(args) {
+        return hex;
+    }
     let delim = args.chars().next().unwrap();
     let rest = &args[delim.len_utf8()..];
 
@@ -368,25 +610,64 @@ fn parse_change_args(args: &str) -> Result<Command, String> {
         (after_from.to_string(), "")
     };
 
-    let (target, count) = if remainder.is_empty() {
+    let (first_op, rest) = split_target(remainder);
+    let (target, count) = if first_op.is_empty() {
         (None, None)
-    } else if let Ok(n) = remainder.parse::<usize>() {
+    } else if let Ok(n) = first_op.parse::<usize>() {
         (None, Some(n))
     } else {
-        let parts: Vec<&str> = remainder.splitn(2, char::is_whitespace).collect();
-        let target = Some(Target::parse(parts[0])?);
-        let count = parts.get(1).and_then(|s| s.trim().parse::<usize>().ok());
-        (target, count)
+        (Some(Target::parse(first_op)?), None)
+    };
+
+    let mut ops = rest.split_whitespace();
+    let occurrences = match ops.next() {
+        None => 1,
+        Some("*") => usize::MAX,
+        Some(n) => n
+            .parse::<usize>()
+            .map_err(|_| format!("CHANGE: invalid occurrence count: {}", n))?,
     };
+    let first = match ops.next() {
+        None => 1,
+        Some(n) => match n.parse::<usize>() {
+            Ok(p) if p > 0 => p,
+            _ => return Err(format!("CHANGE: invalid starting occurrence: {}", n)),
+        },
+    };
+    if let Some(extra) = ops.next() {
+        return Err(format!("CHANGE: too many operands: {}", extra));
+    }
 
     Ok(Command::Change {
         from,
         to,
         target,
         count,
+        occurrences,
+        first,
     })
 }
 
+/// Parse the undelimited hex form `CHANGE X'C1'X'C2' [operands]`.
+///
+/// The strings stay in `X'..'` notation; they are decoded with the file's
+/// code page when the change runs with SET HEX ON.
+fn parse_hex_change(args: &str) -> Option<Result<Command, String>> {
+    let hex_len = |s: &str| {
+        let rest = s.strip_prefix(['X', 'x'])?.strip_prefix('\'')?;
+        rest.find('\'').map(|end| end + 3)
+    };
+    let from_len = hex_len(args)?;
+    let (from, rest) = args.split_at(from_len);
+    let Some(to_len) = hex_len(rest) else {
+        return Some(Err(format!("CHANGE: expected X'..' after {}", from)));
+    };
+    let (to, remainder) = rest.split_at(to_len);
+    // Reuse the delimited parser for the target and occurrence operands
+    let delimited = format!("/{}/{}/ {}", from, to, remainder.trim());
+    Some(parse_change_args(&delimited))
+}
+
 /// Parse CURSOR HOME | CURSOR FILE line col
 fn parse_cursor_args(args: &str) -> Result<Command, String> {
     if args.is_empty() {
@@ -512,10 +793,69 @@ fn parse_set_args(args: &str) -> Result<Command, String> {
             .parse::<usize>()
             .map_err(|_| "SET TRUNC requires a column number".to_string())?;
         Ok(Command::Set(SetCommand::Trunc(n)))
+    } else if matches_abbrev(&subcmd_upper, "ZONE", 1) {
+        // SET ZONE left right|*
+        let parts: Vec<&str> = subargs.split_whitespace().collect();
+        if parts.len() != 2 {
+            return Err("SET ZONE requires left and right columns".to_string());
+        }
+        let left = parts[0]
+            .parse::<usize>()
+            .map_err(|_| format!("SET ZONE: invalid column: {}", parts[0]))?;
+        let right = if parts[1] == "*" {
+            usize::MAX
+        } else {
+            parts[1]
+                .parse::<usize>()
+                .map_err(|_| format!("SET ZONE: invalid column: {}", parts[1]))?
+        };
+        if left == 0 || left > right {
+            return Err(format!("SET ZONE: invalid zone {} {}", parts[0], parts[1]));
+        }
+        Ok(Command::Set(SetCommand::Zone(left, right)))
+    } else if matches_abbrev(&subcmd_upper, "RECFM", 5) {
+        match subargs.to_uppercase().as_str() {
+            "F" => Ok(Command::Set(SetCommand::Recfm(RecordFormat::Fixed))),
+            "V" => Ok(Command::Set(SetCommand::Recfm(RecordFormat::Variable))),
+            _ => Err(format!("SET RECFM: expected F or V, got: {}", subargs)),
+        }
+    } else if matches_abbrev(&subcmd_upper, "LRECL", 5) {
+        match subargs.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(Command::Set(SetCommand::Lrecl(n))),
+            _ => Err(format!("SET LRECL: invalid record length: {}", subargs)),
+        }
+    } else if matches_abbrev(&subcmd_upper, "CODEPAGE", 3) {
+        Codepage::parse(subargs)
+            .map(|cp| Command::Set(SetCommand::Codepage(cp)))
+            .ok_or_else(|| format!("SET CODEPAGE: unknown code page: {}", subargs))
     } else if matches_abbrev(&subcmd_upper, "NUMBER", 2) {
         Ok(Command::Set(SetCommand::Number(parse_on_off(subargs)?)))
     } else if matches_abbrev(&subcmd_upper, "PREFIX", 2) {
+        let (word, rest) = split_first_word(subargs);
+        if matches_abbrev(&word.to_uppercase(), "SYNONYM", 3) {
+            let names: Vec<&str> = rest.split_whitespace().collect();
+            let [new, old] = names[..] else {
+                return Err("SET PREFIX SYNONYM requires a new and an old name".to_string());
+            };
+            return Ok(Command::Set(SetCommand::PrefixSynonym(
+                new.to_lowercase(),
+                old.to_lowercase(),
+            )));
+        }
         Ok(Command::Set(SetCommand::Prefix(parse_on_off(subargs)?)))
+    } else if matches_abbrev(&subcmd_upper, "PENDING", 4) {
+        let (word, name) = split_first_word(subargs);
+        let word = word.to_uppercase();
+        if word == "OFF" && name.is_empty() {
+            Ok(Command::Set(SetCommand::Pending(None)))
+        } else if word == "BLOCK" && !name.is_empty() && !name.contains(char::is_whitespace) {
+            Ok(Command::Set(SetCommand::Pending(Some(name.to_uppercase()))))
+        } else {
+            Err(format!(
+                "SET PENDING: expected BLOCK name or OFF, got: {}",
+                subargs
+            ))
+        }
     } else if matches_abbrev(&subcmd_upper, "SCALE", 2) {
         Ok(Command::Set(SetCommand::Scale(parse_on_off(subargs)?)))
     } else if matches_abbrev(&subcmd_upper, "CURLINE", 3) {
@@ -529,19 +869,93 @@ fn parse_set_args(args: &str) -> Result<Command, String> {
             }
         }
     } else if matches_abbrev(&subcmd_upper, "CASE", 2) {
-        let setting = match subargs.to_uppercase().as_str() {
-            "M" | "MIXED" => CaseSetting::Mixed,
-            "U" | "UPPER" => CaseSetting::Upper,
-            "R" | "RESPECT" => CaseSetting::Respect,
-            "I" | "IGNORE" => CaseSetting::Ignore,
-            _ => {
-                return Err(format!(
-                    "SET CASE: expected MIXED/UPPER/RESPECT/IGNORE, got: {}",
-                    subargs
-                ))
+        let parse = |word: &str| match word.to_uppercase().as_str() {
+            "M" | "MIXED" => Ok(CaseSetting::Mixed),
+            "U" | "UPPER" => Ok(CaseSetting::Upper),
+            "R" | "RESPECT" => Ok(CaseSetting::Respect),
+            "I" | "IGNORE" => Ok(CaseSetting::Ignore),
+            _ => Err(format!(
+                "SET CASE: expected MIXED/UPPER/RESPECT/IGNORE, got: {}",
+                subargs
+            )),
+        };
+        let (first, rest) = split_first_word(subargs);
+        let first = parse(first)?;
+        let second = match rest.trim() {
+            "" => None,
+            word => {
+                let second = parse(word)?;
+                // IBM order: Mixed|Upper first, then Respect|Ignore
+                if matches!(first, CaseSetting::Respect | CaseSetting::Ignore)
+                    || matches!(second, CaseSetting::Mixed | CaseSetting::Upper)
+                {
+                    return Err(format!("SET CASE: invalid operands: {}", subargs));
+                }
+                Some(second)
             }
         };
-        Ok(Command::Set(SetCommand::Case(setting)))
+        Ok(Command::Set(SetCommand::Case(first, second)))
+    } else if matches_abbrev(&subcmd_upper, "SPILL", 3) {
+        let setting = match subargs.to_uppercase().as_str() {
+            "OFF" => SpillSetting::Off,
+            "ON" => SpillSetting::On,
+            "WORD" => SpillSetting::Word,
+            _ => return Err(format!("SET SPILL: expected ON/OFF/WORD, got: {}", subargs)),
+        };
+        Ok(Command::Set(SetCommand::Spill(setting)))
+    } else if matches_abbrev(&subcmd_upper, "POINT", 1) {
+        let (name, rest) = split_first_word(subargs);
+        let name = parse_point_name(name).map_err(|e| format!("SET POINT: {}", e))?;
+        match rest.to_uppercase().as_str() {
+            "" => Ok(Command::Set(SetCommand::Point(name, true))),
+            "OFF" => Ok(Command::Set(SetCommand::Point(name, false))),
+            _ => Err(format!("SET POINT: invalid operand: {}", rest)),
+        }
+    } else if matches_abbrev(&subcmd_upper, "SELECT", 3) {
+        let (level_str, rest) = split_first_word(subargs);
+        let relative = level_str.starts_with(['+', '-']);
+        let level = level_str
+            .parse::<i64>()
+            .ok()
+            .filter(|n| relative || *n >= 0)
+            .ok_or_else(|| format!("SET SELECT: invalid selection level: {}", level_str))?;
+        let target = match rest.trim() {
+            "" => None,
+            t => Some(Target::parse(t)?),
+        };
+        Ok(Command::Set(SetCommand::Select {
+            level,
+            relative,
+            target,
+        }))
+    } else if matches_abbrev(&subcmd_upper, "DISPLAY", 4) {
+        let parse_level = |word: &str| {
+            word.parse::<usize>()
+                .map_err(|_| format!("SET DISPLAY: invalid selection level: {}", word))
+        };
+        let (low_str, high_str) = split_first_word(subargs);
+        let low = parse_level(low_str)?;
+        let high = match high_str.trim() {
+            "" => low,
+            "*" => usize::MAX,
+            word => parse_level(word)?,
+        };
+        if high < low {
+            return Err(format!("SET DISPLAY: invalid range: {}", subargs));
+        }
+        Ok(Command::Set(SetCommand::Display(low, high)))
+    } else if matches_abbrev(&subcmd_upper, "SCOPE", 5) {
+        let upper = subargs.to_uppercase();
+        if matches_abbrev(&upper, "DISPLAY", 1) {
+            Ok(Command::Set(SetCommand::Scope(ScopeSetting::Display)))
+        } else if matches_abbrev(&upper, "ALL", 1) {
+            Ok(Command::Set(SetCommand::Scope(ScopeSetting::All)))
+        } else {
+            Err(format!(
+                "SET SCOPE: expected DISPLAY or ALL, got: {}",
+                subargs
+            ))
+        }
     } else if matches_abbrev(&subcmd_upper, "WRAP", 2) {
         Ok(Command::Set(SetCommand::Wrap(parse_on_off(subargs)?)))
     } else if matches_abbrev(&subcmd_upper, "HEX", 3) {
@@ -580,6 +994,28 @@ fn parse_set_args(args: &str) -> Result<Command, String> {
         Ok(Command::Set(SetCommand::Shadow(parse_on_off(subargs)?)))
     } else if matches_abbrev(&subcmd_upper, "STAY", 2) {
         Ok(Command::Set(SetCommand::Stay(parse_on_off(subargs)?)))
+    } else if matches_abbrev(&subcmd_upper, "ARBCHAR", 3) {
+        let (on_off, ch) = split_first_word(subargs);
+        let on = parse_on_off(on_off)?;
+        let mut chars = ch.chars();
+        let arbchar = chars.next();
+        if chars.next().is_some() {
+            return Err(format!(
+                "SET ARBCHAR: expected a single character, got: {}",
+                ch
+            ));
+        }
+        Ok(Command::Set(SetCommand::Arbchar(on, arbchar)))
+    } else if matches_abbrev(&subcmd_upper, "UNDOFILE", 5) {
+        Ok(Command::Set(SetCommand::UndoFile(parse_on_off(subargs)?)))
+    } else if matches_abbrev(&subcmd_upper, "UNDO", 3) {
+        match subargs.to_uppercase().as_str() {
+            "OFF" => Ok(Command::Set(SetCommand::Undo(0))),
+            n => n
+                .parse::<usize>()
+                .map(|n| Command::Set(SetCommand::Undo(n)))
+                .map_err(|_| format!("SET UNDO: expected a count or OFF, got: {}", subargs)),
+        }
     } else if matches_abbrev(&subcmd_upper, "MACRO", 3) {
         // SET MACRO PATH dir1 dir2 ...
         let (next_word, rest) = split_first_word(subargs);
@@ -648,6 +1084,10 @@ mod tests {
             Command::Locate(Target::StringForward(s)) => assert_eq!(s, "hello"),
             other => panic!("Expected Locate, got {:?}", other),
         }
+        match parse_command("-/hello/").unwrap() {
+            Command::Locate(Target::StringBackward(s)) => assert_eq!(s, "hello"),
+            other => panic!("Expected backward Locate, got {:?}", other),
+        }
     }
 
     #[test]
@@ -656,6 +1096,14 @@ mod tests {
             Command::Locate(Target::StringForward(s)) => assert_eq!(s, "hello"),
             other => panic!("Expected Locate, got {:?}", other),
         }
+        match parse_command(":12").unwrap() {
+            Command::Locate(Target::Absolute(12)) => {}
+            other => panic!("Expected Locate :12, got {:?}", other),
+        }
+        match parse_command(".top").unwrap() {
+            Command::Locate(Target::Point(name)) => assert_eq!(name, "TOP"),
+            other => panic!("Expected Locate .TOP, got {:?}", other),
+        }
     }
 
     #[test]
@@ -677,6 +1125,110 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_xedit_file() {
+        match parse_command("x profile xedit").unwrap() {
+            Command::Xedit(Some(spec)) => assert_eq!(spec, "profile xedit"),
+            other => panic!("Expected Xedit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_xedit_no_args() {
+        match parse_command("xedit").unwrap() {
+            Command::Xedit(None) => {}
+            other => panic!("Expected Xedit(None), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_extract() {
+        match parse_command("ext /curline/size/").unwrap() {
+            Command::Extract(items) => assert_eq!(items, "/curline/size/"),
+            other => panic!("Expected Extract, got {:?}", other),
+        }
+        assert!(parse_command("extract").is_err());
+    }
+
+    #[test]
+    fn parse_replace() {
+        match parse_command("r new text").unwrap() {
+            Command::Replace(Some(text)) => assert_eq!(text, "new text"),
+            other => panic!("Expected Replace, got {:?}", other),
+        }
+        match parse_command("replace").unwrap() {
+            Command::Replace(None) => {}
+            other => panic!("Expected Replace(None), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_replace_keeps_leading_blanks() {
+        match parse_command("replace    indented").unwrap() {
+            Command::Replace(Some(text)) => assert_eq!(text, "   indented"),
+            other => panic!("Expected Replace, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_add() {
+        match parse_command("a 3").unwrap() {
+            Command::Add(3) => {}
+            other => panic!("Expected Add(3), got {:?}", other),
+        }
+        match parse_command("add").unwrap() {
+            Command::Add(1) => {}
+            other => panic!("Expected Add(1), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_overlay() {
+        match parse_command("o  X_Y").unwrap() {
+            Command::Overlay(text) => assert_eq!(text, " X_Y"),
+            other => panic!("Expected Overlay, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_copy_two_targets() {
+        match parse_command("copy 2 :5").unwrap() {
+            Command::Copy(Target::Relative(2), Target::Absolute(5)) => {}
+            other => panic!("Expected Copy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_move_string_targets() {
+        match parse_command("move /end of/ -/top here/").unwrap() {
+            Command::Move(Target::StringForward(a), Target::StringBackward(b)) => {
+                assert_eq!(a, "end of");
+                assert_eq!(b, "top here");
+            }
+            other => panic!("Expected Move, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_copy_requires_two_targets() {
+        assert!(parse_command("copy 2").is_err());
+        assert!(parse_command("move").is_err());
+    }
+
+    #[test]
+    fn parse_set_zone() {
+        match parse_command("set zone 8 72").unwrap() {
+            Command::Set(SetCommand::Zone(8, 72)) => {}
+            other => panic!("Expected Zone(8, 72), got {:?}", other),
+        }
+        match parse_command("set z 10 *").unwrap() {
+            Command::Set(SetCommand::Zone(10, usize::MAX)) => {}
+            other => panic!("Expected Zone(10, MAX), got {:?}", other),
+        }
+        assert!(parse_command("set zone 20 10").is_err());
+        assert!(parse_command("set zone 5").is_err());
+    }
+
     #[test]
     fn parse_set_number() {
         match parse_command("set nu on").unwrap() {
@@ -688,11 +1240,34 @@ mod tests {
     #[test]
     fn parse_set_case_respect() {
         match parse_command("set ca respect").unwrap() {
-            Command::Set(SetCommand::Case(CaseSetting::Respect)) => {}
+            Command::Set(SetCommand::Case(CaseSetting::Respect, None)) => {}
             other => panic!("Expected Set Case Respect, got {:?}", other),
         }
     }
 
+    #[test]
+    fn parse_set_spill() {
+        match parse_command("SET SPILL WORD").unwrap() {
+            Command::Set(SetCommand::Spill(SpillSetting::Word)) => {}
+            other => panic!("Expected Set(Spill(Word)), got {:?}", other),
+        }
+        match parse_command("SET SPI off").unwrap() {
+            Command::Set(SetCommand::Spill(SpillSetting::Off)) => {}
+            other => panic!("Expected Set(Spill(Off)), got {:?}", other),
+        }
+        assert!(parse_command("SET SPILL LINE").is_err());
+    }
+
+    #[test]
+    fn parse_set_case_two_operands() {
+        match parse_command("SET CASE U I").unwrap() {
+            Command::Set(SetCommand::Case(CaseSetting::Upper, Some(CaseSetting::Ignore))) => {}
+            other => panic!("Expected Set Case Upper Ignore, got {:?}", other),
+        }
+        assert!(parse_command("SET CASE R U").is_err());
+        assert!(parse_command("SET CASE U M").is_err());
+    }
+
     #[test]
     fn parse_nop() {
         match parse_command("").unwrap() {
@@ -1094,6 +1669,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_redo() {
+        match parse_command("redo").unwrap() {
+            Command::Redo => {}
+            other => panic!("Expected Redo, got {:?}", other),
+        }
+        assert!(parse_command("red").is_err());
+    }
+
+    #[test]
+    fn parse_set_undo() {
+        match parse_command("SET UNDO 50").unwrap() {
+            Command::Set(SetCommand::Undo(50)) => {}
+            other => panic!("Expected Set(Undo(50)), got {:?}", other),
+        }
+        match parse_command("SET UND OFF").unwrap() {
+            Command::Set(SetCommand::Undo(0)) => {}
+            other => panic!("Expected Set(Undo(0)), got {:?}", other),
+        }
+        match parse_command("SET UNDOFILE ON").unwrap() {
+            Command::Set(SetCommand::UndoFile(true)) => {}
+            other => panic!("Expected Set(UndoFile(true)), got {:?}", other),
+        }
+        assert!(parse_command("SET UNDO many").is_err());
+    }
+
     // -- Error handling tests --
 
     #[test]
@@ -1160,4 +1761,183 @@ mod tests {
             other => panic!("Expected Queue(3), got {:?}", other),
         }
     }
+
+    #[test]
+    fn parse_locate_regex_and_not_shorthand() {
+        match parse_command("re/^a.c/").unwrap() {
+            Command::Locate(Target::RegexForward(re)) => assert_eq!(re.as_str(), "^a.c"),
+            other => panic!("Expected regex Locate, got {:?}", other),
+        }
+        match parse_command("~/foo/").unwrap() {
+            Command::Locate(Target::Not(_)) => {}
+            other => panic!("Expected Not Locate, got {:?}", other),
+        }
+        match parse_command("DELETE WORD/end/").unwrap() {
+            Command::Delete(Some(Target::WordForward(w))) => assert_eq!(w, "end"),
+            other => panic!("Expected word Delete, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_change_occurrence_operands() {
+        match parse_command("c /x/y/ * * 2").unwrap() {
+            Command::Change {
+                target: Some(Target::Star),
+                count: None,
+                occurrences: usize::MAX,
+                first: 2,
+                ..
+            } => {}
+            other => panic!("Expected Change * * 2, got {:?}", other),
+        }
+        match parse_command("c /x/y/ :5 3").unwrap() {
+            Command::Change {
+                target: Some(Target::Absolute(5)),
+                occurrences: 3,
+                first: 1,
+                ..
+            } => {}
+            other => panic!("Expected Change :5 3, got {:?}", other),
+        }
+        assert!(parse_command("c /x/y/ * * 0").is_err());
+        assert!(parse_command("c /x/y/ * x").is_err());
+    }
+
+    #[test]
+    fn parse_set_arbchar() {
+        match parse_command("set arb on").unwrap() {
+            Command::Set(SetCommand::Arbchar(true, None)) => {}
+            other => panic!("Expected Set(Arbchar(true, None)), got {:?}", other),
+        }
+        match parse_command("SET ARBCHAR ON %").unwrap() {
+            Command::Set(SetCommand::Arbchar(true, Some('%'))) => {}
+            other => panic!("Expected Set(Arbchar(true, %)), got {:?}", other),
+        }
+        assert!(parse_command("SET ARBCHAR ON ab").is_err());
+        assert!(parse_command("SET ARBCHAR MAYBE").is_err());
+    }
+
+    #[test]
+    fn parse_set_recfm_and_lrecl() {
+        match parse_command("SET RECFM F").unwrap() {
+            Command::Set(SetCommand::Recfm(RecordFormat::Fixed)) => {}
+            other => panic!("Expected Set(Recfm(Fixed)), got {:?}", other),
+        }
+        match parse_command("set lrecl 133").unwrap() {
+            Command::Set(SetCommand::Lrecl(133)) => {}
+            other => panic!("Expected Set(Lrecl(133)), got {:?}", other),
+        }
+        assert!(parse_command("SET RECFM U").is_err());
+        assert!(parse_command("SET LRECL 0").is_err());
+    }
+
+    #[test]
+    fn parse_set_codepage() {
+        match parse_command("SET CODEPAGE 1047").unwrap() {
+            Command::Set(SetCommand::Codepage(Codepage::Cp1047)) => {}
+            other => panic!("Expected Set(Codepage(Cp1047)), got {:?}", other),
+        }
+        assert!(parse_command("SET CODEPAGE KOI8").is_err());
+    }
+
+    #[test]
+    fn parse_change_hex_strings() {
+        match parse_command("CHANGE X'C1'X'C2' * *").unwrap() {
+            Command::Change {
+                from,
+                to,
+                target: Some(Target::Star),
+                occurrences: usize::MAX,
+                ..
+            } => {
+                assert_eq!(from, "X'C1'");
+                assert_eq!(to, "X'C2'");
+            }
+            other => panic!("Expected hex Change, got {:?}", other),
+        }
+        assert!(parse_command("CHANGE X'C1'").is_err());
+    }
+
+    #[test]
+    fn parse_column_commands() {
+        match parse_command("cl :5").unwrap() {
+            Command::CLocate(ColumnTarget::Absolute(5)) => {}
+            other => panic!("Expected CLocate(:5), got {:?}", other),
+        }
+        match parse_command("cd").unwrap() {
+            Command::CDelete(ColumnTarget::Relative(1)) => {}
+            other => panic!("Expected CDelete(1), got {:?}", other),
+        }
+        match parse_command("co  a_b").unwrap() {
+            Command::COverlay(text) => assert_eq!(text, " a_b"),
+            other => panic!("Expected COverlay, got {:?}", other),
+        }
+        match parse_command("copy 2 :5").unwrap() {
+            Command::Copy(..) => {}
+            other => panic!("Expected Copy, got {:?}", other),
+        }
+        match parse_command("c /a/b/").unwrap() {
+            Command::Change { .. } => {}
+            other => panic!("Expected Change, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_set_prefix_synonym_and_pending() {
+        match parse_command("SET PREFIX SYN K D").unwrap() {
+            Command::Set(SetCommand::PrefixSynonym(new, old)) => {
+                assert_eq!((new.as_str(), old.as_str()), ("k", "d"));
+            }
+            other => panic!("Expected Set(PrefixSynonym), got {:?}", other),
+        }
+        match parse_command("SET PREFIX ON").unwrap() {
+            Command::Set(SetCommand::Prefix(true)) => {}
+            other => panic!("Expected Set(Prefix(true)), got {:?}", other),
+        }
+        match parse_command("SET PENDING BLOCK zz").unwrap() {
+            Command::Set(SetCommand::Pending(Some(name))) => assert_eq!(name, "ZZ"),
+            other => panic!("Expected Set(Pending(ZZ)), got {:?}", other),
+        }
+        match parse_command("SET PEND OFF").unwrap() {
+            Command::Set(SetCommand::Pending(None)) => {}
+            other => panic!("Expected Set(Pending(None)), got {:?}", other),
+        }
+        assert!(parse_command("SET PREFIX SYNONYM K").is_err());
+        assert!(parse_command("SET PENDING BLOCK").is_err());
+    }
+
+    #[test]
+    fn parse_set_select_display_scope() {
+        match parse_command("SET SEL 2 :5").unwrap() {
+            Command::Set(SetCommand::Select {
+                level: 2,
+                relative: false,
+                target: Some(Target::Absolute(5)),
+            }) => {}
+            other => panic!("Expected Set(Select 2 :5), got {:?}", other),
+        }
+        match parse_command("SET SELECT -1").unwrap() {
+            Command::Set(SetCommand::Select {
+                level: -1,
+                relative: true,
+                target: None,
+            }) => {}
+            other => panic!("Expected Set(Select -1), got {:?}", other),
+        }
+        match parse_command("SET DISP 1 *").unwrap() {
+            Command::Set(SetCommand::Display(1, usize::MAX)) => {}
+            other => panic!("Expected Set(Display(1, *)), got {:?}", other),
+        }
+        match parse_command("SET DISPLAY 2").unwrap() {
+            Command::Set(SetCommand::Display(2, 2)) => {}
+            other => panic!("Expected Set(Display(2, 2)), got {:?}", other),
+        }
+        match parse_command("SET SCOPE a").unwrap() {
+            Command::Set(SetCommand::Scope(ScopeSetting::All)) => {}
+            other => panic!("Expected Set(Scope(All)), got {:?}", other),
+        }
+        assert!(parse_command("SET DISPLAY 3 1").is_err());
+        assert!(parse_command("SET SELECT x").is_err());
+        assert!(parse_command("SET SCOPE NONE").is_err());
+    }
 }
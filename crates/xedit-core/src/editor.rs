@@ -1,13 +1,21 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::collections::VecDeque;
-use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use crate::buffer::Buffer;
+use crate::buffer::{Buffer, RecordFormat};
+use crate::codepage::Codepage;
 use crate::command::*;
 use crate::error::{Result, XeditError};
+use crate::filesystem::{FileId, FileSystem, NativeFs};
+use crate::host::Host;
+use crate::line_index::LineIndex;
 use crate::prefix::*;
-use crate::target::Target;
+use crate::target::{ColumnTarget, MatchOptions, Target};
+use crate::undo::{fingerprint, UndoLog, UndoState};
 
 /// Cursor placement request from CURSOR command
 #[derive(Debug, Clone)]
@@ -16,13 +24,15 @@ pub enum CursorRequest {
     File { line: usize, col: usize },
 }
 
-/// Snapshot of editor state for undo
-#[derive(Debug, Clone)]
-struct UndoSnapshot {
-    lines: Vec<String>,
-    current_line: usize,
-    current_col: usize,
-    alt_count: usize,
+/// Record format and encoding for loading a file
+/// (`xedit --recfm F --lrecl 80 --codepage 037`)
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub recfm: Option<RecordFormat>,
+    pub lrecl: Option<usize>,
+    pub codepage: Codepage,
+    /// BROWSE: read lines on demand and refuse changes (`xedit --browse`)
+    pub browse: bool,
 }
 
 /// XEDIT editor state for a single file.
@@ -44,6 +54,10 @@ pub struct Editor {
     filemode: String,
     filepath: Option<PathBuf>,
     readonly: bool,
+    /// Loaded as back-to-back fixed-length records with no newlines
+    binary_records: bool,
+    /// Encoding used to read and write the file
+    codepage: Codepage,
 
     // Settings
     trunc: usize,
@@ -53,9 +67,16 @@ pub struct Editor {
     show_prefix: bool,
     show_scale: bool,
     case_respect: bool,
+    /// SET CASE UPPER: typed data is uppercased
+    case_upper: bool,
+    spill: SpillSetting,
+    /// Set when an edit loses text past the truncation column
+    truncated: bool,
     hex: bool,
     stay: bool,
     wrap: bool,
+    arbchar_on: bool,
+    arbchar: char,
     curline: CurLinePosition,
     verify_start: usize,
     verify_end: usize,
@@ -66,24 +87,35 @@ pub struct Editor {
     // Macro settings
     /// Search path for REXX macros (directories to check)
     macro_path: Vec<PathBuf>,
+    /// Where files, GET input and macros are read from and saved to
+    fs: Rc<dyn FileSystem>,
+    /// Where macros' ADDRESS CMS commands go, when embedded in a CMS
+    host: Option<Rc<RefCell<dyn Host>>>,
+    /// SET PREFIX SYNONYM: prefix name -> the command or macro it stands for
+    prefix_synonyms: HashMap<String, String>,
 
     // Operational state
     alt_count: usize,
     message: Option<String>,
     pending_block: Option<PendingBlock>,
+    /// Line a prefix macro was entered on, while the macro runs
+    prefix_line: Option<usize>,
     pending_operation: Option<PendingOperation>,
     /// Number of lines per page (set by TUI based on screen size)
     page_size: usize,
 
-    // ALL filter: if Some, each entry says whether the line is visible
-    all_filter: Option<Vec<bool>>,
+    // Selection: lines whose SET SELECT level is in `display` are shown
+    display: (usize, usize),
+    scope: ScopeSetting,
     show_shadow: bool,
 
     // Command history
     command_history: Vec<String>,
 
-    // Undo stack (multi-level)
-    undo_stack: Vec<UndoSnapshot>,
+    // Undo/redo log (multi-level, delta-based)
+    undo: UndoLog,
+    /// SET UNDOFILE: keep undo history in a sidecar file across sessions
+    undo_file: bool,
 
     // Cursor request
     cursor_request: Option<CursorRequest>,
@@ -125,6 +157,8 @@ impl Editor {
             filemode: "A1".to_string(),
             filepath: None,
             readonly: false,
+            binary_records: false,
+            codepage: Codepage::Utf8,
             trunc: 72,
             zone_left: 1,
             zone_right: 72,
@@ -132,23 +166,34 @@ impl Editor {
             show_prefix: true,
             show_scale: false,
             case_respect: false,
+            case_upper: false,
+            spill: SpillSetting::Off,
+            truncated: false,
             hex: false,
             stay: true,
             wrap: false,
+            arbchar_on: false,
+            arbchar: '$',
             curline: CurLinePosition::Middle,
             verify_start: 1,
             verify_end: 80,
             pf_keys: default_pf_keys(),
             macro_path: vec![PathBuf::from(".")],
+            fs: Rc::new(NativeFs),
+            host: None,
+            prefix_synonyms: HashMap::new(),
             alt_count: 0,
             message: None,
             pending_block: None,
+            prefix_line: None,
             pending_operation: None,
             page_size: 20,
-            all_filter: None,
+            display: (0, 0),
+            scope: ScopeSetting::Display,
             show_shadow: true,
             command_history: Vec::new(),
-            undo_stack: Vec::new(),
+            undo: UndoLog::default(),
+            undo_file: false,
             cursor_request: None,
             reserved_lines: HashMap::new(),
             color_overrides: HashMap::new(),
@@ -182,10 +227,19 @@ impl Editor {
         &self.filemode
     }
 
+    pub fn filepath(&self) -> Option<&Path> {
+        self.filepath.as_deref()
+    }
+
     pub fn trunc(&self) -> usize {
         self.trunc
     }
 
+    /// SET ZONE columns (right is `usize::MAX` for `*`)
+    pub fn zone(&self) -> (usize, usize) {
+        (self.zone_left, self.zone_right)
+    }
+
     pub fn alt_count(&self) -> usize {
         self.alt_count
     }
@@ -222,6 +276,28 @@ impl Editor {
         self.case_respect
     }
 
+    pub fn case_upper(&self) -> bool {
+        self.case_upper
+    }
+
+    /// Typed text as it goes into the file: uppercased under SET CASE UPPER
+    fn apply_case<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.case_upper {
+            Cow::Owned(text.chars().map(upper_char).collect())
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// A typed character as it goes into the file (see `apply_case`)
+    fn apply_case_char(&self, ch: char) -> char {
+        if self.case_upper {
+            upper_char(ch)
+        } else {
+            ch
+        }
+    }
+
     pub fn hex(&self) -> bool {
         self.hex
     }
@@ -234,6 +310,15 @@ impl Editor {
         self.wrap
     }
 
+    pub fn codepage(&self) -> Codepage {
+        self.codepage
+    }
+
+    /// The arbitrary character, if SET ARBCHAR is ON
+    pub fn arbchar(&self) -> Option<char> {
+        self.arbchar_on.then_some(self.arbchar)
+    }
+
     pub fn verify_start(&self) -> usize {
         self.verify_start
     }
@@ -266,6 +351,11 @@ impl Editor {
         self.buffer.is_modified()
     }
 
+    /// Whether commands that change the file are refused (BROWSE)
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
     pub fn has_pending_block(&self) -> bool {
         self.pending_block.is_some()
     }
@@ -274,6 +364,14 @@ impl Editor {
         self.pending_block.as_ref()
     }
 
+    /// Parse prefix area text, applying SET PREFIX SYNONYM. A name that is
+    /// neither built in nor a macro on the macro path is not a command.
+    pub fn parse_prefix(&self, input: &str) -> Option<PrefixCommand> {
+        PrefixCommand::parse_with_synonyms(input, &self.prefix_synonyms, |name| {
+            self.macro_exists(name)
+        })
+    }
+
     pub fn set_page_size(&mut self, size: usize) {
         self.page_size = size.max(1);
     }
@@ -303,76 +401,105 @@ impl Editor {
         Ok(CommandResult::ok())
     }
 
-    // -- ALL filter --
+    // -- Selection levels (SET SELECT / DISPLAY / SCOPE, ALL) --
 
-    pub fn all_filter_active(&self) -> bool {
-        self.all_filter.is_some()
+    /// Whether some lines may be hidden by their selection level
+    pub fn selection_active(&self) -> bool {
+        self.buffer.has_select_levels() || self.display.0 > 0
     }
 
+    /// Whether a line's selection level is in the SET DISPLAY range;
+    /// TOF and EOF are always displayed
     pub fn is_line_visible(&self, line_num: usize) -> bool {
-        match &self.all_filter {
-            Some(filter) => {
-                if line_num == 0 || line_num > filter.len() {
-                    true
-                } else {
-                    filter[line_num - 1]
-                }
-            }
-            None => true,
+        if line_num == 0 || line_num > self.buffer.len() {
+            return true;
         }
+        let (low, high) = self.display;
+        (low..=high).contains(&self.buffer.select_level(line_num))
+    }
+
+    /// Whether commands act on a line: with SET SCOPE DISPLAY only the
+    /// displayed lines are in scope
+    fn in_scope(&self, line_num: usize) -> bool {
+        self.scope == ScopeSetting::All || self.is_line_visible(line_num)
+    }
+
+    /// Whether SET SCOPE DISPLAY currently leaves some lines out of scope
+    fn scope_limited(&self) -> bool {
+        self.scope == ScopeSetting::Display && self.selection_active()
     }
 
     /// Count consecutive hidden lines starting after line_num
     pub fn shadow_count_after(&self, line_num: usize) -> usize {
-        match &self.all_filter {
-            Some(filter) => {
-                let mut count = 0;
-                let mut i = line_num; // line_num is 1-based, next is line_num+1
-                while i < filter.len() {
-                    if !filter[i] {
-                        count += 1;
-                        i += 1;
-                    } else {
-                        break;
-                    }
-                }
-                count
-            }
-            None => 0,
+        if !self.selection_active() {
+            return 0;
         }
+        ((line_num + 1)..=self.buffer.len())
+            .take_while(|&n| !self.is_line_visible(n))
+            .count()
     }
 
     pub fn show_shadow(&self) -> bool {
         self.show_shadow
     }
 
+    /// ALL target: select the matching lines (level 1, the rest 0) and
+    /// display only them. ALL alone puts every line back at level 0.
     fn cmd_all(&mut self, target: Option<&Target>) -> Result<CommandResult> {
         match target {
             Some(t) => {
-                let case_respect = self.case_respect;
-                let mut filter = Vec::with_capacity(self.buffer.len());
+                let opts = self.match_options();
                 let mut visible_count = 0;
                 for i in 1..=self.buffer.len() {
-                    let text = self.buffer.line_text(i).unwrap_or("");
-                    let visible = t.matches_line(case_respect, text);
+                    let visible = self
+                        .buffer
+                        .with_text(i, |text| t.matches_line(&opts, text))
+                        .unwrap_or(false);
                     if visible {
                         visible_count += 1;
                     }
-                    filter.push(visible);
+                    self.buffer.set_select_level(i, usize::from(visible));
                 }
-                self.all_filter = Some(filter);
+                self.display = (1, 1);
                 Ok(CommandResult::with_message(format!(
                     "{} line(s) displayed",
                     visible_count
                 )))
             }
             None => {
-                self.all_filter = None;
+                self.buffer.clear_select_levels();
+                self.display = (0, 0);
                 Ok(CommandResult::with_message("ALL reset"))
             }
         }
     }
 
+    /// SET SELECT [+|-]n [target]: set (or adjust) the selection level of
+    /// the lines in scope from the current line up to the target
+    fn set_select(&mut self, level: i64, relative: bool, target: Option<&Target>) -> Result<()> {
+        if self.current_line == 0 && target.is_none() {
+            return Err(XeditError::InvalidCommand(
+                "SET SELECT: no line at Top of File".to_string(),
+            ));
+        }
+        let (start, end) = match target {
+            Some(t) => self.target_range(t)?,
+            None => (self.current_line, self.current_line),
+        };
+        for n in start..=end {
+            if !self.in_scope(n) {
+                continue;
+            }
+            let new_level = if relative {
+                (self.buffer.select_level(n) as i64 + level).max(0) as usize
+            } else {
+                level as usize
+            };
+            self.buffer.set_select_level(n, new_level);
+        }
+        Ok(())
+    }
+
     fn cmd_sort(
         &mut self,
         target: Option<&Target>,
@@ -388,12 +515,7 @@ impl Editor {
             self.current_line
         };
         let end = if let Some(t) = target {
-            let case_respect = self.case_respect;
-            let buffer = &self.buffer;
-            t.resolve(self.current_line, buffer.len(), case_respect, &|n| {
-                buffer.line_text(n).map(String::from)
-            })
-            .unwrap_or(self.buffer.len())
+            self.resolve_target(t).unwrap_or(self.buffer.len())
         } else {
             self.buffer.len()
         };
@@ -402,13 +524,19 @@ impl Editor {
             return Err(XeditError::InvalidCommand("Nothing to sort".to_string()));
         }
 
-        // Extract lines in range
-        let mut lines_to_sort: Vec<String> = (start..=end)
-            .filter_map(|i| self.buffer.line_text(i).map(String::from))
+        // Extract lines in range, remembering where each came from
+        let mut lines_to_sort: Vec<(String, usize)> = (start..=end)
+            .filter_map(|i| self.buffer.line_text(i).map(|t| (t.to_string(), i)))
             .collect();
 
+        // Without explicit columns, the sort key is the zone
+        let (col_start, col_end) = match col_start {
+            Some(_) => (col_start, col_end),
+            None => (Some(self.zone_left), Some(self.zone_right)),
+        };
+
         // Sort by key
-        lines_to_sort.sort_by(|a, b| {
+        lines_to_sort.sort_by(|(a, _), (b, _)| {
             let key_a = sort_key(a, col_start, col_end);
             let key_b = sort_key(b, col_start, col_end);
             if ascending {
@@ -418,13 +546,17 @@ impl Editor {
             }
         });
 
-        // Replace in buffer
-        for (i, text) in lines_to_sort.into_iter().enumerate() {
-            let line_num = start + i;
-            if let Some(line) = self.buffer.get_mut(line_num) {
-                line.set_text(text);
-            }
+        // Replace in buffer; points move with their lines
+        let mut moved_to = vec![0; lines_to_sort.len()];
+        for (i, (text, from)) in lines_to_sort.into_iter().enumerate() {
+            self.buffer.set_line_text(start + i, text);
+            moved_to[from - start] = start + i;
         }
+        self.buffer.renumber_points(|line| {
+            line.checked_sub(start)
+                .and_then(|i| moved_to.get(i).copied())
+                .unwrap_or(line)
+        });
 
         self.alt_count += 1;
         let count = end - start + 1;
@@ -445,6 +577,24 @@ impl Editor {
         &self.macro_path
     }
 
+    /// Read and write files through `fs` instead of the host file system
+    pub fn set_filesystem(&mut self, fs: Rc<dyn FileSystem>) {
+        self.fs = fs;
+    }
+
+    pub fn filesystem(&self) -> &Rc<dyn FileSystem> {
+        &self.fs
+    }
+
+    /// Send macros' ADDRESS CMS commands to `host`
+    pub fn set_host(&mut self, host: Rc<RefCell<dyn Host>>) {
+        self.host = Some(host);
+    }
+
+    pub fn host(&self) -> Option<&Rc<RefCell<dyn Host>>> {
+        self.host.as_ref()
+    }
+
     // -- Display customization --
 
     pub fn reserved_line(&self, row: usize) -> Option<&str> {
@@ -517,38 +667,99 @@ impl Editor {
 
     // -- Undo --
 
-    /// Capture buffer state before a modifying command
+    /// Start an undo group before a modifying command; buffer changes
+    /// made until the next call are undone together
     pub fn snapshot_for_undo(&mut self) {
-        self.undo_stack.push(UndoSnapshot {
-            lines: self
-                .buffer
-                .lines()
-                .iter()
-                .map(|l| l.text().to_string())
-                .collect(),
-            current_line: self.current_line,
-            current_col: self.current_col,
+        let state = self.undo_state();
+        self.undo.begin(&mut self.buffer, state);
+    }
+
+    /// Close the command's undo group; later changes made without a
+    /// snapshot get a group of their own
+    fn end_undo_group(&mut self) {
+        let state = self.undo_state();
+        self.undo.end(&mut self.buffer, state);
+    }
+
+    fn undo_state(&self) -> UndoState {
+        UndoState {
+            line: self.current_line,
+            col: self.current_col,
             alt_count: self.alt_count,
-        });
+        }
+    }
+
+    fn restore_undo_state(&mut self, state: UndoState) {
+        self.current_line = state.line.min(self.buffer.len());
+        self.current_col = state.col;
+        self.alt_count = state.alt_count;
     }
 
     fn cmd_undo(&mut self) -> Result<CommandResult> {
-        if let Some(snap) = self.undo_stack.pop() {
-            self.buffer = Buffer::from_lines(snap.lines);
-            self.current_line = snap.current_line;
-            self.current_col = snap.current_col;
-            self.alt_count = snap.alt_count;
-            // Clear ALL filter — it may reference the old buffer layout
-            self.all_filter = None;
-            let remaining = self.undo_stack.len();
-            let msg = if remaining > 0 {
-                format!("Undone ({} more)", remaining)
-            } else {
-                "Undone".to_string()
-            };
-            Ok(CommandResult::with_message(msg))
+        let state = self.undo_state();
+        let restore = self
+            .undo
+            .undo(&mut self.buffer, state)
+            .ok_or_else(|| XeditError::InvalidCommand("Nothing to undo".to_string()))?;
+        self.restore_undo_state(restore);
+        let remaining = self.undo.undo_len();
+        let msg = if remaining > 0 {
+            format!("Undone ({} more)", remaining)
+        } else {
+            "Undone".to_string()
+        };
+        Ok(CommandResult::with_message(msg))
+    }
+
+    fn cmd_redo(&mut self) -> Result<CommandResult> {
+        let state = self.undo_state();
+        let restore = self
+            .undo
+            .redo(&mut self.buffer, state)
+            .ok_or_else(|| XeditError::InvalidCommand("Nothing to redo".to_string()))?;
+        self.restore_undo_state(restore);
+        let remaining = self.undo.redo_len();
+        let msg = if remaining > 0 {
+            format!("Redone ({} more)", remaining)
         } else {
-            Err(XeditError::InvalidCommand("Nothing to undo".to_string()))
+            "Redone".to_string()
+        };
+        Ok(CommandResult::with_message(msg))
+    }
+
+    /// Sidecar file holding the undo history: `.name.ext.undo` beside the
+    /// file. There is none for a file that cannot be written.
+    fn undo_sidecar(&self) -> Option<PathBuf> {
+        let path = self.filepath.as_ref()?;
+        if !self.fs.is_writable(path) {
+            return None;
+        }
+        let name = path.file_name()?.to_string_lossy();
+        Some(path.with_file_name(format!(".{}.undo", name)))
+    }
+
+    /// Pick up undo history saved by an earlier session, if it still
+    /// matches the file on disk and nothing has been edited yet
+    fn restore_undo_file(&mut self) {
+        if self.undo.undo_len() > 0 || self.buffer.is_modified() {
+            return;
+        }
+        let (Some(sidecar), Some(path)) = (self.undo_sidecar(), self.filepath.as_ref()) else {
+            return;
+        };
+        let content = match self.fs.read(&sidecar) {
+            Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                self.message = Some(format!("Undo history not restored: {}", e));
+                return;
+            }
+        };
+        let Ok(bytes) = self.fs.read(path) else {
+            return;
+        };
+        if let Err(e) = self.undo.load(&content, fingerprint(&bytes)) {
+            self.message = Some(format!("Undo history not restored: {}", e));
         }
     }
 
@@ -564,12 +775,29 @@ impl Editor {
         for dir in &self.macro_path {
             for candidate in &candidates {
                 let path = dir.join(candidate);
-                if let Ok(source) = fs::read_to_string(&path) {
+                if let Some(source) = self.read_macro(&path) {
                     return Some((path, source));
                 }
             }
         }
-        None
+        let path = self.fs.find_macro(name)?;
+        let source = self.read_macro(&path)?;
+        Some((path, source))
+    }
+
+    #[cfg(feature = "rexx")]
+    fn macro_exists(&self, name: &str) -> bool {
+        self.find_macro(name).is_some()
+    }
+
+    #[cfg(not(feature = "rexx"))]
+    fn macro_exists(&self, _name: &str) -> bool {
+        false
+    }
+
+    #[cfg(feature = "rexx")]
+    fn read_macro(&self, path: &Path) -> Option<String> {
+        String::from_utf8(self.fs.read(path).ok()?).ok()
     }
 
     /// Run the PROFILE XEDIT macro if it exists.
@@ -612,10 +840,44 @@ impl Editor {
     // -- File operations --
 
     pub fn load_file(&mut self, path: &Path) -> Result<()> {
-        let content = fs::read_to_string(path)
-            .map_err(|_| XeditError::FileNotFound(path.display().to_string()))?;
-        let lines: Vec<String> = content.lines().map(String::from).collect();
-        self.buffer = Buffer::from_lines(lines);
+        self.load_file_with(path, &LoadOptions::default())
+    }
+
+    /// Load a file located by the file system (`FileSystem::locate`),
+    /// keeping its fn ft fm rather than deriving them from the path. A file
+    /// that does not exist yet is edited as a new, empty file.
+    pub fn open_file(&mut self, id: &FileId, opts: &LoadOptions) -> Result<()> {
+        self.load(&id.path, opts, true)?;
+        self.filename = id.filename.clone();
+        self.filetype = id.filetype.clone();
+        self.filemode = id.filemode.clone();
+        Ok(())
+    }
+
+    /// Load a file with an explicit record format, length and code page.
+    ///
+    /// A RECFM F file with an LRECL is split into binary records of LRECL
+    /// bytes (one character per byte, trailing blanks dropped) unless it
+    /// is ASCII or UTF-8 text whose every line is newline-terminated and
+    /// exactly LRECL bytes long, as `save_file` writes it; an EBCDIC file
+    /// is always records. A short last record is reported in the message. EBCDIC
+    /// lines end in NL (X'15') or LF (X'25'). With `opts.browse` the file
+    /// is indexed rather than read in, and is read-only.
+    pub fn load_file_with(&mut self, path: &Path, opts: &LoadOptions) -> Result<()> {
+        self.load(path, opts, false)
+    }
+
+    /// `load_file_with`, starting a new file if `new_file` allows and the
+    /// file does not exist
+    fn load(&mut self, path: &Path, opts: &LoadOptions, new_file: bool) -> Result<()> {
+        let max_width = if opts.browse {
+            self.load_index(path, opts)?
+        } else {
+            self.load_contents(path, opts, new_file)?;
+            self.buffer.iter().map(|l| l.len()).max().unwrap_or(80)
+        };
+        self.codepage = opts.codepage;
+        self.readonly = opts.browse;
 
         if let Some(stem) = path.file_stem() {
             self.filename = stem.to_string_lossy().to_uppercase();
@@ -625,46 +887,167 @@ impl Editor {
         }
         self.filepath = Some(path.to_path_buf());
 
-        let max_width = self
-            .buffer
-            .lines()
-            .iter()
-            .map(|l| l.len())
-            .max()
-            .unwrap_or(80);
         if max_width > self.trunc {
             self.trunc = max_width;
-            self.zone_right = max_width;
-            self.verify_end = max_width;
         }
+        if self.buffer.record_limit().is_some() {
+            self.trunc = self.trunc.min(self.buffer.lrecl());
+        }
+        self.zone_right = self.trunc;
+        self.verify_end = self.verify_end.max(self.trunc);
 
         self.current_line = if self.buffer.is_empty() { 0 } else { 1 };
         self.alt_count = 0;
+        self.undo = UndoLog::new(self.undo.limit());
+        if self.undo_file && !self.readonly {
+            self.restore_undo_file();
+        }
+        Ok(())
+    }
+
+    /// Read the whole file into the buffer; with `new_file`, a file that
+    /// does not exist is read as empty
+    fn load_contents(&mut self, path: &Path, opts: &LoadOptions, new_file: bool) -> Result<()> {
+        let bytes = match self.fs.read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if new_file && e.kind() == io::ErrorKind::NotFound => {
+                self.message = Some("New file".to_string());
+                Vec::new()
+            }
+            Err(_) => return Err(XeditError::FileNotFound(path.display().to_string())),
+        };
+        let cp = opts.codepage;
+        let fixed_lrecl = opts
+            .lrecl
+            .filter(|&n| n > 0 && opts.recfm == Some(RecordFormat::Fixed));
+        match fixed_lrecl {
+            Some(lrecl) if cp.is_ebcdic() || !has_uniform_lines(&bytes, lrecl) => {
+                self.report_short_record(bytes.len() as u64, lrecl);
+                let records = bytes
+                    .chunks(lrecl)
+                    .map(|rec| cp.decode(rec).trim_end_matches(' ').to_string())
+                    .collect();
+                self.buffer = Buffer::from_records(records, lrecl);
+                self.binary_records = true;
+                self.trunc = lrecl;
+            }
+            _ => {
+                let content = if cp.is_ebcdic() {
+                    cp.decode(&bytes).replace('\u{85}', "\n")
+                } else {
+                    String::from_utf8(bytes).map_err(|e| {
+                        XeditError::Io(io::Error::new(io::ErrorKind::InvalidData, e))
+                    })?
+                };
+                let lines: Vec<String> = content.lines().map(String::from).collect();
+                self.buffer = Buffer::from_lines(lines);
+                if let Some(recfm) = &opts.recfm {
+                    self.buffer.set_recfm(recfm.clone());
+                }
+                if let Some(lrecl) = opts.lrecl.filter(|&n| n > 0) {
+                    self.buffer.set_lrecl(lrecl);
+                }
+                self.binary_records = false;
+            }
+        }
         Ok(())
     }
 
+    /// Note a file of binary records that ends in a partial record, which
+    /// is read (and saved) padded with blanks
+    fn report_short_record(&mut self, file_len: u64, lrecl: usize) {
+        let short = (file_len % lrecl as u64) as usize;
+        if short > 0 {
+            self.message = Some(format!(
+                "Last record is {} bytes, short of LRECL {}; it is padded with blanks",
+                short, lrecl
+            ));
+        }
+    }
+
+    /// Open a file for BROWSE through a line index, in the same record
+    /// layouts as `load_contents`. Returns the longest line's width.
+    fn load_index(&mut self, path: &Path, opts: &LoadOptions) -> Result<usize> {
+        let not_found = |_| XeditError::FileNotFound(path.display().to_string());
+        let cp = opts.codepage;
+        let fixed_lrecl = opts
+            .lrecl
+            .filter(|&n| n > 0 && opts.recfm == Some(RecordFormat::Fixed));
+        let index = match fixed_lrecl {
+            Some(lrecl) if cp.is_ebcdic() => LineIndex::open_records(path, cp, lrecl),
+            _ => LineIndex::open_lines(path, cp),
+        }
+        .map_err(not_found)?;
+        let index = match fixed_lrecl {
+            Some(lrecl) if !cp.is_ebcdic() && !index.has_uniform_lines(lrecl) => {
+                LineIndex::open_records(path, cp, lrecl).map_err(not_found)?
+            }
+            _ => index,
+        };
+
+        let max_width = index.max_width();
+        match fixed_lrecl {
+            Some(lrecl) if !index.is_terminated() => {
+                self.report_short_record(index.file_len(), lrecl);
+                self.buffer = Buffer::from_index(index, RecordFormat::Fixed, lrecl);
+                self.binary_records = true;
+                self.trunc = lrecl;
+            }
+            _ => {
+                let recfm = opts.recfm.clone().unwrap_or(RecordFormat::Variable);
+                let lrecl = opts.lrecl.filter(|&n| n > 0).unwrap_or(max_width.max(80));
+                self.buffer = Buffer::from_index(index, recfm, lrecl);
+                self.binary_records = false;
+            }
+        }
+        Ok(max_width)
+    }
+
     pub fn save_file(&mut self) -> Result<()> {
         let path = self.filepath.as_ref().ok_or(XeditError::NoFile)?;
-        if self.readonly {
-            return Err(XeditError::ReadOnly);
-        }
-        let content: String = self
-            .buffer
-            .lines()
-            .iter()
-            .map(|l| l.text())
-            .collect::<Vec<_>>()
-            .join("\n");
+        self.check_writable("SAVE")?;
+        let fixed = self.buffer.record_limit().is_some();
+        let records: Vec<String> = (1..=self.buffer.len())
+            .map(|n| {
+                if fixed {
+                    self.buffer.fixed_record(n).unwrap_or_default()
+                } else {
+                    self.buffer.line_text(n).unwrap_or_default().to_string()
+                }
+            })
+            .collect();
 
-        let content = if content.is_empty() {
-            String::new()
+        let cp = self.codepage;
+        let bytes = if fixed && (self.binary_records || cp.is_ebcdic()) {
+            encode_records(cp, &records, None)?
+        } else if cp.is_ebcdic() {
+            // EBCDIC NL ends each variable-length line
+            encode_records(cp, &records, Some(0x15))?
         } else {
-            content + "\n"
+            let content = records.join("\n");
+            if content.is_empty() {
+                Vec::new()
+            } else {
+                (content + "\n").into_bytes()
+            }
         };
-
-        fs::write(path, &content)?;
+        self.fs.write(path, &bytes)?;
         self.buffer.clear_modified();
         self.alt_count = 0;
+
+        // The file is saved even if its undo history cannot be
+        if self.undo_file {
+            if let Some(sidecar) = self.undo_sidecar() {
+                let state = self.undo_state();
+                let content = self.undo.save(&mut self.buffer, state, fingerprint(&bytes));
+                self.fs.write(&sidecar, content.as_bytes()).map_err(|e| {
+                    XeditError::Io(io::Error::new(
+                        e.kind(),
+                        format!("File saved, but undo history not written: {}", e),
+                    ))
+                })?;
+            }
+        }
         Ok(())
     }
 
@@ -672,6 +1055,13 @@ impl Editor {
 
     pub fn execute(&mut self, cmd: &Command) -> Result<CommandResult> {
         self.message = None;
+        self.truncated = false;
+        if let Some(name) = cmd.modifying_name() {
+            if let Err(e) = self.check_writable(name) {
+                self.message = Some(e.to_string());
+                return Err(e);
+            }
+        }
         let result = match cmd {
             Command::Up(n) => self.cmd_up(*n),
             Command::Down(n) => self.cmd_down(*n),
@@ -687,15 +1077,30 @@ impl Editor {
                 to,
                 target,
                 count,
-            } => self.cmd_change(from, to, target.as_ref(), *count),
+                occurrences,
+                first,
+            } => self.cmd_change(from, to, target.as_ref(), *count, *occurrences, *first),
             Command::Input(text) => self.cmd_input(text.as_deref()),
             Command::Delete(target) => self.cmd_delete(target.as_ref()),
+            Command::Replace(text) => self.cmd_replace(text.as_deref()),
+            Command::Add(n) => self.cmd_add(*n),
+            Command::Overlay(text) => self.cmd_overlay(text),
+            Command::Copy(range, dest) => self.cmd_copy_move(range, dest, OperationType::Copy),
+            Command::Move(range, dest) => self.cmd_copy_move(range, dest, OperationType::Move),
+            Command::CLocate(target) => self.cmd_clocate(target),
+            Command::CDelete(target) => self.cmd_cdelete(target),
+            Command::CInsert(text) => self.cmd_cinsert(text),
+            Command::CAppend(text) => self.cmd_cappend(text),
+            Command::CReplace(text) => self.cmd_creplace(text),
+            Command::COverlay(text) => self.cmd_coverlay(text),
             Command::File => self.cmd_file(),
             Command::Save => self.cmd_save(),
             Command::Quit => self.cmd_quit(),
             Command::QQuit => Ok(CommandResult::quit()),
             Command::Get(filename) => self.cmd_get(filename),
+            Command::Xedit(spec) => Ok(CommandResult::edit(spec.clone())),
             Command::Undo => self.cmd_undo(),
+            Command::Redo => self.cmd_redo(),
             Command::Stack(n) => self.cmd_stack(*n),
             Command::Queue(n) => self.cmd_queue(*n),
             Command::Cursor(target) => self.cmd_cursor(target),
@@ -708,12 +1113,22 @@ impl Editor {
             } => self.cmd_sort(target.as_ref(), *ascending, *col_start, *col_end),
             Command::Set(subcmd) => self.cmd_set(subcmd),
             Command::Query(what) => self.cmd_query(what),
+            // Outside a macro there are no variables to set, so EXTRACT only
+            // checks its items; the macro engine stores the values in stems
+            Command::Extract(items) => self.extract(items).map(|_| CommandResult::ok()),
             Command::Refresh => Ok(CommandResult::refresh()),
             Command::Help => self.cmd_help(),
             #[cfg(feature = "rexx")]
             Command::Macro(args) => self.cmd_macro(args),
             Command::Nop => Ok(CommandResult::ok()),
         };
+        let result = self.mark_truncated(result);
+        // Lines of a browsed file that could not be read were skipped,
+        // which is what went wrong even if a search then failed
+        let result = match self.buffer.take_read_error() {
+            Some(e) => Err(XeditError::Io(e)),
+            None => result,
+        };
 
         // Capture message from result
         if let Ok(ref r) = result {
@@ -724,51 +1139,233 @@ impl Editor {
         if let Err(ref e) = result {
             self.message = Some(e.to_string());
         }
+        self.end_undo_group();
+        self.buffer.trim_cache(self.current_line);
 
         result
     }
 
+    /// Refuse `what` when the file is read-only
+    fn check_writable(&self, what: &str) -> Result<()> {
+        if self.readonly {
+            Err(XeditError::ReadOnly(what.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Insert a line in input mode (called by TUI for each line entered)
-    pub fn input_line(&mut self, text: &str) {
-        self.buffer.insert_after(self.current_line, text);
-        self.current_line += 1;
+    pub fn input_line(&mut self, text: &str) -> Result<()> {
+        self.check_writable("INPUT")?;
+        self.check_record_length(text.chars().count())?;
+        let text = self.apply_case(text).into_owned();
+        self.truncated = false;
+        self.insert_within_trunc(&text);
         self.alt_count += 1;
+        self.report_truncation();
+        Ok(())
+    }
+
+    /// TRUNC and ZONE can never reach past a fixed record; variable
+    /// records have no length limit, so SET LRECL leaves them alone
+    fn fit_trunc_to_record(&mut self) {
+        if let Some(lrecl) = self.buffer.record_limit() {
+            self.trunc = self.trunc.min(lrecl);
+            self.zone_right = self.zone_right.min(lrecl);
+        }
+    }
+
+    /// Reject a line of `len` characters that would not fit a fixed record
+    fn check_record_length(&self, len: usize) -> Result<()> {
+        match self.buffer.record_limit() {
+            Some(lrecl) if len > lrecl => Err(XeditError::InvalidCommand(format!(
+                "Record length exceeds LRECL {}",
+                lrecl
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    // -- Truncation column (SET TRUNC / SET SPILL) --
+
+    /// Split a line at the truncation column: edits only touch the first
+    /// part, and the columns past TRUNC stay where they are
+    fn split_trunc<'a>(&self, text: &'a str) -> (&'a str, &'a str) {
+        let at = text
+            .char_indices()
+            .nth(self.trunc)
+            .map_or(text.len(), |(i, _)| i);
+        text.split_at(at)
+    }
+
+    /// Store `head` as the columns up to TRUNC of line `line_num`, followed
+    /// by `tail`. Text pushed past TRUNC is dropped and flagged as truncated,
+    /// or with SET SPILL inserted as new lines after it. Returns the number
+    /// of spilled lines.
+    fn store_within_trunc(&mut self, line_num: usize, head: &str, tail: &str) -> usize {
+        let trunc = self.trunc.max(1);
+        let mut chars: Vec<char> = head.chars().collect();
+        let mut spilled = None;
+        if chars.len() > trunc {
+            let rest_blank = chars[trunc..].iter().all(|&c| c == ' ');
+            if self.spill == SpillSetting::Off || rest_blank {
+                self.truncated |= !rest_blank;
+                chars.truncate(trunc);
+            } else {
+                // SPILL WORD breaks at the last blank that keeps a word whole
+                let word_break = match self.spill {
+                    SpillSetting::Word => chars[1..=trunc].iter().rposition(|&c| c == ' '),
+                    _ => None,
+                };
+                spilled = Some(match word_break {
+                    Some(i) => chars.split_off(i + 1).split_off(1),
+                    None => chars.split_off(trunc),
+                });
+            }
+        }
+        let mut text: String = chars.into_iter().collect();
+        if !tail.is_empty() {
+            let pad = trunc.saturating_sub(text.chars().count());
+            text.extend(std::iter::repeat_n(' ', pad));
+            text.push_str(tail);
+        }
+        self.buffer.set_line_text(line_num, text);
+
+        // A spilled line may itself be too long and spill again
+        let mut count = 0;
+        if let Some(rest) = spilled {
+            let rest: String = rest.into_iter().collect();
+            self.buffer.insert_after(line_num, "");
+            count = 1 + self.store_within_trunc(line_num + 1, &rest, "");
+        }
+        count
+    }
+
+    /// Apply `edit` to the columns up to TRUNC of line `line_num`.
+    /// Returns the number of spilled lines.
+    fn edit_within_trunc(&mut self, line_num: usize, edit: impl FnOnce(&mut Vec<char>)) -> usize {
+        let Some(text) = self.buffer.line_text(line_num) else {
+            return 0;
+        };
+        let (head, tail) = self.split_trunc(text);
+        let mut chars: Vec<char> = head.chars().collect();
+        let tail = tail.to_string();
+        edit(&mut chars);
+        let head: String = chars.into_iter().collect();
+        self.store_within_trunc(line_num, &head, &tail)
+    }
+
+    /// Add a line of typed text after the current line, which moves to the
+    /// last line written (including any spilled lines)
+    fn insert_within_trunc(&mut self, text: &str) {
+        self.buffer.insert_after(self.current_line, "");
+        self.current_line += 1;
+        self.current_line += self.store_within_trunc(self.current_line, text, "");
+    }
+
+    /// Report a command that lost text past TRUNC: "Truncated", RC=3
+    fn mark_truncated(&self, result: Result<CommandResult>) -> Result<CommandResult> {
+        match result {
+            Ok(r) if self.truncated => Ok(r.truncated()),
+            other => other,
+        }
+    }
+
+    /// Show "Truncated" for a screen edit that lost text past TRUNC
+    fn report_truncation(&mut self) {
+        if self.truncated {
+            self.message = Some("Truncated".to_string());
+        }
     }
 
     // -- Character-level editing (for data area screen editing) --
 
     /// Overtype a character at the given line and 0-based column
-    pub fn overtype_char(&mut self, line_num: usize, col: usize, ch: char) {
+    pub fn overtype_char(&mut self, line_num: usize, col: usize, ch: char) -> Result<()> {
+        let ch = self.apply_case_char(ch);
+        self.overtype_raw(line_num, col, ch)
+    }
+
+    /// Overtype without SET CASE (hex digits name an exact byte)
+    fn overtype_raw(&mut self, line_num: usize, col: usize, ch: char) -> Result<()> {
         if line_num == 0 || line_num > self.buffer.len() {
-            return;
+            return Ok(());
         }
-        if let Some(line) = self.buffer.get_mut(line_num) {
-            let mut text = line.text().to_string();
-            // Pad with spaces if needed
-            while text.len() <= col {
-                text.push(' ');
-            }
-            let mut chars: Vec<char> = text.chars().collect();
-            if col < chars.len() {
+        self.check_writable("Typing")?;
+        self.check_record_length(col + 1)?;
+        self.truncated = false;
+        if col >= self.trunc {
+            self.truncated = true;
+        } else {
+            self.edit_within_trunc(line_num, |chars| {
+                // Pad with spaces if needed
+                if chars.len() <= col {
+                    chars.resize(col + 1, ' ');
+                }
                 chars[col] = ch;
-            }
-            line.set_text(chars.into_iter().collect::<String>());
+            });
         }
+        self.report_truncation();
+        Ok(())
     }
 
     /// Insert a character at the given line and 0-based column
-    pub fn insert_char(&mut self, line_num: usize, col: usize, ch: char) {
+    pub fn insert_char(&mut self, line_num: usize, col: usize, ch: char) -> Result<()> {
         if line_num == 0 || line_num > self.buffer.len() {
-            return;
+            return Ok(());
         }
-        if let Some(line) = self.buffer.get_mut(line_num) {
-            let mut text = line.text().to_string();
-            while text.len() < col {
-                text.push(' ');
-            }
-            text.insert(col, ch);
-            line.set_text(text);
+        self.check_writable("Typing")?;
+        let len = self
+            .buffer
+            .get(line_num)
+            .map_or(0, |l| l.text().chars().count());
+        self.check_record_length(len.max(col) + 1)?;
+        let ch = self.apply_case_char(ch);
+        self.truncated = false;
+        if col >= self.trunc {
+            self.truncated = true;
+        } else {
+            self.edit_within_trunc(line_num, |chars| {
+                if chars.len() < col {
+                    chars.resize(col, ' ');
+                }
+                chars.insert(col, ch);
+            });
         }
+        self.report_truncation();
+        Ok(())
+    }
+
+    /// Overtype one hex digit of the character at the given line and 0-based
+    /// column: `high` picks the first digit. The byte is the one
+    /// `Codepage::to_hex` shows for the character (its Latin-1 code point
+    /// in UTF-8); a column past the end is a blank.
+    pub fn overtype_hex_digit(
+        &mut self,
+        line_num: usize,
+        col: usize,
+        high: bool,
+        digit: char,
+    ) -> Result<()> {
+        let value = digit
+            .to_digit(16)
+            .ok_or_else(|| XeditError::InvalidCommand(format!("Invalid hex digit: {}", digit)))?
+            as u8;
+        let current = self
+            .buffer
+            .get(line_num)
+            .map(|l| l.text().chars().nth(col).unwrap_or(' '))
+            .ok_or_else(|| XeditError::InvalidCommand("No line to edit".to_string()))?;
+        let cp = self.codepage;
+        let byte = cp.encode_char(current).ok_or_else(|| {
+            XeditError::InvalidCommand(format!("Character {:?} has no {} byte", current, cp.name()))
+        })?;
+        let byte = if high {
+            (byte & 0x0F) | (value << 4)
+        } else {
+            (byte & 0xF0) | value
+        };
+        self.overtype_raw(line_num, col, cp.decode_byte(byte))
     }
 
     /// Delete the character at the given line and 0-based column
@@ -776,11 +1373,23 @@ impl Editor {
         if line_num == 0 || line_num > self.buffer.len() {
             return;
         }
-        if let Some(line) = self.buffer.get_mut(line_num) {
-            let mut text = line.text().to_string();
-            if col < text.len() {
-                text.remove(col);
-                line.set_text(text);
+        if let Err(e) = self.check_writable("Typing") {
+            self.message = Some(e.to_string());
+            return;
+        }
+        if col < self.trunc {
+            // Columns past TRUNC stay in place
+            self.edit_within_trunc(line_num, |chars| {
+                if col < chars.len() {
+                    chars.remove(col);
+                }
+            });
+        } else if let Some(text) = self.buffer.line_text(line_num) {
+            let mut chars: Vec<char> = text.chars().collect();
+            if col < chars.len() {
+                chars.remove(col);
+                self.buffer
+                    .set_line_text(line_num, chars.into_iter().collect::<String>());
             }
         }
     }
@@ -793,11 +1402,11 @@ impl Editor {
     // -- Navigation --
 
     fn cmd_up(&mut self, n: usize) -> Result<CommandResult> {
-        if self.all_filter.is_some() {
+        if self.scope_limited() {
             let mut remaining = n;
             while remaining > 0 && self.current_line > 0 {
                 self.current_line -= 1;
-                if self.is_line_visible(self.current_line) || self.current_line == 0 {
+                if self.in_scope(self.current_line) {
                     remaining -= 1;
                 }
             }
@@ -808,11 +1417,11 @@ impl Editor {
     }
 
     fn cmd_down(&mut self, n: usize) -> Result<CommandResult> {
-        if self.all_filter.is_some() {
+        if self.scope_limited() {
             let mut remaining = n;
             while remaining > 0 && self.current_line < self.buffer.len() {
                 self.current_line += 1;
-                if self.is_line_visible(self.current_line) {
+                if self.in_scope(self.current_line) {
                     remaining -= 1;
                 }
             }
@@ -852,50 +1461,141 @@ impl Editor {
 
     // -- Search --
 
-    fn cmd_locate(&mut self, target: &Target) -> Result<CommandResult> {
-        let case_respect = self.case_respect;
-        let buffer = &self.buffer;
-        let resolved = target.resolve(self.current_line, buffer.len(), case_respect, &|n| {
-            buffer.line_text(n).map(String::from)
-        });
-        match resolved {
-            Some(line) => {
-                self.current_line = line;
-                Ok(CommandResult::ok())
+    /// Resolve a target from the current line using the editor's settings
+    fn resolve_target(&self, target: &Target) -> Option<usize> {
+        match target {
+            Target::Point(name) => return self.buffer.point(name),
+            Target::Relative(offset) if self.scope_limited() => {
+                return self.offset_in_scope(*offset)
             }
-            None => {
-                let msg = match target {
-                    Target::StringForward(s) | Target::StringBackward(s) => {
-                        format!("Target not found: {}", s)
-                    }
-                    _ => "Target not found".to_string(),
-                };
-                Err(XeditError::TargetNotFound(msg))
+            _ => {}
+        }
+        target.resolve(
+            self.current_line,
+            self.buffer.len(),
+            &self.match_options(),
+            &|n| self.scoped_text(n),
+        )
+    }
+
+    /// Text of a line that is in scope; lines out of scope never match
+    fn scoped_text(&self, line_num: usize) -> Option<String> {
+        if self.in_scope(line_num) {
+            self.buffer.with_text(line_num, str::to_string)
+        } else {
+            None
+        }
+    }
+
+    /// The line `offset` lines in scope away from the current line
+    fn offset_in_scope(&self, offset: i64) -> Option<usize> {
+        let mut line = self.current_line;
+        let mut remaining = offset.unsigned_abs();
+        while remaining > 0 {
+            line = if offset > 0 {
+                line + 1
+            } else {
+                line.checked_sub(1)?
+            };
+            if line > self.buffer.len() {
+                return None;
+            }
+            if self.in_scope(line) {
+                remaining -= 1;
             }
         }
+        Some(line)
     }
 
-    fn cmd_change(
-        &mut self,
-        from: &str,
-        to: &str,
-        target: Option<&Target>,
-        count: Option<usize>,
-    ) -> Result<CommandResult> {
-        self.snapshot_for_undo();
-        let max_changes = count.unwrap_or(1);
-        let mut changes_made = 0;
+    /// Resolve a LOCATE or CHANGE target, wrapping around the file when
+    /// SET WRAP is ON. Also reports whether the search wrapped.
+    fn resolve_search(&self, target: &Target) -> Option<(usize, bool)> {
+        if !self.wrap || matches!(target, Target::Point(_)) {
+            return self.resolve_target(target).map(|line| (line, false));
+        }
+        target.resolve_wrapped(
+            self.current_line,
+            self.buffer.len(),
+            &self.match_options(),
+            &|n| self.scoped_text(n),
+        )
+    }
+
+    /// Search settings (case, zone, arbchar) for string targets and CHANGE
+    fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            case_respect: self.case_respect,
+            zone: (self.zone_left, self.zone_right),
+            arbchar: self.arbchar(),
+            hex: self.hex.then_some(self.codepage),
+        }
+    }
 
-        let end_line = if let Some(t) = target {
-            let case_respect = self.case_respect;
-            let buffer = &self.buffer;
-            t.resolve(self.current_line, buffer.len(), case_respect, &|n| {
-                buffer.line_text(n).map(String::from)
-            })
-            .unwrap_or(self.buffer.len())
-        } else {
-            self.buffer.len()
-        };
+    /// Lines covered by a target used as a range (DELETE, COPY, MOVE).
+    ///
+    /// XEDIT ranges run from the current line up to, but not including,
+    /// the target line; `*` and `n` cover whole lines from the current one.
+    fn target_range(&self, target: &Target) -> Result<(usize, usize)> {
+        let current = self.current_line;
+        let len = self.buffer.len();
+        let (start, end) = match target {
+            Target::Star => (current.max(1), len),
+            Target::Relative(n) if *n > 0 && self.scope_limited() => {
+                let end = self.offset_in_scope(*n - 1).unwrap_or(len);
+                (current.max(1), end)
+            }
+            Target::Relative(n) if *n > 0 => (current.max(1), (current + *n as usize - 1).min(len)),
+            _ => {
+                let line = self
+                    .resolve_target(target)
+                    .ok_or_else(|| target_not_found(target))?;
+                if line > current {
+                    (current.max(1), line - 1)
+                } else {
+                    (line + 1, current)
+                }
+            }
+        };
+        if start > end || end == 0 {
+            return Err(XeditError::InvalidCommand(
+                "Target defines no lines".to_string(),
+            ));
+        }
+        Ok((start, end))
+    }
+
+    fn cmd_locate(&mut self, target: &Target) -> Result<CommandResult> {
+        match self.resolve_search(target) {
+            Some((line, wrapped)) => {
+                self.current_line = line;
+                if wrapped {
+                    Ok(CommandResult::with_message("Wrapped..."))
+                } else {
+                    Ok(CommandResult::ok())
+                }
+            }
+            None => Err(target_not_found(target)),
+        }
+    }
+
+    fn cmd_change(
+        &mut self,
+        from: &str,
+        to: &str,
+        target: Option<&Target>,
+        count: Option<usize>,
+        occurrences: usize,
+        first: usize,
+    ) -> Result<CommandResult> {
+        self.snapshot_for_undo();
+        let opts = self.match_options();
+        let to = self.apply_case(to);
+        let to = to.as_ref();
+        // Without a target or count only the next matching line changes
+        let max_lines = count.unwrap_or(if target.is_some() { usize::MAX } else { 1 });
+        let mut lines_changed = 0;
+        let mut changes_made = 0;
+        let mut wrapped_change = false;
 
         let start = if self.current_line == 0 {
             1
@@ -903,36 +1603,63 @@ impl Editor {
             self.current_line
         };
 
-        for line_num in start..=end_line {
-            if changes_made >= max_changes {
+        // With SET WRAP ON, `*` and the default target continue from the
+        // top of the file back to the line before the current one
+        let (end_line, wrapped) = match target {
+            Some(Target::Star) | None if self.wrap => (start - 1, true),
+            Some(t) => self.resolve_search(t).unwrap_or((self.buffer.len(), false)),
+            None => (self.buffer.len(), false),
+        };
+        // Lines from this index on were reached by wrapping past the end
+        let wrap_index = (self.buffer.len() + 1).saturating_sub(start);
+        let mut lines: Vec<usize> = if wrapped {
+            (start..=self.buffer.len())
+                .chain(1..=end_line.min(start - 1))
+                .collect()
+        } else {
+            (start..=end_line).collect()
+        };
+
+        for i in 0..lines.len() {
+            if lines_changed >= max_lines {
                 break;
             }
-            if let Some(line) = self.buffer.get(line_num) {
-                let text = line.text().to_string();
-                let (needle, haystack) = if self.case_respect {
-                    (from.to_string(), text.clone())
-                } else {
-                    (from.to_uppercase(), text.to_uppercase())
-                };
-
-                if let Some(pos) = haystack.find(&needle) {
-                    let new_text = format!("{}{}{}", &text[..pos], to, &text[pos + from.len()..]);
-                    if let Some(line_mut) = self.buffer.get_mut(line_num) {
-                        line_mut.set_text(new_text);
-                    }
-                    changes_made += 1;
-                    self.alt_count += 1;
-                    if !self.stay {
-                        self.current_line = line_num;
+            let line_num = lines[i];
+            if !self.in_scope(line_num) {
+                continue;
+            }
+            let Some(text) = self.buffer.line_text(line_num) else {
+                continue;
+            };
+            // Only the columns up to TRUNC are changed
+            let (head, tail) = self.split_trunc(text);
+            if let Some((new_head, n)) =
+                change_occurrences(&opts, head, from, to, occurrences, first)
+            {
+                let tail = tail.to_string();
+                let spilled = self.store_within_trunc(line_num, &new_head, &tail);
+                // Lines still to visit move down past any spilled lines
+                if spilled > 0 {
+                    for later in lines[i + 1..].iter_mut().filter(|l| **l > line_num) {
+                        *later += spilled;
                     }
                 }
+                lines_changed += 1;
+                changes_made += n;
+                wrapped_change |= wrapped && i >= wrap_index;
+                self.alt_count += 1;
+                if !self.stay {
+                    self.current_line = line_num + spilled;
+                }
             }
         }
 
         if changes_made > 0 {
             Ok(CommandResult::with_message(format!(
-                "{} change(s) made",
-                changes_made
+                "{}{} occurrence(s) changed on {} line(s)",
+                if wrapped_change { "Wrapped... " } else { "" },
+                changes_made,
+                lines_changed
             )))
         } else {
             Err(XeditError::TargetNotFound(format!(
@@ -946,9 +1673,10 @@ impl Editor {
 
     fn cmd_input(&mut self, text: Option<&str>) -> Result<CommandResult> {
         if let Some(text) = text {
+            self.check_record_length(text.chars().count())?;
             self.snapshot_for_undo();
-            self.buffer.insert_after(self.current_line, text);
-            self.current_line += 1;
+            let text = self.apply_case(text).into_owned();
+            self.insert_within_trunc(&text);
             self.alt_count += 1;
             Ok(CommandResult::ok())
         } else {
@@ -964,45 +1692,319 @@ impl Editor {
             ));
         }
 
-        match target {
-            None => {
-                self.buffer.delete(self.current_line);
-                self.alt_count += 1;
-                if self.current_line > self.buffer.len() {
-                    self.current_line = self.buffer.len();
+        let (start, end) = match target {
+            None => (self.current_line, self.current_line),
+            Some(t) => self.target_range(t)?,
+        };
+        let count = if self.scope_limited() {
+            // Lines out of scope in the range are kept; each run of lines
+            // in scope goes at once, from the bottom up
+            let mut runs: Vec<(usize, usize)> = Vec::new();
+            for n in (start..=end).filter(|&n| self.in_scope(n)) {
+                match runs.last_mut() {
+                    Some((_, last)) if *last + 1 == n => *last = n,
+                    _ => runs.push((n, n)),
                 }
+            }
+            for &(from, to) in runs.iter().rev() {
+                self.buffer.delete_range(from, to);
+            }
+            runs.iter().map(|(from, to)| to - from + 1).sum()
+        } else {
+            self.buffer.delete_range(start, end);
+            end - start + 1
+        };
+        self.alt_count += count;
+        self.current_line = start.min(self.buffer.len());
+        if target.is_none() {
+            Ok(CommandResult::ok())
+        } else {
+            Ok(CommandResult::with_message(format!(
+                "{} line(s) deleted",
+                count
+            )))
+        }
+    }
+
+    fn cmd_replace(&mut self, text: Option<&str>) -> Result<CommandResult> {
+        if self.current_line == 0 {
+            return Err(XeditError::InvalidCommand(
+                "Cannot REPLACE at Top of File".to_string(),
+            ));
+        }
+        if let Some(text) = text {
+            self.check_record_length(text.chars().count())?;
+        }
+        self.snapshot_for_undo();
+        self.alt_count += 1;
+        match text {
+            Some(text) => {
+                let text = self.apply_case(text).into_owned();
+                self.current_line += self.store_within_trunc(self.current_line, &text, "");
                 Ok(CommandResult::ok())
             }
-            Some(Target::Star) => {
-                let count = self.buffer.len() - self.current_line + 1;
-                self.buffer
-                    .delete_range(self.current_line, self.buffer.len());
-                self.alt_count += count;
-                if self.current_line > self.buffer.len() {
-                    self.current_line = self.buffer.len();
+            None => {
+                // No text: the current line is deleted and input mode replaces it
+                self.buffer.delete(self.current_line);
+                self.current_line -= 1;
+                Ok(CommandResult::enter_input())
+            }
+        }
+    }
+
+    fn cmd_add(&mut self, n: usize) -> Result<CommandResult> {
+        self.snapshot_for_undo();
+        for _ in 0..n {
+            self.buffer.insert_after(self.current_line, "");
+        }
+        self.alt_count += n;
+        // The cursor goes to the first added line; the current line stays put
+        self.cursor_request = Some(CursorRequest::File {
+            line: self.current_line + 1,
+            col: 1,
+        });
+        Ok(CommandResult::ok())
+    }
+
+    /// OVERLAY text: blanks leave the line unchanged, `_` puts a blank
+    fn cmd_overlay(&mut self, text: &str) -> Result<CommandResult> {
+        if self.current_line == 0 {
+            return Err(XeditError::InvalidCommand(
+                "Cannot OVERLAY at Top of File".to_string(),
+            ));
+        }
+        self.snapshot_for_undo();
+        let text = self.apply_case(text).into_owned();
+        if self.current_line <= self.buffer.len() {
+            self.edit_within_trunc(self.current_line, |chars| {
+                for (i, c) in text.chars().enumerate() {
+                    if c == ' ' {
+                        continue;
+                    }
+                    if chars.len() <= i {
+                        chars.resize(i + 1, ' ');
+                    }
+                    chars[i] = if c == '_' { ' ' } else { c };
                 }
-                Ok(CommandResult::with_message(format!(
-                    "{} line(s) deleted",
-                    count
-                )))
+            });
+            self.alt_count += 1;
+        }
+        Ok(CommandResult::ok())
+    }
+
+    // -- Column pointer --
+
+    /// Resolve a column target from the column pointer to `(line, column)`.
+    /// String targets search the current line after (or before) the column
+    /// pointer, then with `other_lines` the following (or preceding) lines.
+    fn resolve_column(&self, target: &ColumnTarget, other_lines: bool) -> Option<(usize, usize)> {
+        let line = self.current_line;
+        let col = self.current_col;
+        match target {
+            ColumnTarget::Absolute(n) => Some((line, *n)),
+            ColumnTarget::Relative(offset) => {
+                let col = col as i64 + offset;
+                (col >= 1).then_some((line, col as usize))
             }
-            Some(Target::Relative(n)) if *n > 0 => {
-                let end = (self.current_line + *n as usize - 1).min(self.buffer.len());
-                let count = end - self.current_line + 1;
-                self.buffer.delete_range(self.current_line, end);
-                self.alt_count += count;
-                if self.current_line > self.buffer.len() {
-                    self.current_line = self.buffer.len();
+            ColumnTarget::Star => {
+                let len = self.buffer.with_text(line, |text| text.chars().count());
+                Some((line, len.unwrap_or(0) + 1))
+            }
+            ColumnTarget::StringForward(needle) => {
+                let opts = self.match_options();
+                let find = |n: usize, from_col: usize| {
+                    self.buffer
+                        .with_text(n, |text| first_column_match(&opts, text, needle, from_col))
+                        .flatten()
+                };
+                let here = (line > 0).then(|| find(line, col + 1)).flatten();
+                here.map(|c| (line, c)).or_else(|| {
+                    if !other_lines {
+                        return None;
+                    }
+                    (line + 1..=self.buffer.len()).find_map(|n| find(n, 1).map(|c| (n, c)))
+                })
+            }
+            ColumnTarget::StringBackward(needle) => {
+                let opts = self.match_options();
+                let find = |n: usize, before_col: usize| {
+                    self.buffer
+                        .with_text(n, |text| last_column_match(&opts, text, needle, before_col))
+                        .flatten()
+                };
+                let here = (line > 0).then(|| find(line, col)).flatten();
+                here.map(|c| (line, c)).or_else(|| {
+                    if !other_lines {
+                        return None;
+                    }
+                    (1..line)
+                        .rev()
+                        .find_map(|n| find(n, usize::MAX).map(|c| (n, c)))
+                })
+            }
+        }
+    }
+
+    /// CLOCATE column-target: move the column pointer. A string found on
+    /// another line moves the line pointer too.
+    fn cmd_clocate(&mut self, target: &ColumnTarget) -> Result<CommandResult> {
+        let (line, col) = self
+            .resolve_column(target, true)
+            .ok_or_else(|| column_target_not_found(target))?;
+        self.current_line = line;
+        self.current_col = col;
+        Ok(CommandResult::ok())
+    }
+
+    /// Apply `edit` to the current line's columns (up to TRUNC) for one of
+    /// the C-family commands, which work at the column pointer
+    fn edit_at_column(
+        &mut self,
+        name: &str,
+        edit: impl FnOnce(&mut Vec<char>),
+    ) -> Result<CommandResult> {
+        if self.current_line == 0 {
+            return Err(XeditError::InvalidCommand(format!(
+                "Cannot {} at Top of File",
+                name
+            )));
+        }
+        if self.current_col > self.trunc {
+            // Nothing of the line past TRUNC can be changed
+            self.truncated = true;
+            return Ok(CommandResult::ok());
+        }
+        self.snapshot_for_undo();
+        self.edit_within_trunc(self.current_line, edit);
+        self.alt_count += 1;
+        Ok(CommandResult::ok())
+    }
+
+    /// CDELETE [column-target]: delete from the column pointer up to the
+    /// target column. A target to the left deletes the columns before the
+    /// pointer, which moves to the target.
+    fn cmd_cdelete(&mut self, target: &ColumnTarget) -> Result<CommandResult> {
+        if self.current_line == 0 {
+            return Err(XeditError::InvalidCommand(
+                "Cannot CDELETE at Top of File".to_string(),
+            ));
+        }
+        let (_, to) = self
+            .resolve_column(target, false)
+            .ok_or_else(|| column_target_not_found(target))?;
+        let (from, to) = if to >= self.current_col {
+            (self.current_col, to)
+        } else {
+            (to, self.current_col)
+        };
+        if from == to {
+            return Ok(CommandResult::ok());
+        }
+        self.current_col = from;
+        self.edit_at_column("CDELETE", |chars| {
+            let end = (to - 1).min(chars.len());
+            let start = (from - 1).min(end);
+            chars.drain(start..end);
+        })
+    }
+
+    /// CINSERT text: insert text at the column pointer
+    fn cmd_cinsert(&mut self, text: &str) -> Result<CommandResult> {
+        let text = self.apply_case(text).into_owned();
+        let at = self.current_col - 1;
+        self.edit_at_column("CINSERT", |chars| {
+            if chars.len() < at {
+                chars.resize(at, ' ');
+            }
+            chars.splice(at..at, text.chars());
+        })
+    }
+
+    /// CAPPEND [text]: add text after the last nonblank character. The
+    /// column pointer moves to the first character appended.
+    fn cmd_cappend(&mut self, text: &str) -> Result<CommandResult> {
+        let end = self
+            .buffer
+            .with_text(self.current_line, |line| {
+                self.split_trunc(line).0.trim_end().chars().count()
+            })
+            .unwrap_or(0);
+        if self.current_line > 0 {
+            self.current_col = end + 1;
+        }
+        if text.is_empty() {
+            return Ok(CommandResult::ok());
+        }
+        let text = self.apply_case(text).into_owned();
+        self.edit_at_column("CAPPEND", |chars| {
+            chars.truncate(end);
+            chars.extend(text.chars());
+        })
+    }
+
+    /// CREPLACE text: replace characters starting at the column pointer
+    fn cmd_creplace(&mut self, text: &str) -> Result<CommandResult> {
+        let text = self.apply_case(text).into_owned();
+        let at = self.current_col - 1;
+        self.edit_at_column("CREPLACE", |chars| {
+            let end = at + text.chars().count();
+            if chars.len() < end {
+                chars.resize(end, ' ');
+            }
+            chars.splice(at..end, text.chars());
+        })
+    }
+
+    /// COVERLAY text: overlay at the column pointer; blanks leave the line
+    /// unchanged and `_` puts a blank
+    fn cmd_coverlay(&mut self, text: &str) -> Result<CommandResult> {
+        let text = self.apply_case(text).into_owned();
+        let at = self.current_col - 1;
+        self.edit_at_column("COVERLAY", |chars| {
+            for (i, c) in text.chars().enumerate() {
+                if c == ' ' {
+                    continue;
                 }
-                Ok(CommandResult::with_message(format!(
-                    "{} line(s) deleted",
-                    count
-                )))
+                if chars.len() <= at + i {
+                    chars.resize(at + i + 1, ' ');
+                }
+                chars[at + i] = if c == '_' { ' ' } else { c };
             }
-            _ => Err(XeditError::InvalidCommand(
-                "Invalid target for DELETE".to_string(),
-            )),
+        })
+    }
+
+    /// COPY/MOVE target1 target2: the lines up to target1 go after target2.
+    /// The last line copied or moved becomes the current line.
+    fn cmd_copy_move(
+        &mut self,
+        range: &Target,
+        dest: &Target,
+        op_type: OperationType,
+    ) -> Result<CommandResult> {
+        let (start, end) = self.target_range(range)?;
+        let dest_line = self
+            .resolve_target(dest)
+            .ok_or_else(|| target_not_found(dest))?;
+        if op_type == OperationType::Move && dest_line >= start && dest_line < end {
+            return Err(XeditError::InvalidCommand(
+                "MOVE destination is inside the lines being moved".to_string(),
+            ));
         }
+
+        self.snapshot_for_undo();
+        let count = end - start + 1;
+        let op = PendingOperation {
+            op_type,
+            source_start: start,
+            source_end: end,
+        };
+        let result = self.copy_or_move_lines(&op, dest_line);
+        self.current_line = if op.op_type == OperationType::Move && dest_line >= end {
+            dest_line
+        } else {
+            dest_line + count
+        };
+        Ok(result)
     }
 
     // -- File commands --
@@ -1026,9 +2028,11 @@ impl Editor {
     }
 
     fn cmd_get(&mut self, filename: &str) -> Result<CommandResult> {
-        let path = Path::new(filename);
-        let content =
-            fs::read_to_string(path).map_err(|_| XeditError::FileNotFound(filename.to_string()))?;
+        let not_found = |_| XeditError::FileNotFound(filename.to_string());
+        let id = self.fs.locate(filename).map_err(not_found)?;
+        let bytes = self.fs.read(&id.path).map_err(not_found)?;
+        let content = String::from_utf8(bytes)
+            .map_err(|e| XeditError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
         let lines: Vec<String> = content.lines().map(String::from).collect();
         let count = lines.len();
         self.snapshot_for_undo();
@@ -1052,16 +2056,69 @@ impl Editor {
                 self.zone_left = *left;
                 self.zone_right = *right;
             }
+            SetCommand::Recfm(recfm) => {
+                self.buffer.set_recfm(recfm.clone());
+                self.fit_trunc_to_record();
+            }
+            SetCommand::Lrecl(n) => {
+                self.buffer.set_lrecl(*n);
+                self.fit_trunc_to_record();
+            }
+            SetCommand::Codepage(cp) => self.codepage = *cp,
             SetCommand::Number(on) => self.show_number = *on,
             SetCommand::Prefix(on) => self.show_prefix = *on,
             SetCommand::Scale(on) => self.show_scale = *on,
             SetCommand::CurLine(pos) => self.curline = pos.clone(),
-            SetCommand::Case(setting) => {
-                self.case_respect = matches!(setting, CaseSetting::Respect);
+            SetCommand::Case(first, second) => {
+                for setting in std::iter::once(first).chain(second) {
+                    match setting {
+                        CaseSetting::Mixed => self.case_upper = false,
+                        CaseSetting::Upper => self.case_upper = true,
+                        CaseSetting::Respect => self.case_respect = true,
+                        CaseSetting::Ignore => self.case_respect = false,
+                    }
+                }
+            }
+            SetCommand::Spill(setting) => self.spill = *setting,
+            SetCommand::Point(name, true) => {
+                if self.current_line == 0 {
+                    return Err(XeditError::InvalidCommand(
+                        "SET POINT: Top of File cannot be named".to_string(),
+                    ));
+                }
+                self.buffer.set_point(name, self.current_line);
             }
+            SetCommand::Point(name, false) => {
+                if !self.buffer.remove_point(name) {
+                    return Err(XeditError::InvalidCommand(format!(
+                        "SET POINT: no such name: .{}",
+                        name
+                    )));
+                }
+            }
+            SetCommand::Select {
+                level,
+                relative,
+                target,
+            } => self.set_select(*level, *relative, target.as_ref())?,
+            SetCommand::Display(low, high) => self.display = (*low, *high),
+            SetCommand::Scope(scope) => self.scope = *scope,
             SetCommand::Wrap(on) => self.wrap = *on,
             SetCommand::Hex(on) => self.hex = *on,
             SetCommand::Stay(on) => self.stay = *on,
+            SetCommand::Arbchar(on, ch) => {
+                self.arbchar_on = *on;
+                if let Some(ch) = ch {
+                    self.arbchar = *ch;
+                }
+            }
+            SetCommand::Undo(limit) => self.undo.set_limit(*limit),
+            SetCommand::UndoFile(on) => {
+                self.undo_file = *on;
+                if *on {
+                    self.restore_undo_file();
+                }
+            }
             SetCommand::Shadow(on) => self.show_shadow = *on,
             SetCommand::Reserved(row, text) => {
                 self.reserved_lines.insert(*row, text.clone());
@@ -1088,12 +2145,20 @@ impl Editor {
             SetCommand::MacroPath(paths) => {
                 self.macro_path = paths.clone();
             }
+            SetCommand::PrefixSynonym(new, old) => {
+                self.prefix_synonyms.insert(new.clone(), old.clone());
+            }
+            SetCommand::Pending(name) => return self.set_pending(name.as_deref()),
         }
         Ok(CommandResult::ok())
     }
 
     fn cmd_query(&self, what: &str) -> Result<CommandResult> {
-        let what_upper = what.trim().to_uppercase();
+        let what_upper = what
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_uppercase();
         let msg = match what_upper.as_str() {
             "" => format!(
                 "Size={} Line={} Col={} Alt={} Trunc={}",
@@ -1107,9 +2172,54 @@ impl Editor {
             "LINE" => format!("Line={}", self.current_line),
             "COLUMN" | "COL" => format!("Col={}", self.current_col),
             "TRUNC" => format!("Trunc={}", self.trunc),
+            "ZONE" => format!("Zone={} {}", self.zone_left, zone_column(self.zone_right)),
             "ALT" => format!("Alt={}", self.alt_count),
+            "ARBCHAR" | "ARB" => format!(
+                "Arbchar={} {}",
+                if self.arbchar_on { "ON" } else { "OFF" },
+                self.arbchar
+            ),
             "LRECL" => format!("Lrecl={}", self.buffer.lrecl()),
             "RECFM" => format!("Recfm={:?}", self.buffer.recfm()),
+            "CODEPAGE" | "CP" => format!("Codepage={}", self.codepage.name()),
+            "SPILL" => format!("Spill={}", spill_name(self.spill)),
+            "SELECT" => format!(
+                "Select={} {}",
+                self.buffer.select_level(self.current_line),
+                self.buffer.max_select_level()
+            ),
+            "DISPLAY" => format!("Display={} {}", self.display.0, zone_column(self.display.1)),
+            "SCOPE" => format!("Scope={}", scope_name(self.scope)),
+            "PENDING" => match &self.pending_block {
+                Some(pending) => format!(
+                    "Pending={} {}",
+                    block_name(&pending.command),
+                    pending.start_line
+                ),
+                None => "No block pending".to_string(),
+            },
+            "CASE" => format!(
+                "Case={} {}",
+                if self.case_upper { "UPPER" } else { "MIXED" },
+                if self.case_respect {
+                    "RESPECT"
+                } else {
+                    "IGNORE"
+                }
+            ),
+            "POINT" => match self.buffer.points_at(self.current_line).as_slice() {
+                [] => format!("No names on line {}", self.current_line),
+                names => format!("Point=.{}", names.join(" .")),
+            },
+            "POINT *" => match self.point_list().as_slice() {
+                [] => "No names defined".to_string(),
+                points => format!("Point={}", points.join(" ")),
+            },
+            "UNDO" => format!(
+                "Undo={} Undofile={}",
+                self.undo.limit(),
+                if self.undo_file { "ON" } else { "OFF" }
+            ),
             _ => {
                 return Err(XeditError::InvalidCommand(format!(
                     "Unknown QUERY: {}",
@@ -1120,6 +2230,123 @@ impl Editor {
         Ok(CommandResult::with_message(msg))
     }
 
+    /// Evaluate EXTRACT operands (`/item/item/...`) against the current state.
+    ///
+    /// Returns one `(ITEM, values)` pair per requested item, in request order.
+    /// Each item maps onto REXX stems as `item.0` = count, `item.n` = value.
+    pub fn extract(&self, operands: &str) -> Result<Vec<(String, Vec<String>)>> {
+        let operands = operands.trim();
+        let delim = operands.chars().next().ok_or_else(|| {
+            XeditError::InvalidCommand("EXTRACT requires /item/ operands".to_string())
+        })?;
+        let items: Vec<&str> = if delim.is_alphanumeric() {
+            operands.split_whitespace().collect()
+        } else {
+            operands
+                .split(delim)
+                .flat_map(str::split_whitespace)
+                .collect()
+        };
+
+        let mut results = Vec::new();
+        let mut items = items.into_iter().peekable();
+        while let Some(item) = items.next() {
+            let name = item.to_uppercase();
+            // EXTRACT /POINT */ lists every name instead of the current line's
+            let values = if name == "POINT" && items.next_if_eq(&"*").is_some() {
+                Some(self.point_list())
+            } else {
+                self.extract_item(&name)
+            };
+            let values = values.ok_or_else(|| {
+                XeditError::InvalidCommand(format!("Invalid EXTRACT item: {}", item))
+            })?;
+            results.push((name, values));
+        }
+        Ok(results)
+    }
+
+    /// Every SET POINT name as `line .NAME`, in line order
+    fn point_list(&self) -> Vec<String> {
+        self.buffer
+            .points()
+            .into_iter()
+            .map(|(line, name)| format!("{} .{}", line, name))
+            .collect()
+    }
+
+    fn extract_item(&self, name: &str) -> Option<Vec<String>> {
+        let on_off = |flag: bool| if flag { "ON" } else { "OFF" }.to_string();
+        let values = match name {
+            "CURLINE" => vec![
+                self.current_line.to_string(),
+                on_off(self.at_tof()),
+                self.current_line_text().to_string(),
+            ],
+            "SIZE" => vec![self.buffer.len().to_string()],
+            "LINE" => vec![self.current_line.to_string()],
+            "COLUMN" => vec![self.current_col.to_string()],
+            "FNAME" => vec![self.filename.clone()],
+            "FTYPE" => vec![self.filetype.clone()],
+            "FMODE" => vec![self.filemode.clone()],
+            "TRUNC" => vec![self.trunc.to_string()],
+            "ZONE" => vec![self.zone_left.to_string(), zone_column(self.zone_right)],
+            "ALT" => vec![self.alt_count.to_string()],
+            "TOF" => vec![on_off(self.at_tof())],
+            "EOF" => vec![on_off(self.at_eof())],
+            "MODIFIED" => vec![on_off(self.is_modified())],
+            "LRECL" => vec![self.buffer.lrecl().to_string()],
+            "RECFM" => vec![match self.buffer.recfm() {
+                RecordFormat::Variable => "V",
+                RecordFormat::Fixed => "F",
+            }
+            .to_string()],
+            "CODEPAGE" => vec![self.codepage.name().to_string()],
+            "NUMBER" => vec![on_off(self.show_number)],
+            "PREFIX" => vec![on_off(self.show_prefix)],
+            "SCALE" => vec![on_off(self.show_scale)],
+            "CASE" => vec![
+                if self.case_upper { "UPPER" } else { "MIXED" }.to_string(),
+                if self.case_respect {
+                    "RESPECT"
+                } else {
+                    "IGNORE"
+                }
+                .to_string(),
+            ],
+            "WRAP" => vec![on_off(self.wrap)],
+            "SPILL" => vec![spill_name(self.spill).to_string()],
+            "SELECT" => vec![
+                self.buffer.select_level(self.current_line).to_string(),
+                self.buffer.max_select_level().to_string(),
+            ],
+            "DISPLAY" => vec![self.display.0.to_string(), zone_column(self.display.1)],
+            "SCOPE" => vec![scope_name(self.scope).to_string()],
+            // The line a pending block starts on and the block's prefix name
+            "PENDING" => match &self.pending_block {
+                Some(pending) => vec![
+                    pending.start_line.to_string(),
+                    block_name(&pending.command).to_string(),
+                ],
+                None => Vec::new(),
+            },
+            // The current line's number and names, if it has any
+            "POINT" => match self.buffer.points_at(self.current_line).as_slice() {
+                [] => Vec::new(),
+                names => vec![format!("{} .{}", self.current_line, names.join(" ."))],
+            },
+            "HEX" => vec![on_off(self.hex)],
+            "STAY" => vec![on_off(self.stay)],
+            "ARBCHAR" => vec![on_off(self.arbchar_on), self.arbchar.to_string()],
+            "UNDO" => vec![self.undo.limit().to_string(), on_off(self.undo_file)],
+            "SHADOW" => vec![on_off(self.show_shadow)],
+            "VERIFY" => vec![self.verify_start.to_string(), self.verify_end.to_string()],
+            "LASTMSG" => vec![self.message.clone().unwrap_or_default()],
+            _ => return None,
+        };
+        Some(values)
+    }
+
     #[cfg(feature = "rexx")]
     fn cmd_macro(&mut self, args: &str) -> Result<CommandResult> {
         let (macro_name, macro_args) = if let Some(pos) = args.find(char::is_whitespace) {
@@ -1136,6 +2363,58 @@ impl Editor {
         Ok(CommandResult::ok())
     }
 
+    /// Run a prefix macro with `line operand` as its argument string
+    #[cfg(feature = "rexx")]
+    fn run_prefix_macro(
+        &mut self,
+        line_num: usize,
+        name: &str,
+        operand: &str,
+    ) -> Result<CommandResult> {
+        let (_path, source) = self
+            .find_macro(name)
+            .ok_or_else(|| XeditError::PrefixError(format!("Unknown prefix command: {}", name)))?;
+        let outer = self.prefix_line.replace(line_num);
+        let args = format!("{} {}", line_num, operand);
+        let result = crate::macro_engine::run_macro(self, &source, args.trim_end());
+        self.prefix_line = outer;
+        result?;
+        Ok(CommandResult::ok())
+    }
+
+    #[cfg(not(feature = "rexx"))]
+    fn run_prefix_macro(
+        &mut self,
+        _line_num: usize,
+        name: &str,
+        _operand: &str,
+    ) -> Result<CommandResult> {
+        Err(XeditError::PrefixError(format!(
+            "Unknown prefix command: {}",
+            name
+        )))
+    }
+
+    /// SET PENDING BLOCK name / SET PENDING OFF, for block prefix macros.
+    /// The block starts on the line the running prefix macro was entered on.
+    fn set_pending(&mut self, name: Option<&str>) -> Result<CommandResult> {
+        let Some(name) = name else {
+            self.pending_block = None;
+            return Ok(CommandResult::ok());
+        };
+        let command = BlockType::User(name.to_string());
+        if matches!(&self.pending_block, Some(p) if p.command != command) {
+            return Err(XeditError::PrefixError(
+                "Conflicting block operation pending".to_string(),
+            ));
+        }
+        self.pending_block = Some(PendingBlock {
+            command,
+            start_line: self.prefix_line.unwrap_or(self.current_line),
+        });
+        Ok(CommandResult::ok())
+    }
+
     fn cmd_stack(&mut self, n: usize) -> Result<CommandResult> {
         if self.current_line == 0 {
             return Err(XeditError::InvalidCommand(
@@ -1178,7 +2457,7 @@ impl Editor {
 
     fn cmd_help(&self) -> Result<CommandResult> {
         Ok(CommandResult::with_message(
-            "Commands: UP DOWN TOP BOTTOM FORWARD BACKWARD LOCATE CHANGE INPUT DELETE FILE SAVE QUIT QQUIT SET QUERY",
+            "Commands: UP DOWN TOP BOTTOM FORWARD BACKWARD LOCATE CHANGE INPUT DELETE REPLACE ADD OVERLAY COPY MOVE FILE SAVE QUIT QQUIT SET QUERY",
         ))
     }
 
@@ -1191,14 +2470,33 @@ impl Editor {
     ) -> Result<CommandResult> {
         // Snapshot for undo on modifying prefix commands
         match cmd {
-            PrefixCommand::SetCurrent => {}
-            _ => self.snapshot_for_undo(),
+            PrefixCommand::SetCurrent
+            | PrefixCommand::Point(_)
+            | PrefixCommand::Exclude(_)
+            | PrefixCommand::ExcludeBlock
+            | PrefixCommand::Show(_)
+            // Prefix macros are checked command by command as they run
+            | PrefixCommand::User { .. } => {}
+            _ => {
+                self.check_writable("Prefix command")?;
+                self.snapshot_for_undo();
+            }
         }
-        match cmd {
+        self.truncated = false;
+        let result = match cmd {
             PrefixCommand::SetCurrent => {
                 self.current_line = line_num;
                 Ok(CommandResult::ok())
             }
+            PrefixCommand::Point(name) => {
+                if line_num == 0 || line_num > self.buffer.len() {
+                    return Err(XeditError::PrefixError(
+                        "Only file lines can be named".to_string(),
+                    ));
+                }
+                self.buffer.set_point(name, line_num);
+                Ok(CommandResult::ok())
+            }
             PrefixCommand::Delete => {
                 self.buffer.delete(line_num);
                 self.alt_count += 1;
@@ -1225,25 +2523,34 @@ impl Editor {
                 Ok(CommandResult::ok())
             }
             PrefixCommand::ShiftRight(n) => {
-                if let Some(line) = self.buffer.get_mut(line_num) {
-                    let new_text = format!("{}{}", " ".repeat(*n), line.text());
-                    line.set_text(new_text);
+                if line_num >= 1 && line_num <= self.buffer.len() {
+                    self.edit_within_trunc(line_num, |chars| {
+                        chars.splice(0..0, std::iter::repeat_n(' ', *n));
+                    });
                     self.alt_count += 1;
                 }
                 Ok(CommandResult::ok())
             }
             PrefixCommand::ShiftLeft(n) => {
-                if let Some(line) = self.buffer.get_mut(line_num) {
-                    let text = line.text();
-                    let spaces = text.len().min(*n);
-                    if text[..spaces].chars().all(|c| c == ' ') {
-                        let new_text = text[spaces..].to_string();
-                        line.set_text(new_text);
-                        self.alt_count += 1;
-                    }
+                // Only blanks are shifted off; columns past TRUNC stay put
+                let blank = self.buffer.line_text(line_num).is_some_and(|text| {
+                    let (head, _) = self.split_trunc(text);
+                    head.chars().take(*n).all(|c| c == ' ')
+                });
+                if blank {
+                    self.edit_within_trunc(line_num, |chars| {
+                        chars.drain(..chars.len().min(*n));
+                    });
+                    self.alt_count += 1;
                 }
                 Ok(CommandResult::ok())
             }
+            PrefixCommand::Exclude(n) => {
+                let end = (line_num + n.saturating_sub(1)).min(self.buffer.len());
+                self.exclude_lines(line_num, end)
+            }
+            PrefixCommand::ExcludeBlock => self.handle_block_marker(line_num, BlockType::Exclude),
+            PrefixCommand::Show(count) => self.show_lines(line_num, *count),
             PrefixCommand::DeleteBlock => self.handle_block_marker(line_num, BlockType::Delete),
             PrefixCommand::CopyBlock => self.handle_block_marker(line_num, BlockType::Copy),
             PrefixCommand::MoveBlock => self.handle_block_marker(line_num, BlockType::Move),
@@ -1272,7 +2579,11 @@ impl Editor {
             }
             PrefixCommand::Following => self.execute_pending_destination(line_num, true),
             PrefixCommand::Preceding => self.execute_pending_destination(line_num, false),
-        }
+            PrefixCommand::User { name, operand } => self.run_prefix_macro(line_num, name, operand),
+        };
+        self.end_undo_group();
+        self.buffer.trim_cache(self.current_line);
+        self.mark_truncated(result)
     }
 
     fn handle_block_marker(
@@ -1337,6 +2648,11 @@ impl Editor {
                     self.alt_count += end - start + 1;
                     Ok(CommandResult::with_message("Block duplicated"))
                 }
+                BlockType::Exclude => self.exclude_lines(start, end),
+                BlockType::User(name) => Err(XeditError::PrefixError(format!(
+                    "Block {} is ended by its prefix macro",
+                    name
+                ))),
             }
         } else {
             self.pending_block = Some(PendingBlock {
@@ -1347,28 +2663,116 @@ impl Editor {
         }
     }
 
-    fn execute_pending_destination(
-        &mut self,
-        dest_line: usize,
-        after: bool,
-    ) -> Result<CommandResult> {
-        let op = self
-            .pending_operation
-            .take()
-            .ok_or_else(|| XeditError::PrefixError("No pending copy/move operation".to_string()))?;
-
-        let mut texts = Vec::new();
-        for i in op.source_start..=op.source_end {
-            if let Some(line) = self.buffer.get(i) {
-                texts.push(line.text().to_string());
-            }
+    /// Prefix `x`/`xx`: hide lines by giving them a selection level just
+    /// outside SET DISPLAY (above the high level, or below the low one
+    /// when the high level is `*`)
+    fn exclude_lines(&mut self, start: usize, end: usize) -> Result<CommandResult> {
+        if start == 0 || start > self.buffer.len() {
+            return Err(XeditError::PrefixError(
+                "Only file lines can be excluded".to_string(),
+            ));
         }
-
+        let (low, high) = self.display;
+        let level = match (low, high) {
+            (_, high) if high < usize::MAX => high + 1,
+            (low, _) if low > 0 => low - 1,
+            _ => {
+                return Err(XeditError::PrefixError(
+                    "Lines cannot be excluded with SET DISPLAY 0 *".to_string(),
+                ))
+            }
+        };
+        for n in start..=end {
+            self.buffer.set_select_level(n, level);
+        }
+        self.current_to_visible();
+        Ok(CommandResult::ok())
+    }
+
+    /// Prefix `s[n]`/`s-n` on a shadow line: give the first (or last) n of
+    /// its hidden lines, or all of them, the low SET DISPLAY level
+    fn show_lines(&mut self, line_num: usize, count: Option<i64>) -> Result<CommandResult> {
+        let (first, last) = self.shadow_group(line_num).ok_or_else(|| {
+            XeditError::PrefixError("S is only valid on a shadow line".to_string())
+        })?;
+        let (first, last) = match count {
+            None => (first, last),
+            Some(n) if n >= 0 => (
+                first,
+                first
+                    .saturating_add(n as usize)
+                    .min(last + 1)
+                    .min(self.buffer.len() + 1)
+                    - 1,
+            ),
+            Some(n) => (
+                (last + 1)
+                    .saturating_sub(n.unsigned_abs() as usize)
+                    .max(first),
+                last,
+            ),
+        };
+        for n in first..=last {
+            self.buffer.set_select_level(n, self.display.0);
+        }
+        Ok(CommandResult::ok())
+    }
+
+    /// The run of hidden lines `(first, last)` that `line_num` is in
+    pub fn shadow_group(&self, line_num: usize) -> Option<(usize, usize)> {
+        if self.is_line_visible(line_num) {
+            return None;
+        }
+        let mut first = line_num;
+        while !self.is_line_visible(first - 1) {
+            first -= 1;
+        }
+        Some((first, line_num + self.shadow_count_after(line_num)))
+    }
+
+    /// Move off a current line that was just hidden: down to the next
+    /// displayed line, or up when there is none
+    fn current_to_visible(&mut self) {
+        if self.is_line_visible(self.current_line) {
+            return;
+        }
+        let len = self.buffer.len();
+        self.current_line = (self.current_line..=len)
+            .find(|&n| self.is_line_visible(n))
+            .or_else(|| {
+                (0..self.current_line)
+                    .rev()
+                    .find(|&n| self.is_line_visible(n))
+            })
+            .unwrap_or(0);
+    }
+
+    fn execute_pending_destination(
+        &mut self,
+        dest_line: usize,
+        after: bool,
+    ) -> Result<CommandResult> {
+        let op = self
+            .pending_operation
+            .take()
+            .ok_or_else(|| XeditError::PrefixError("No pending copy/move operation".to_string()))?;
+
         let insert_after = if after {
             dest_line
         } else {
             dest_line.saturating_sub(1)
         };
+        Ok(self.copy_or_move_lines(&op, insert_after))
+    }
+
+    /// Copy or move the operation's source lines to follow `insert_after`
+    fn copy_or_move_lines(&mut self, op: &PendingOperation, insert_after: usize) -> CommandResult {
+        let mut texts = Vec::new();
+        for i in op.source_start..=op.source_end {
+            if let Some(line) = self.buffer.get(i) {
+                texts.push(line.text().to_string());
+            }
+        }
 
         // For move: delete source lines first (adjust dest if needed)
         if op.op_type == OperationType::Move {
@@ -1387,20 +2791,218 @@ impl Editor {
             if self.current_line > self.buffer.len() {
                 self.current_line = self.buffer.len();
             }
-            Ok(CommandResult::with_message(format!(
-                "{} line(s) moved",
-                count
-            )))
+            CommandResult::with_message(format!("{} line(s) moved", count))
         } else {
             let count = texts.len();
             self.buffer.insert_lines_after(insert_after, texts);
             self.alt_count += count;
-            Ok(CommandResult::with_message(format!(
-                "{} line(s) copied",
-                count
-            )))
+            CommandResult::with_message(format!("{} line(s) copied", count))
+        }
+    }
+}
+
+/// Display a zone column, with `usize::MAX` shown as `*`
+fn zone_column(col: usize) -> String {
+    if col == usize::MAX {
+        "*".to_string()
+    } else {
+        col.to_string()
+    }
+}
+
+/// SET SPILL value as shown by QUERY and EXTRACT
+fn spill_name(spill: SpillSetting) -> &'static str {
+    match spill {
+        SpillSetting::Off => "OFF",
+        SpillSetting::On => "ON",
+        SpillSetting::Word => "WORD",
+    }
+}
+
+fn scope_name(scope: ScopeSetting) -> &'static str {
+    match scope {
+        ScopeSetting::Display => "DISPLAY",
+        ScopeSetting::All => "ALL",
+    }
+}
+
+/// The prefix name that marks a kind of block, as EXTRACT /PENDING/ shows it
+fn block_name(block: &BlockType) -> &str {
+    match block {
+        BlockType::Delete => "DD",
+        BlockType::Copy => "CC",
+        BlockType::Move => "MM",
+        BlockType::Duplicate => "\"\"",
+        BlockType::Exclude => "XX",
+        BlockType::User(name) => name,
+    }
+}
+
+/// Uppercase a character unless that would change the character count
+fn upper_char(ch: char) -> char {
+    let mut upper = ch.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(u), None) => u,
+        _ => ch,
+    }
+}
+
+/// Error for a target that could not be resolved
+fn target_not_found(target: &Target) -> XeditError {
+    let msg = match target {
+        Target::StringForward(s)
+        | Target::StringBackward(s)
+        | Target::WordForward(s)
+        | Target::WordBackward(s) => {
+            format!("Target not found: {}", s)
+        }
+        Target::RegexForward(re) | Target::RegexBackward(re) => {
+            format!("Target not found: {}", re.as_str())
+        }
+        Target::Point(name) => format!("Target not found: .{}", name),
+        _ => "Target not found".to_string(),
+    };
+    XeditError::TargetNotFound(msg)
+}
+
+fn column_target_not_found(target: &ColumnTarget) -> XeditError {
+    let msg = match target {
+        ColumnTarget::StringForward(s) | ColumnTarget::StringBackward(s) => {
+            format!("Target not found: {}", s)
+        }
+        _ => "Target not found".to_string(),
+    };
+    XeditError::TargetNotFound(msg)
+}
+
+/// Column (1-based) of the first match of `needle` starting at or after
+/// column `from_col`
+fn first_column_match(
+    opts: &MatchOptions,
+    text: &str,
+    needle: &str,
+    from_col: usize,
+) -> Option<usize> {
+    let from = text
+        .char_indices()
+        .nth(from_col - 1)
+        .map_or(text.len(), |(i, _)| i);
+    let (start, _) = opts.find_from(text, needle, from)?;
+    Some(text[..start].chars().count() + 1)
+}
+
+/// Column (1-based) of the last match of `needle` starting before column
+/// `before_col`
+fn last_column_match(
+    opts: &MatchOptions,
+    text: &str,
+    needle: &str,
+    before_col: usize,
+) -> Option<usize> {
+    let mut found = None;
+    let mut from = 0;
+    while let Some((start, _)) = opts.find_from(text, needle, from) {
+        let col = text[..start].chars().count() + 1;
+        if col >= before_col {
+            break;
+        }
+        found = Some(col);
+        match text[start..].chars().next() {
+            Some(c) => from = start + c.len_utf8(),
+            None => break,
+        }
+    }
+    found
+}
+
+/// Whether `bytes` is newline-terminated lines of exactly `width` bytes
+/// each: a fixed-record text file rather than binary records
+fn has_uniform_lines(bytes: &[u8], width: usize) -> bool {
+    bytes.len().is_multiple_of(width + 1)
+        && bytes
+            .chunks(width + 1)
+            .all(|line| line[width] == b'\n' && !line[..width].contains(&b'\n'))
+}
+
+/// Encode records one byte per character, each followed by `terminator`
+fn encode_records(cp: Codepage, records: &[String], terminator: Option<u8>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for record in records {
+        let encoded = cp.encode(record).map_err(|c| {
+            XeditError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Character {:?} cannot be written in {}", c, cp.name()),
+            ))
+        })?;
+        bytes.extend(encoded);
+        bytes.extend(terminator);
+    }
+    Ok(bytes)
+}
+
+/// Apply CHANGE to one line: replace up to `occurrences` matches of `from`,
+/// starting with match number `first`. Returns the new text and how many
+/// occurrences were replaced, or `None` if nothing changed.
+fn change_occurrences(
+    opts: &MatchOptions,
+    text: &str,
+    from: &str,
+    to: &str,
+    occurrences: usize,
+    first: usize,
+) -> Option<(String, usize)> {
+    let to = opts.decode_hex(to);
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut pos = 0;
+    let mut seen = 0;
+    let mut changed = 0;
+    while changed < occurrences {
+        let Some(m) = opts.find_match(text, from, pos) else {
+            break;
+        };
+        seen += 1;
+        if seen >= first {
+            result.push_str(&text[copied..m.start]);
+            result.push_str(&expand_arbchar(opts.arbchar, &to, text, &m.spans));
+            copied = m.end;
+            changed += 1;
+        }
+        pos = if m.end > m.start {
+            m.end
+        } else {
+            // An empty match must still advance past a character
+            match text[m.end..].chars().next() {
+                Some(c) => m.end + c.len_utf8(),
+                None => break,
+            }
+        };
+    }
+    if changed == 0 {
+        return None;
+    }
+    result.push_str(&text[copied..]);
+    Some((result, changed))
+}
+
+/// Substitute each arbitrary character in a CHANGE replacement string with
+/// the text matched by the corresponding arbitrary character in the search.
+fn expand_arbchar(arbchar: Option<char>, to: &str, text: &str, spans: &[(usize, usize)]) -> String {
+    let Some(arb) = arbchar else {
+        return to.to_string();
+    };
+    let mut spans = spans.iter();
+    let mut out = String::with_capacity(to.len());
+    for c in to.chars() {
+        if c == arb {
+            if let Some(&(start, end)) = spans.next() {
+                out.push_str(&text[start..end]);
+            }
+        } else {
+            out.push(c);
         }
     }
+    out
 }
 
 /// Extract sort key from a line, optionally by column range (1-based).
@@ -1428,6 +3030,7 @@ impl Default for Editor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
 
     fn editor_with_lines(lines: &[&str]) -> Editor {
         let mut ed = Editor::new();
@@ -1545,7 +3148,7 @@ mod tests {
         let mut ed = editor_with_lines(&["apple", "banana", "apricot", "cherry"]);
         ed.execute(&Command::All(Some(Target::StringForward("ap".into()))))
             .unwrap();
-        assert!(ed.all_filter_active());
+        assert!(ed.selection_active());
         assert!(ed.is_line_visible(1)); // apple
         assert!(!ed.is_line_visible(2)); // banana
         assert!(ed.is_line_visible(3)); // apricot
@@ -1557,9 +3160,9 @@ mod tests {
         let mut ed = editor_with_lines(&["apple", "banana"]);
         ed.execute(&Command::All(Some(Target::StringForward("ap".into()))))
             .unwrap();
-        assert!(ed.all_filter_active());
+        assert!(ed.selection_active());
         ed.execute(&Command::All(None)).unwrap();
-        assert!(!ed.all_filter_active());
+        assert!(!ed.selection_active());
     }
 
     #[test]
@@ -1595,6 +3198,8 @@ mod tests {
             to: "hi".into(),
             target: None,
             count: None,
+            occurrences: 1,
+            first: 1,
         })
         .unwrap();
         assert_eq!(ed.buffer().line_text(1), Some("hi world"));
@@ -1634,6 +3239,112 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn redo_after_undo() {
+        let mut ed = editor_with_lines(&["a", "b", "c"]);
+        ed.current_line = 2;
+        ed.execute(&Command::Delete(None)).unwrap();
+        ed.execute(&Command::Undo).unwrap();
+        assert_eq!(texts(&ed), ["a", "b", "c"]);
+
+        let result = ed.execute(&Command::Redo).unwrap();
+        assert_eq!(result.message.as_deref(), Some("Redone"));
+        assert_eq!(texts(&ed), ["a", "c"]);
+        assert!(ed.execute(&Command::Redo).is_err());
+    }
+
+    #[test]
+    fn new_change_discards_redo() {
+        let mut ed = editor_with_lines(&["a", "b"]);
+        ed.current_line = 1;
+        ed.execute(&Command::Delete(None)).unwrap();
+        ed.execute(&Command::Undo).unwrap();
+        change(&mut ed, "c /a/x/").unwrap();
+        assert!(ed.execute(&Command::Redo).is_err());
+        assert_eq!(texts(&ed), ["x", "b"]);
+    }
+
+    #[test]
+    fn set_undo_limits_history() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d"]);
+        ed.execute(&Command::Set(SetCommand::Undo(2))).unwrap();
+        ed.current_line = 1;
+        for _ in 0..3 {
+            ed.execute(&Command::Delete(None)).unwrap();
+        }
+        ed.execute(&Command::Undo).unwrap();
+        ed.execute(&Command::Undo).unwrap();
+        assert!(ed.execute(&Command::Undo).is_err());
+        assert_eq!(texts(&ed), ["b", "c", "d"]);
+
+        let result = ed.execute(&Command::Query("UNDO".into())).unwrap();
+        assert_eq!(result.message.as_deref(), Some("Undo=2 Undofile=OFF"));
+    }
+
+    #[test]
+    fn undo_keeps_record_format() {
+        let mut ed = editor_with_lines(&["abc"]);
+        ed.execute(&Command::Set(SetCommand::Recfm(RecordFormat::Fixed)))
+            .unwrap();
+        ed.execute(&Command::Set(SetCommand::Lrecl(10))).unwrap();
+        change(&mut ed, "c /abc/xyz/").unwrap();
+        ed.execute(&Command::Undo).unwrap();
+        assert_eq!(ed.buffer().recfm(), &RecordFormat::Fixed);
+        assert_eq!(ed.buffer().lrecl(), 10);
+        assert_eq!(texts(&ed), ["abc"]);
+    }
+
+    #[test]
+    fn save_finishes_when_undo_file_fails() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "one\n").unwrap();
+        // A directory where the sidecar should go cannot be written
+        fs::create_dir(dir.path().join(".notes.txt.undo")).unwrap();
+
+        let mut ed = Editor::new();
+        ed.load_file(&path).unwrap();
+        ed.execute(&Command::Set(SetCommand::UndoFile(true)))
+            .unwrap();
+        change(&mut ed, "c /one/ONE/").unwrap();
+        let err = ed.save_file().unwrap_err();
+        assert!(err.to_string().contains("File saved"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ONE\n");
+        assert!(!ed.buffer().is_modified());
+    }
+
+    #[test]
+    fn undo_file_survives_reopen() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "one\ntwo\n").unwrap();
+
+        let mut ed = Editor::new();
+        ed.load_file(&path).unwrap();
+        ed.execute(&Command::Set(SetCommand::UndoFile(true)))
+            .unwrap();
+        change(&mut ed, "c /two/TWO/ *").unwrap();
+        ed.save_file().unwrap();
+        assert!(dir.path().join(".notes.txt.undo").exists());
+
+        let mut reopened = Editor::new();
+        reopened.load_file(&path).unwrap();
+        reopened
+            .execute(&Command::Set(SetCommand::UndoFile(true)))
+            .unwrap();
+        reopened.execute(&Command::Undo).unwrap();
+        assert_eq!(texts(&reopened), ["one", "two"]);
+
+        // A file changed behind our back does not pick up stale history
+        fs::write(&path, "other\n").unwrap();
+        let mut changed = Editor::new();
+        changed.load_file(&path).unwrap();
+        changed
+            .execute(&Command::Set(SetCommand::UndoFile(true)))
+            .unwrap();
+        assert!(changed.execute(&Command::Undo).is_err());
+    }
+
     #[test]
     fn history_skips_special() {
         let mut ed = Editor::new();
@@ -1700,6 +3411,8 @@ mod tests {
             to: "hi".into(),
             target: None,
             count: None,
+            occurrences: 1,
+            first: 1,
         })
         .unwrap();
 
@@ -2028,4 +3741,1299 @@ if ftype.1 = 'RS' then
         assert_eq!(ed.data_stack_pop().as_deref(), Some("alpha"));
         assert_eq!(ed.data_stack_pop().as_deref(), Some("beta"));
     }
+
+    // -- EXTRACT tests --
+
+    #[test]
+    fn extract_requested_items_only() {
+        let mut ed = editor_with_lines(&["alpha", "beta"]);
+        ed.current_line = 2;
+        let items = ed.extract("/curline/size/").unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].0, "CURLINE");
+        assert_eq!(items[0].1, vec!["2", "OFF", "beta"]);
+        assert_eq!(items[1], ("SIZE".to_string(), vec!["2".to_string()]));
+    }
+
+    #[test]
+    fn extract_reflects_live_state() {
+        let mut ed = editor_with_lines(&["alpha", "beta"]);
+        ed.execute(&Command::Down(1)).unwrap();
+        let items = ed.extract("/LINE/").unwrap();
+        assert_eq!(items[0].1, vec!["2"]);
+    }
+
+    #[test]
+    fn extract_unknown_item_errors() {
+        let ed = editor_with_lines(&["alpha"]);
+        assert!(ed.extract("/CURLINE/BOGUS/").is_err());
+        assert!(ed.extract("").is_err());
+    }
+
+    // -- REPLACE / ADD / OVERLAY / COPY / MOVE tests --
+
+    fn texts(ed: &Editor) -> Vec<&str> {
+        ed.buffer().iter().map(|l| l.text()).collect()
+    }
+
+    #[test]
+    fn replace_current_line() {
+        let mut ed = editor_with_lines(&["alpha", "beta"]);
+        ed.current_line = 2;
+        ed.execute(&Command::Replace(Some("gamma".to_string())))
+            .unwrap();
+        assert_eq!(texts(&ed), vec!["alpha", "gamma"]);
+        ed.execute(&Command::Undo).unwrap();
+        assert_eq!(texts(&ed), vec!["alpha", "beta"]);
+    }
+
+    #[test]
+    fn replace_without_text_enters_input() {
+        let mut ed = editor_with_lines(&["alpha", "beta", "gamma"]);
+        ed.current_line = 2;
+        let result = ed.execute(&Command::Replace(None)).unwrap();
+        assert_eq!(result.action, CommandAction::EnterInput);
+        ed.input_line("BETA").unwrap();
+        assert_eq!(texts(&ed), vec!["alpha", "BETA", "gamma"]);
+    }
+
+    #[test]
+    fn replace_at_tof_errors() {
+        let mut ed = editor_with_lines(&["alpha"]);
+        ed.current_line = 0;
+        assert!(ed
+            .execute(&Command::Replace(Some("x".to_string())))
+            .is_err());
+    }
+
+    #[test]
+    fn add_blank_lines() {
+        let mut ed = editor_with_lines(&["alpha", "beta"]);
+        ed.execute(&Command::Add(2)).unwrap();
+        assert_eq!(texts(&ed), vec!["alpha", "", "", "beta"]);
+        assert_eq!(ed.current_line(), 1);
+        match ed.take_cursor_request() {
+            Some(CursorRequest::File { line: 2, col: 1 }) => {}
+            other => panic!("Expected cursor on line 2, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn overlay_blanks_and_underscores() {
+        let mut ed = editor_with_lines(&["abcdef"]);
+        ed.execute(&Command::Overlay("X _ Z    12".to_string()))
+            .unwrap();
+        assert_eq!(texts(&ed), vec!["Xb dZf   12"]);
+    }
+
+    #[test]
+    fn copy_lines_after_target() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d"]);
+        ed.execute(&Command::Copy(Target::Relative(2), Target::Absolute(4)))
+            .unwrap();
+        assert_eq!(texts(&ed), vec!["a", "b", "c", "d", "a", "b"]);
+        assert_eq!(ed.current_line(), 6);
+    }
+
+    #[test]
+    fn copy_to_top_of_file() {
+        let mut ed = editor_with_lines(&["a", "b", "c"]);
+        ed.current_line = 3;
+        ed.execute(&Command::Copy(Target::Relative(1), Target::Absolute(0)))
+            .unwrap();
+        assert_eq!(texts(&ed), vec!["c", "a", "b", "c"]);
+        assert_eq!(ed.current_line(), 1);
+    }
+
+    #[test]
+    fn move_lines_down() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d"]);
+        ed.execute(&Command::Move(
+            Target::StringForward("c".to_string()),
+            Target::Star,
+        ))
+        .unwrap();
+        assert_eq!(texts(&ed), vec!["c", "d", "a", "b"]);
+        assert_eq!(ed.current_line(), 4);
+        ed.execute(&Command::Undo).unwrap();
+        assert_eq!(texts(&ed), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn move_lines_up() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d"]);
+        ed.current_line = 3;
+        ed.execute(&Command::Move(Target::Relative(2), Target::Absolute(1)))
+            .unwrap();
+        assert_eq!(texts(&ed), vec!["a", "c", "d", "b"]);
+        assert_eq!(ed.current_line(), 3);
+    }
+
+    #[test]
+    fn move_into_itself_errors() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d"]);
+        let result = ed.execute(&Command::Move(Target::Relative(3), Target::Relative(1)));
+        assert!(result.is_err());
+        assert_eq!(texts(&ed), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn delete_up_to_string_target() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d"]);
+        ed.execute(&Command::Delete(Some(Target::StringForward(
+            "c".to_string(),
+        ))))
+        .unwrap();
+        assert_eq!(texts(&ed), vec!["c", "d"]);
+    }
+
+    // -- ZONE tests --
+
+    #[test]
+    fn locate_only_within_zone() {
+        let mut ed = editor_with_lines(&["      * comment", "       MOVE A", "MOVE B"]);
+        ed.execute(&Command::Set(SetCommand::Zone(8, 72))).unwrap();
+        ed.current_line = 0;
+        ed.execute(&Command::Locate(Target::StringForward("MOVE".to_string())))
+            .unwrap();
+        assert_eq!(ed.current_line(), 2);
+        let result = ed.execute(&Command::Locate(Target::StringForward("MOVE".to_string())));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn change_only_within_zone() {
+        let mut ed = editor_with_lines(&["AAAA AAAA"]);
+        ed.execute(&Command::Set(SetCommand::Zone(3, 6))).unwrap();
+        ed.execute(&Command::Change {
+            from: "A".to_string(),
+            to: "b".to_string(),
+            target: None,
+            count: None,
+            occurrences: 1,
+            first: 1,
+        })
+        .unwrap();
+        assert_eq!(ed.buffer().line_text(1), Some("AAbA AAAA"));
+    }
+
+    #[test]
+    fn all_matches_within_zone() {
+        let mut ed = editor_with_lines(&["XX..", "..XX", "XXXX"]);
+        ed.execute(&Command::Set(SetCommand::Zone(1, 2))).unwrap();
+        ed.execute(&Command::All(Some(Target::StringForward("XX".to_string()))))
+            .unwrap();
+        assert!(ed.is_line_visible(1));
+        assert!(!ed.is_line_visible(2));
+        assert!(ed.is_line_visible(3));
+    }
+
+    #[test]
+    fn sort_defaults_to_zone_columns() {
+        let mut ed = editor_with_lines(&["a 3", "b 1", "c 2"]);
+        ed.execute(&Command::Set(SetCommand::Zone(3, 3))).unwrap();
+        ed.execute(&Command::Sort {
+            target: None,
+            ascending: true,
+            col_start: None,
+            col_end: None,
+        })
+        .unwrap();
+        assert_eq!(ed.buffer().line_text(1), Some("b 1"));
+        assert_eq!(ed.buffer().line_text(3), Some("a 3"));
+    }
+
+    #[test]
+    fn query_and_extract_zone() {
+        let mut ed = editor_with_lines(&["x"]);
+        ed.execute(&Command::Set(SetCommand::Zone(10, usize::MAX)))
+            .unwrap();
+        let result = ed.execute(&Command::Query("zone".to_string())).unwrap();
+        assert_eq!(result.message.as_deref(), Some("Zone=10 *"));
+        let items = ed.extract("/ZONE/").unwrap();
+        assert_eq!(items[0].1, vec!["10", "*"]);
+    }
+
+    // -- Regex / word / not target tests --
+
+    #[test]
+    fn locate_regex_target() {
+        let mut ed = editor_with_lines(&["alpha", "item 42", "beta"]);
+        ed.execute(&Command::Locate(Target::parse("RE/\\d+$/").unwrap()))
+            .unwrap();
+        assert_eq!(ed.current_line(), 2);
+    }
+
+    #[test]
+    fn all_with_not_target() {
+        let mut ed = editor_with_lines(&["* comment", "code", "* more", "more code"]);
+        ed.execute(&Command::All(Some(Target::parse("~/*/").unwrap())))
+            .unwrap();
+        assert!(!ed.is_line_visible(1));
+        assert!(ed.is_line_visible(2));
+        assert!(!ed.is_line_visible(3));
+        assert!(ed.is_line_visible(4));
+    }
+
+    #[test]
+    fn delete_up_to_word_target() {
+        let mut ed = editor_with_lines(&["a", "category", "b", "cat c", "d"]);
+        ed.execute(&Command::Delete(Some(Target::parse("WORD/cat/").unwrap())))
+            .unwrap();
+        assert_eq!(texts(&ed), vec!["cat c", "d"]);
+    }
+
+    #[test]
+    fn change_within_regex_target_range() {
+        let mut ed = editor_with_lines(&["x1", "x2", "END", "x3"]);
+        ed.execute(&Command::Change {
+            from: "x".into(),
+            to: "y".into(),
+            target: Some(Target::parse("RE/^E.D$/").unwrap()),
+            count: Some(10),
+            occurrences: 1,
+            first: 1,
+        })
+        .unwrap();
+        assert_eq!(texts(&ed), vec!["y1", "y2", "END", "x3"]);
+    }
+
+    // -- ARBCHAR / CHANGE occurrence tests --
+
+    fn change(ed: &mut Editor, cmd: &str) -> Result<CommandResult> {
+        ed.execute(&parse_command(cmd).unwrap())
+    }
+
+    #[test]
+    fn change_all_occurrences_from_second() {
+        let mut ed = editor_with_lines(&["x x x x", "x", "x x"]);
+        change(&mut ed, "CHANGE /x/y/ * * 2").unwrap();
+        assert_eq!(texts(&ed), vec!["x y y y", "x", "x y"]);
+        assert_eq!(
+            ed.last_message(),
+            Some("4 occurrence(s) changed on 2 line(s)")
+        );
+    }
+
+    #[test]
+    fn change_limited_occurrences_per_line() {
+        let mut ed = editor_with_lines(&["aaaa", "aa"]);
+        change(&mut ed, "CHANGE /a/b/ * 2").unwrap();
+        assert_eq!(texts(&ed), vec!["bbaa", "bb"]);
+    }
+
+    #[test]
+    fn change_with_arbchar_carries_span() {
+        let mut ed = editor_with_lines(&["call foo(1)", "call bar(2)"]);
+        change(&mut ed, "SET ARBCHAR ON").unwrap();
+        change(&mut ed, "CHANGE /call $(/invoke $[/ *").unwrap();
+        assert_eq!(texts(&ed), vec!["invoke foo[1)", "invoke bar[2)"]);
+    }
+
+    #[test]
+    fn arbchar_off_is_literal() {
+        let mut ed = editor_with_lines(&["a$b", "axb"]);
+        change(&mut ed, "CHANGE /a$b/z/ *").unwrap();
+        assert_eq!(texts(&ed), vec!["z", "axb"]);
+        change(&mut ed, "SET ARBCHAR ON %").unwrap();
+        ed.current_line = 1;
+        change(&mut ed, "CHANGE /a%b/[%]/ *").unwrap();
+        assert_eq!(texts(&ed), vec!["z", "[x]"]);
+    }
+
+    #[test]
+    fn arbchar_in_locate_and_extract() {
+        let mut ed = editor_with_lines(&["alpha", "begin x end", "omega"]);
+        change(&mut ed, "SET ARBCHAR ON").unwrap();
+        change(&mut ed, "LOCATE /begin$end/").unwrap();
+        assert_eq!(ed.current_line(), 2);
+        let items = ed.extract("/ARBCHAR/").unwrap();
+        assert_eq!(items[0].1, vec!["ON", "$"]);
+        let result = change(&mut ed, "QUERY ARBCHAR").unwrap();
+        assert_eq!(result.message.as_deref(), Some("Arbchar=ON $"));
+    }
+
+    // -- RECFM / LRECL tests --
+
+    fn fixed_options(lrecl: usize) -> LoadOptions {
+        LoadOptions {
+            recfm: Some(RecordFormat::Fixed),
+            lrecl: Some(lrecl),
+            ..LoadOptions::default()
+        }
+    }
+
+    #[test]
+    fn load_binary_fixed_records() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, b"AAAA  BB  CC\xC1 ").unwrap();
+
+        let mut ed = Editor::new();
+        ed.load_file_with(&path, &fixed_options(4)).unwrap();
+        assert_eq!(texts(&ed), vec!["AAAA", "  BB", "  CC", "\u{C1}"]);
+        assert_eq!(ed.buffer().recfm(), &RecordFormat::Fixed);
+        assert_eq!(ed.trunc(), 4);
+        assert_eq!(
+            ed.message(),
+            Some("Last record is 2 bytes, short of LRECL 4; it is padded with blanks")
+        );
+
+        ed.current_line = 4;
+        ed.execute(&Command::Replace(Some("DD".into()))).unwrap();
+        ed.save_file().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"AAAA  BB  CCDD  ");
+    }
+
+    #[test]
+    fn fixed_layout_comes_from_record_lengths() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("deck.txt");
+
+        // Lines of exactly LRECL bytes are a fixed-record text file
+        fs::write(&path, "ab  \ncd  \n").unwrap();
+        let mut ed = Editor::new();
+        ed.load_file_with(&path, &fixed_options(4)).unwrap();
+        assert_eq!(texts(&ed), vec!["ab  ", "cd  "]);
+        ed.save_file().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"ab  \ncd  \n");
+
+        // Binary records that happen to contain a newline byte stay records
+        fs::write(&path, b"a\nbcdefg\n").unwrap();
+        let mut ed = Editor::new();
+        ed.load_file_with(&path, &fixed_options(4)).unwrap();
+        assert_eq!(texts(&ed), vec!["a\nbc", "defg", "\n"]);
+        assert!(ed.message().is_some());
+    }
+
+    #[test]
+    fn save_fixed_text_pads_and_truncates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("deck.txt");
+        fs::write(&path, "short\nmuch too long\n").unwrap();
+
+        let mut ed = Editor::new();
+        ed.load_file(&path).unwrap();
+        ed.execute(&parse_command("SET RECFM F").unwrap()).unwrap();
+        ed.execute(&parse_command("SET LRECL 8").unwrap()).unwrap();
+        ed.save_file().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "short   \nmuch too\n");
+    }
+
+    #[test]
+    fn typing_past_lrecl_is_rejected() {
+        let mut ed = editor_with_lines(&["abcd"]);
+        ed.execute(&Command::Set(SetCommand::Recfm(RecordFormat::Fixed)))
+            .unwrap();
+        ed.execute(&Command::Set(SetCommand::Lrecl(5))).unwrap();
+        assert_eq!(ed.trunc(), 5);
+
+        ed.overtype_char(1, 4, 'e').unwrap();
+        assert!(ed.overtype_char(1, 5, 'f').is_err());
+        assert!(ed.insert_char(1, 0, 'x').is_err());
+        assert!(ed.input_line("123456").is_err());
+        assert!(ed.execute(&Command::Input(Some("123456".into()))).is_err());
+        assert_eq!(texts(&ed), vec!["abcde"]);
+    }
+
+    #[test]
+    fn variable_records_have_no_length_limit() {
+        let mut ed = editor_with_lines(&["abc"]);
+        ed.execute(&Command::Set(SetCommand::Lrecl(3))).unwrap();
+        ed.insert_char(1, 3, 'd').unwrap();
+        assert_eq!(texts(&ed), vec!["abcd"]);
+    }
+
+    // -- Codepage tests --
+
+    #[test]
+    fn load_and_save_ebcdic_fixed_records() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("deck.data");
+        // "HELLO   " and "[1]     " in CP1047, LRECL 8
+        let data = [
+            0xC8, 0xC5, 0xD3, 0xD3, 0xD6, 0x40, 0x40, 0x40, 0xAD, 0xF1, 0xBD, 0x40, 0x40, 0x40,
+            0x40, 0x40,
+        ];
+        fs::write(&path, data).unwrap();
+
+        let mut ed = Editor::new();
+        let opts = LoadOptions {
+            codepage: Codepage::Cp1047,
+            ..fixed_options(8)
+        };
+        ed.load_file_with(&path, &opts).unwrap();
+        assert_eq!(texts(&ed), vec!["HELLO", "[1]"]);
+        assert_eq!(ed.extract("/CODEPAGE/").unwrap()[0].1, vec!["CP1047"]);
+
+        ed.save_file().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+
+    #[test]
+    fn ebcdic_variable_lines_end_in_nl() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.text");
+        fs::write(&path, [0xC1, 0x15, 0xC2, 0xC3, 0x15]).unwrap();
+
+        let mut ed = Editor::new();
+        let opts = LoadOptions {
+            codepage: Codepage::Cp037,
+            ..LoadOptions::default()
+        };
+        ed.load_file_with(&path, &opts).unwrap();
+        assert_eq!(texts(&ed), vec!["A", "BC"]);
+        ed.save_file().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), [0xC1, 0x15, 0xC2, 0xC3, 0x15]);
+    }
+
+    #[test]
+    fn set_codepage_converts_on_save() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("ascii.txt");
+        fs::write(&path, "AB\n").unwrap();
+
+        let mut ed = Editor::new();
+        ed.load_file(&path).unwrap();
+        ed.execute(&parse_command("SET CODEPAGE CP500").unwrap())
+            .unwrap();
+        let result = ed
+            .execute(&parse_command("QUERY CODEPAGE").unwrap())
+            .unwrap();
+        assert_eq!(result.message.as_deref(), Some("Codepage=CP500"));
+        ed.save_file().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), [0xC1, 0xC2, 0x15]);
+
+        ed.current_line = 1;
+        ed.execute(&Command::Replace(Some("\u{20AC}".into())))
+            .unwrap();
+        assert!(ed.save_file().is_err());
+    }
+
+    // -- Hex tests --
+
+    #[test]
+    fn overtype_hex_digits() {
+        let mut ed = editor_with_lines(&["AB"]);
+        ed.overtype_hex_digit(1, 0, false, '2').unwrap();
+        assert_eq!(texts(&ed), vec!["BB"]);
+        ed.overtype_hex_digit(1, 3, true, '5').unwrap();
+        assert_eq!(texts(&ed), vec!["BB P"]);
+        assert!(ed.overtype_hex_digit(1, 0, true, 'g').is_err());
+    }
+
+    #[test]
+    fn overtype_hex_digits_in_ebcdic() {
+        let mut ed = editor_with_lines(&["A"]);
+        ed.execute(&Command::Set(SetCommand::Codepage(Codepage::Cp037)))
+            .unwrap();
+        // A is X'C1' in CP037; X'C2' is B
+        ed.overtype_hex_digit(1, 0, false, '2').unwrap();
+        assert_eq!(texts(&ed), vec!["B"]);
+    }
+
+    #[test]
+    fn change_hex_strings_with_hex_on() {
+        let mut ed = editor_with_lines(&["ABA", "X'C1'"]);
+        ed.execute(&Command::Set(SetCommand::Codepage(Codepage::Cp037)))
+            .unwrap();
+        change(&mut ed, "CHANGE X'C1'X'C2' * *").unwrap();
+        assert_eq!(texts(&ed), vec!["ABA", "X'C2'"]);
+
+        ed.current_line = 1;
+        change(&mut ed, "SET HEX ON").unwrap();
+        change(&mut ed, "CHANGE X'C1'X'C3' * *").unwrap();
+        assert_eq!(texts(&ed), vec!["CBC", "X'C2'"]);
+        change(&mut ed, "TOP").unwrap();
+        change(&mut ed, "LOCATE /X'C2C3'/").unwrap();
+        assert_eq!(ed.current_line(), 1);
+    }
+
+    #[test]
+    fn locate_wraps_with_set_wrap() {
+        let mut ed = editor_with_lines(&["alpha", "beta", "gamma"]);
+        ed.current_line = 2;
+        assert!(change(&mut ed, "/alpha/").is_err());
+
+        ed.execute(&Command::Set(SetCommand::Wrap(true))).unwrap();
+        let result = change(&mut ed, "/alpha/").unwrap();
+        assert_eq!(result.message.as_deref(), Some("Wrapped..."));
+        assert_eq!(ed.current_line(), 1);
+
+        // Backward searches wrap from the bottom of the file
+        let result = change(&mut ed, "-/gamma/").unwrap();
+        assert_eq!(result.message.as_deref(), Some("Wrapped..."));
+        assert_eq!(ed.current_line(), 3);
+
+        // A match before the end of file does not wrap
+        ed.current_line = 1;
+        let result = change(&mut ed, "/beta/").unwrap();
+        assert_eq!(result.message, None);
+    }
+
+    #[test]
+    fn change_star_wraps_with_set_wrap() {
+        let mut ed = editor_with_lines(&["x1", "x2", "x3", "x4"]);
+        ed.current_line = 3;
+        change(&mut ed, "c /x/y/ *").unwrap();
+        assert_eq!(texts(&ed), ["x1", "x2", "y3", "y4"]);
+
+        ed.execute(&Command::Set(SetCommand::Wrap(true))).unwrap();
+        let result = change(&mut ed, "c /x/z/ *").unwrap();
+        assert_eq!(texts(&ed), ["z1", "z2", "y3", "y4"]);
+        assert_eq!(
+            result.message.as_deref(),
+            Some("Wrapped... 2 occurrence(s) changed on 2 line(s)")
+        );
+    }
+
+    #[test]
+    fn change_next_match_wraps_with_set_wrap() {
+        let mut ed = editor_with_lines(&["abc", "def", "ghi"]);
+        ed.current_line = 2;
+        ed.execute(&Command::Set(SetCommand::Wrap(true))).unwrap();
+        change(&mut ed, "c /abc/ABC/").unwrap();
+        assert_eq!(texts(&ed), ["ABC", "def", "ghi"]);
+    }
+
+    #[test]
+    fn case_upper_applies_to_typed_data() {
+        let mut ed = editor_with_lines(&["keep me"]);
+        change(&mut ed, "SET CASE U").unwrap();
+        assert!(ed.case_upper());
+        assert!(!ed.case_respect());
+
+        ed.current_line = 1;
+        ed.input_line("say hello").unwrap();
+        change(&mut ed, "input push 'x'").unwrap();
+        ed.overtype_char(1, 0, 'k').unwrap();
+        ed.insert_char(1, 0, 'x').unwrap();
+        assert_eq!(texts(&ed), ["XKeep me", "SAY HELLO", "PUSH 'X'"]);
+
+        // Prefix-inserted lines take typing through the same paths
+        ed.execute_prefix(3, &PrefixCommand::Insert(1)).unwrap();
+        ed.overtype_char(4, 0, 'q').unwrap();
+        assert_eq!(ed.buffer().line_text(4), Some("Q"));
+    }
+
+    #[test]
+    fn case_upper_applies_to_change_replacement() {
+        let mut ed = editor_with_lines(&["call sub"]);
+        ed.current_line = 1;
+        change(&mut ed, "SET CASE UPPER RESPECT").unwrap();
+        change(&mut ed, "c /sub/routine/").unwrap();
+        assert_eq!(texts(&ed), ["call ROUTINE"]);
+        assert!(ed.case_respect());
+
+        let result = change(&mut ed, "QUERY CASE").unwrap();
+        assert_eq!(result.message.as_deref(), Some("Case=UPPER RESPECT"));
+        change(&mut ed, "SET CASE M").unwrap();
+        change(&mut ed, "r mixed Case").unwrap();
+        assert_eq!(texts(&ed), ["mixed Case"]);
+    }
+
+    #[cfg(feature = "rexx")]
+    #[test]
+    fn profile_sets_case_per_filetype() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("profile.xedit"),
+            "/* PROFILE */\n'EXTRACT /FTYPE/'\nif ftype.1 = 'EXEC' then 'SET CASE U I'\n",
+        )
+        .unwrap();
+        let exec = dir.path().join("hello.exec");
+        let notes = dir.path().join("hello.notes");
+        fs::write(&exec, "/* */\n").unwrap();
+        fs::write(&notes, "text\n").unwrap();
+
+        for (path, upper) in [(&exec, true), (&notes, false)] {
+            let mut ed = Editor::new();
+            ed.set_macro_path(vec![dir.path().to_path_buf()]);
+            ed.load_file(path).unwrap();
+            ed.run_profile();
+            assert_eq!(ed.case_upper(), upper, "{}", path.display());
+        }
+    }
+
+    // -- TRUNC / SPILL tests --
+
+    #[test]
+    fn change_past_trunc_is_truncated() {
+        let mut ed = editor_with_lines(&["abc def", "abc"]);
+        ed.execute(&Command::Set(SetCommand::Trunc(8))).unwrap();
+        let result = change(&mut ed, "c /abc/abcdef/ *").unwrap();
+        assert_eq!(texts(&ed), ["abcdef d", "abcdef"]);
+        assert_eq!(result.rc, 3);
+        assert_eq!(
+            result.message.as_deref(),
+            Some("2 occurrence(s) changed on 2 line(s); Truncated")
+        );
+    }
+
+    #[test]
+    fn columns_past_trunc_stay_in_place() {
+        // Sequence numbers in columns 9-12 survive changes to the data
+        let mut ed = editor_with_lines(&["A B     0010"]);
+        ed.execute(&Command::Set(SetCommand::Trunc(8))).unwrap();
+        change(&mut ed, "c /B/BBB/").unwrap();
+        assert_eq!(texts(&ed), ["A BBB   0010"]);
+        change(&mut ed, "c /BBB//").unwrap();
+        assert_eq!(texts(&ed), ["A       0010"]);
+        ed.delete_char(1, 0);
+        assert_eq!(texts(&ed), ["        0010"]);
+    }
+
+    #[test]
+    fn typing_past_trunc_is_truncated() {
+        let mut ed = editor_with_lines(&["abcd"]);
+        ed.execute(&Command::Set(SetCommand::Trunc(5))).unwrap();
+        ed.overtype_char(1, 5, 'x').unwrap();
+        assert_eq!(ed.message(), Some("Truncated"));
+        ed.insert_char(1, 0, '1').unwrap();
+        assert_eq!(texts(&ed), ["1abcd"]);
+        ed.insert_char(1, 0, '2').unwrap();
+        assert_eq!(texts(&ed), ["21abc"]);
+        assert_eq!(ed.message(), Some("Truncated"));
+
+        ed.input_line("123456789").unwrap();
+        assert_eq!(texts(&ed), ["21abc", "12345"]);
+        let result = change(&mut ed, "input abcdefg").unwrap();
+        assert_eq!(result.rc, 3);
+        let result = change(&mut ed, "replace xy").unwrap();
+        assert_eq!(result.rc, 0);
+        assert_eq!(texts(&ed), ["21abc", "12345", "xy"]);
+    }
+
+    #[test]
+    fn prefix_shift_right_truncates() {
+        let mut ed = editor_with_lines(&["abcd"]);
+        ed.execute(&Command::Set(SetCommand::Trunc(5))).unwrap();
+        let result = ed.execute_prefix(1, &PrefixCommand::ShiftRight(2)).unwrap();
+        assert_eq!(texts(&ed), ["  abc"]);
+        assert_eq!(result.rc, 3);
+    }
+
+    #[test]
+    fn shift_left_stays_within_trunc() {
+        let mut ed = editor_with_lines(&["  äbc  xyz", "  é"]);
+        ed.execute(&Command::Set(SetCommand::Trunc(5))).unwrap();
+        ed.execute_prefix(1, &PrefixCommand::ShiftLeft(2)).unwrap();
+        assert_eq!(texts(&ed), ["äbc    xyz", "  é"]);
+        // Multibyte text is shifted by characters
+        ed.execute_prefix(2, &PrefixCommand::ShiftLeft(2)).unwrap();
+        assert_eq!(texts(&ed)[1], "é");
+        // A non-blank inside the shift is left alone
+        ed.execute_prefix(2, &PrefixCommand::ShiftLeft(1)).unwrap();
+        assert_eq!(texts(&ed)[1], "é");
+    }
+
+    #[test]
+    fn spill_on_moves_text_to_new_lines() {
+        let mut ed = editor_with_lines(&["one"]);
+        ed.execute(&Command::Set(SetCommand::Trunc(4))).unwrap();
+        change(&mut ed, "SET SPILL ON").unwrap();
+        let result = change(&mut ed, "input abcdefghij").unwrap();
+        assert_eq!(result.rc, 0);
+        assert_eq!(texts(&ed), ["one", "abcd", "efgh", "ij"]);
+        assert_eq!(ed.current_line(), 4);
+    }
+
+    #[test]
+    fn spill_word_keeps_words_whole() {
+        let mut ed = editor_with_lines(&["the quick fox", "next"]);
+        ed.execute(&Command::Set(SetCommand::Trunc(13))).unwrap();
+        change(&mut ed, "SET SPILL WORD").unwrap();
+        change(&mut ed, "c /quick/very quick/ *").unwrap();
+        assert_eq!(texts(&ed), ["the very", "quick fox", "next"]);
+        let result = change(&mut ed, "QUERY SPILL").unwrap();
+        assert_eq!(result.message.as_deref(), Some("Spill=WORD"));
+    }
+
+    // -- BROWSE tests --
+
+    fn browse_file(dir: &tempfile::TempDir, lines: usize) -> Editor {
+        let path = dir.path().join("big.log");
+        let content: String = (1..=lines).map(|i| format!("record {}\n", i)).collect();
+        fs::write(&path, content).unwrap();
+        let mut ed = Editor::new();
+        let opts = LoadOptions {
+            browse: true,
+            ..LoadOptions::default()
+        };
+        ed.load_file_with(&path, &opts).unwrap();
+        ed
+    }
+
+    #[test]
+    fn browse_navigates_without_loading_the_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut ed = browse_file(&dir, 5000);
+        assert!(ed.is_readonly());
+        assert!(ed.buffer().is_indexed());
+        assert_eq!(ed.buffer().len(), 5000);
+        assert_eq!(ed.filetype(), "LOG");
+
+        change(&mut ed, ":4000").unwrap();
+        assert_eq!(ed.current_line_text(), "record 4000");
+        change(&mut ed, "LOCATE /record 4999/").unwrap();
+        assert_eq!(ed.current_line(), 4999);
+        change(&mut ed, "-/record 12/").unwrap();
+        assert_eq!(ed.current_line(), 1299);
+
+        ed.set_page_size(100);
+        change(&mut ed, "FORWARD 2").unwrap();
+        assert_eq!(ed.current_line(), 1499);
+        change(&mut ed, "BACKWARD").unwrap();
+        assert_eq!(ed.current_line(), 1399);
+
+        let result = change(&mut ed, "ALL /record 42/").unwrap();
+        assert_eq!(result.message.as_deref(), Some("111 line(s) displayed"));
+        assert!(ed.buffer().is_indexed());
+        assert!(ed.is_line_visible(4200));
+        assert!(!ed.is_line_visible(4300));
+        assert!(!ed.buffer().is_modified());
+    }
+
+    #[test]
+    fn browse_reports_unreadable_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut ed = browse_file(&dir, 3000);
+        fs::write(dir.path().join("big.log"), "record 1\n").unwrap();
+        let err = change(&mut ed, "LOCATE /record 2999/").unwrap_err();
+        assert!(matches!(err, XeditError::Io(_)));
+        assert!(ed.message().unwrap().starts_with("I/O error"));
+    }
+
+    #[test]
+    fn browse_refuses_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut ed = browse_file(&dir, 3);
+        for cmd in [
+            "c /record/rec/ *",
+            "DELETE",
+            "INPUT x",
+            "SAVE",
+            "FILE",
+            "SORT *",
+        ] {
+            let err = change(&mut ed, cmd).unwrap_err();
+            assert!(matches!(err, XeditError::ReadOnly(_)), "{}", cmd);
+        }
+        assert_eq!(ed.message(), Some("SORT not allowed: file is read-only"));
+        assert!(ed.overtype_char(1, 0, 'x').is_err());
+        assert!(ed.insert_char(1, 0, 'x').is_err());
+        assert!(ed.input_line("x").is_err());
+        ed.delete_char(1, 0);
+        assert!(ed.execute_prefix(1, &PrefixCommand::Delete).is_err());
+        ed.execute_prefix(2, &PrefixCommand::SetCurrent).unwrap();
+        assert_eq!(ed.current_line(), 2);
+        assert_eq!(texts(&ed), vec!["record 1", "record 2", "record 3"]);
+        assert!(ed.buffer().is_indexed());
+    }
+
+    #[test]
+    fn browse_fixed_records() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("deck.data");
+        // "HELLO   " and "[1]     " in CP1047, LRECL 8
+        fs::write(
+            &path,
+            [
+                0xC8, 0xC5, 0xD3, 0xD3, 0xD6, 0x40, 0x40, 0x40, 0xAD, 0xF1, 0xBD, 0x40, 0x40, 0x40,
+                0x40, 0x40,
+            ],
+        )
+        .unwrap();
+        let mut ed = Editor::new();
+        let opts = LoadOptions {
+            codepage: Codepage::Cp1047,
+            browse: true,
+            ..fixed_options(8)
+        };
+        ed.load_file_with(&path, &opts).unwrap();
+        assert_eq!(texts(&ed), vec!["HELLO", "[1]"]);
+        assert_eq!(ed.buffer().recfm(), &RecordFormat::Fixed);
+        assert_eq!(ed.trunc(), 8);
+    }
+
+    // -- SET POINT tests --
+
+    #[test]
+    fn point_names_follow_edits() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d", "e"]);
+        ed.current_line = 4;
+        change(&mut ed, "SET POINT .end").unwrap();
+        ed.current_line = 2;
+        change(&mut ed, "SET POINT .Two").unwrap();
+        ed.current_line = 1;
+        change(&mut ed, "INPUT new").unwrap();
+        assert_eq!(ed.buffer().point("END"), Some(5));
+
+        change(&mut ed, "LOCATE .two").unwrap();
+        assert_eq!(ed.current_line_text(), "b");
+        change(&mut ed, "DELETE .end").unwrap();
+        assert_eq!(texts(&ed), vec!["a", "new", "d", "e"]);
+        assert_eq!(ed.current_line_text(), "d");
+        assert_eq!(ed.buffer().point("TWO"), None);
+        assert_eq!(ed.buffer().point("END"), Some(3));
+
+        let err = change(&mut ed, ".two").unwrap_err();
+        assert_eq!(err.to_string(), "Target not found: .TWO");
+        change(&mut ed, "TOP").unwrap();
+        assert!(change(&mut ed, "SET POINT .x").is_err());
+    }
+
+    #[test]
+    fn point_as_copy_destination_and_undo() {
+        let mut ed = editor_with_lines(&["one", "two", "three"]);
+        ed.current_line = 3;
+        change(&mut ed, "SET POINT .here").unwrap();
+        ed.current_line = 1;
+        change(&mut ed, "COPY 1 .here").unwrap();
+        assert_eq!(texts(&ed), vec!["one", "two", "three", "one"]);
+
+        ed.current_line = 1;
+        change(&mut ed, "INPUT zero").unwrap();
+        assert_eq!(ed.buffer().point("HERE"), Some(4));
+        change(&mut ed, "UNDO").unwrap();
+        assert_eq!(ed.buffer().point("HERE"), Some(3));
+
+        ed.current_line = 2;
+        change(&mut ed, "DELETE 2").unwrap();
+        assert_eq!(ed.buffer().point("HERE"), None);
+        change(&mut ed, "UNDO").unwrap();
+        assert_eq!(ed.buffer().point("HERE"), Some(3));
+    }
+
+    #[test]
+    fn sort_moves_points_with_their_lines() {
+        let mut ed = editor_with_lines(&["c", "a", "b"]);
+        change(&mut ed, "SET POINT .c").unwrap();
+        ed.current_line = 3;
+        change(&mut ed, "SET POINT .b").unwrap();
+        ed.current_line = 1;
+        change(&mut ed, "SORT *").unwrap();
+        assert_eq!(texts(&ed), vec!["a", "b", "c"]);
+        assert_eq!(ed.buffer().point("C"), Some(3));
+        assert_eq!(ed.buffer().point("B"), Some(2));
+
+        change(&mut ed, "UNDO").unwrap();
+        assert_eq!(texts(&ed), vec!["c", "a", "b"]);
+        assert_eq!(ed.buffer().point("C"), Some(1));
+        assert_eq!(ed.buffer().point("B"), Some(3));
+    }
+
+    #[test]
+    fn query_and_extract_point() {
+        let mut ed = editor_with_lines(&["a", "b", "c"]);
+        ed.current_line = 2;
+        change(&mut ed, "SET POINT .b2").unwrap();
+        change(&mut ed, "SET POINT .alpha").unwrap();
+        ed.current_line = 3;
+        change(&mut ed, "SET POINT .c").unwrap();
+
+        let msg = |ed: &mut Editor, cmd| change(ed, cmd).unwrap().message.unwrap();
+        assert_eq!(msg(&mut ed, "QUERY POINT"), "Point=.C");
+        assert_eq!(msg(&mut ed, "QUERY POINT *"), "Point=2 .ALPHA 2 .B2 3 .C");
+        ed.current_line = 2;
+        assert_eq!(
+            ed.extract("/POINT/").unwrap(),
+            vec![("POINT".to_string(), vec!["2 .ALPHA .B2".to_string()])]
+        );
+        let all = ed.extract("/POINT */SIZE/").unwrap();
+        assert_eq!(all[0].1, vec!["2 .ALPHA", "2 .B2", "3 .C"]);
+        assert_eq!(all[1].0, "SIZE");
+
+        change(&mut ed, "SET POINT .c OFF").unwrap();
+        assert!(change(&mut ed, "SET POINT .c OFF").is_err());
+        ed.current_line = 1;
+        assert_eq!(msg(&mut ed, "QUERY POINT"), "No names on line 1");
+        assert!(ed.extract("/POINT/").unwrap()[0].1.is_empty());
+    }
+
+    #[test]
+    fn prefix_point_names_a_line() {
+        let mut ed = editor_with_lines(&["a", "b", "c"]);
+        ed.execute_prefix(3, &PrefixCommand::parse(".last").unwrap())
+            .unwrap();
+        ed.execute_prefix(1, &PrefixCommand::Insert(1)).unwrap();
+        assert_eq!(ed.buffer().point("LAST"), Some(4));
+        assert!(ed
+            .execute_prefix(0, &PrefixCommand::Point("TOF".into()))
+            .is_err());
+    }
+
+    #[test]
+    fn clocate_moves_the_column_pointer() {
+        let mut ed = editor_with_lines(&["abc abc", "xyz abc"]);
+        change(&mut ed, "CLOCATE :5").unwrap();
+        assert_eq!(ed.current_col(), 5);
+        change(&mut ed, "CLOCATE -2").unwrap();
+        assert_eq!(ed.current_col(), 3);
+        assert!(change(&mut ed, "CLOCATE -3").is_err());
+        assert_eq!(ed.current_col(), 3);
+        change(&mut ed, "CLOCATE *").unwrap();
+        assert_eq!(ed.current_col(), 8);
+
+        // A string search starts after the pointer and goes on to later lines
+        change(&mut ed, "CLOCATE :1").unwrap();
+        change(&mut ed, "CLOCATE /abc/").unwrap();
+        assert_eq!((ed.current_line(), ed.current_col()), (1, 5));
+        change(&mut ed, "CLOCATE /abc/").unwrap();
+        assert_eq!((ed.current_line(), ed.current_col()), (2, 5));
+        change(&mut ed, "CLOCATE -/abc/").unwrap();
+        assert_eq!((ed.current_line(), ed.current_col()), (1, 5));
+        assert!(matches!(
+            change(&mut ed, "CLOCATE /nope/"),
+            Err(XeditError::TargetNotFound(_))
+        ));
+        assert_eq!((ed.current_line(), ed.current_col()), (1, 5));
+    }
+
+    #[test]
+    fn cdelete_to_a_column_target() {
+        let mut ed = editor_with_lines(&["abcdefgh"]);
+        change(&mut ed, "CLOCATE :3").unwrap();
+        change(&mut ed, "CDELETE").unwrap();
+        assert_eq!(texts(&ed), vec!["abdefgh"]);
+        change(&mut ed, "CDELETE /g/").unwrap();
+        assert_eq!(texts(&ed), vec!["abgh"]);
+        change(&mut ed, "CDELETE -2").unwrap();
+        assert_eq!(texts(&ed), vec!["gh"]);
+        assert_eq!(ed.current_col(), 1);
+        change(&mut ed, "CDELETE *").unwrap();
+        assert_eq!(texts(&ed), vec![""]);
+        ed.execute(&Command::Undo).unwrap();
+        assert_eq!(texts(&ed), vec!["gh"]);
+    }
+
+    #[test]
+    fn cinsert_creplace_coverlay_at_the_pointer() {
+        let mut ed = editor_with_lines(&["abcdef"]);
+        change(&mut ed, "CLOCATE :3").unwrap();
+        change(&mut ed, "CINSERT XY").unwrap();
+        assert_eq!(texts(&ed), vec!["abXYcdef"]);
+        change(&mut ed, "CREPLACE 12345678").unwrap();
+        assert_eq!(texts(&ed), vec!["ab12345678"]);
+        change(&mut ed, "COVERLAY _ Z").unwrap();
+        assert_eq!(texts(&ed), vec!["ab 2Z45678"]);
+        change(&mut ed, "CLOCATE :14").unwrap();
+        change(&mut ed, "CINSERT end").unwrap();
+        assert_eq!(texts(&ed), vec!["ab 2Z45678   end"]);
+        assert!(parse_command("CINSERT").is_err());
+    }
+
+    #[test]
+    fn cappend_after_the_last_nonblank() {
+        let mut ed = editor_with_lines(&["abc   "]);
+        change(&mut ed, "CAPPEND").unwrap();
+        assert_eq!(ed.current_col(), 4);
+        assert_eq!(texts(&ed), vec!["abc   "]);
+        change(&mut ed, "CAPPEND  def").unwrap();
+        assert_eq!(texts(&ed), vec!["abc def"]);
+        assert_eq!(ed.current_col(), 4);
+    }
+
+    #[test]
+    fn column_edits_respect_trunc() {
+        let mut ed = editor_with_lines(&["abcdefgh"]);
+        change(&mut ed, "SET TRUNC 6").unwrap();
+        change(&mut ed, "CLOCATE :2").unwrap();
+        let result = change(&mut ed, "CINSERT XY").unwrap();
+        assert_eq!(result.rc, 3);
+        assert_eq!(texts(&ed), vec!["aXYbcdgh"]);
+        change(&mut ed, "CLOCATE :8").unwrap();
+        change(&mut ed, "CDELETE").unwrap();
+        assert_eq!(texts(&ed), vec!["aXYbcdgh"]);
+    }
+
+    #[test]
+    fn column_commands_at_top_of_file() {
+        let mut ed = editor_with_lines(&["abc"]);
+        ed.execute(&Command::Top).unwrap();
+        assert!(change(&mut ed, "CINSERT x").is_err());
+        assert!(change(&mut ed, "CDELETE").is_err());
+        change(&mut ed, "CLOCATE :4").unwrap();
+        assert_eq!(ed.current_col(), 4);
+    }
+
+    fn visible_lines(ed: &Editor) -> Vec<usize> {
+        (1..=ed.buffer().len())
+            .filter(|&n| ed.is_line_visible(n))
+            .collect()
+    }
+
+    #[test]
+    fn select_levels_and_display_range() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d", "e"]);
+        change(&mut ed, "SET SELECT 2 :4").unwrap();
+        ed.current_line = 5;
+        change(&mut ed, "SET SELECT 1").unwrap();
+        assert!(ed.selection_active());
+        assert_eq!(visible_lines(&ed), vec![4]);
+
+        change(&mut ed, "SET DISPLAY 2").unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 2, 3]);
+        assert_eq!(ed.shadow_count_after(3), 2);
+        change(&mut ed, "SET DISPLAY 1 *").unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 2, 3, 5]);
+
+        // Relative levels never go below 0
+        ed.current_line = 1;
+        change(&mut ed, "SET SELECT -5 2").unwrap();
+        assert_eq!(visible_lines(&ed), vec![3, 5]);
+        // The current line is now hidden, so out of scope until SCOPE ALL
+        change(&mut ed, "SET SELECT +3").unwrap();
+        assert_eq!(visible_lines(&ed), vec![3, 5]);
+        change(&mut ed, "SET SCOPE ALL").unwrap();
+        change(&mut ed, "SET SELECT +3").unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn all_sets_levels_and_display() {
+        let mut ed = editor_with_lines(&["apple", "banana", "apricot", "cherry"]);
+        change(&mut ed, "ALL /ap/").unwrap();
+        let msg = |ed: &mut Editor, cmd| change(ed, cmd).unwrap().message.unwrap();
+        assert_eq!(msg(&mut ed, "QUERY DISPLAY"), "Display=1 1");
+        assert_eq!(msg(&mut ed, "QUERY SELECT"), "Select=1 1");
+
+        // The levels ALL gave stay put when the display range changes
+        change(&mut ed, "SET DISPLAY 0").unwrap();
+        assert_eq!(visible_lines(&ed), vec![2, 4]);
+        change(&mut ed, "SET DISPLAY 0 1").unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 2, 3, 4]);
+
+        change(&mut ed, "ALL").unwrap();
+        assert!(!ed.selection_active());
+        assert_eq!(msg(&mut ed, "QUERY DISPLAY"), "Display=0 0");
+    }
+
+    #[test]
+    fn scope_display_limits_change_and_delete() {
+        let lines = ["x1", "y", "x2", "y", "x3"];
+        let mut ed = editor_with_lines(&lines);
+        change(&mut ed, "ALL /x/").unwrap();
+        change(&mut ed, "CHANGE /y/z/ *").unwrap_err();
+        change(&mut ed, "CHANGE /x/X/ * *").unwrap();
+        assert_eq!(texts(&ed), vec!["X1", "y", "X2", "y", "X3"]);
+
+        // DELETE 2 takes the next two displayed lines, not the hidden one
+        change(&mut ed, "DELETE 2").unwrap();
+        assert_eq!(texts(&ed), vec!["y", "y", "X3"]);
+        assert_eq!(visible_lines(&ed), vec![3]);
+
+        // UNDO gives the lines back their own levels
+        change(&mut ed, "UNDO").unwrap();
+        assert_eq!(texts(&ed), vec!["X1", "y", "X2", "y", "X3"]);
+        assert_eq!(visible_lines(&ed), vec![1, 3, 5]);
+
+        let mut ed = editor_with_lines(&lines);
+        change(&mut ed, "ALL /x/").unwrap();
+        change(&mut ed, "SET SCOPE ALL").unwrap();
+        change(&mut ed, "CHANGE /y/z/ * *").unwrap();
+        assert_eq!(texts(&ed), vec!["x1", "z", "x2", "z", "x3"]);
+        change(&mut ed, "DELETE 2").unwrap();
+        assert_eq!(texts(&ed), vec!["x2", "z", "x3"]);
+    }
+
+    #[test]
+    fn scope_display_limits_targets() {
+        let mut ed = editor_with_lines(&["a", "hidden b", "c", "d"]);
+        change(&mut ed, "SET SELECT 1 :3").unwrap();
+        change(&mut ed, "ALL ~/hidden/").unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 3, 4]);
+        assert!(change(&mut ed, "LOCATE /b/").is_err());
+        change(&mut ed, "LOCATE 1").unwrap();
+        assert_eq!(ed.current_line(), 3);
+        change(&mut ed, "UP 1").unwrap();
+        assert_eq!(ed.current_line(), 1);
+
+        change(&mut ed, "SET SCOPE ALL").unwrap();
+        change(&mut ed, "LOCATE /b/").unwrap();
+        assert_eq!(ed.current_line(), 2);
+    }
+
+    #[test]
+    fn added_lines_share_the_level_above() {
+        let mut ed = editor_with_lines(&["keep", "drop", "keep too"]);
+        change(&mut ed, "ALL /keep/").unwrap();
+        change(&mut ed, "INPUT new").unwrap();
+        assert_eq!(texts(&ed), vec!["keep", "new", "drop", "keep too"]);
+        assert_eq!(visible_lines(&ed), vec![1, 2, 4]);
+
+        // UNDO keeps the selection of the lines that remain
+        ed.execute(&Command::Undo).unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 3]);
+        assert_eq!(
+            ed.extract("/SCOPE/DISPLAY/").unwrap(),
+            vec![
+                ("SCOPE".to_string(), vec!["DISPLAY".to_string()]),
+                (
+                    "DISPLAY".to_string(),
+                    vec!["1".to_string(), "1".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn prefix_exclude_and_show() {
+        let mut ed = editor_with_lines(&["a", "b", "c", "d", "e", "f"]);
+        ed.execute_prefix(2, &PrefixCommand::Exclude(3)).unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 5, 6]);
+        assert_eq!(ed.shadow_group(3), Some((2, 4)));
+        assert_eq!(ed.shadow_group(5), None);
+
+        // s-1 shows the last hidden line of the group, s1 the first
+        ed.execute_prefix(2, &PrefixCommand::Show(Some(-1)))
+            .unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 4, 5, 6]);
+        ed.execute_prefix(2, &PrefixCommand::Show(Some(1))).unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 2, 4, 5, 6]);
+        ed.execute_prefix(3, &PrefixCommand::Show(None)).unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 2, 3, 4, 5, 6]);
+        assert!(matches!(
+            ed.execute_prefix(3, &PrefixCommand::Show(None)),
+            Err(XeditError::PrefixError(_))
+        ));
+
+        // xx excludes a block; a hidden current line moves to a shown one
+        ed.current_line = 4;
+        ed.execute_prefix(5, &PrefixCommand::ExcludeBlock).unwrap();
+        ed.execute_prefix(3, &PrefixCommand::ExcludeBlock).unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 2, 6]);
+        assert_eq!(ed.current_line(), 6);
+
+        // A count past the end of the group shows the whole group
+        ed.execute_prefix(3, &PrefixCommand::Show(Some(i64::MAX)))
+            .unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn exclude_works_with_all() {
+        let mut ed = editor_with_lines(&["x1", "y", "x2", "x3"]);
+        change(&mut ed, "ALL /x/").unwrap();
+        ed.execute_prefix(3, &PrefixCommand::Exclude(1)).unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 4]);
+        assert_eq!(ed.shadow_group(2), Some((2, 3)));
+
+        // Showing the group brings back the line ALL hid as well
+        ed.execute_prefix(2, &PrefixCommand::Show(None)).unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 2, 3, 4]);
+
+        change(&mut ed, "SET DISPLAY 0 *").unwrap();
+        assert!(ed.execute_prefix(1, &PrefixCommand::Exclude(1)).is_err());
+    }
+
+    #[test]
+    fn exclude_in_browse_mode() {
+        let mut ed = editor_with_lines(&["a", "b", "c"]);
+        ed.readonly = true;
+        ed.execute_prefix(2, &PrefixCommand::Exclude(1)).unwrap();
+        assert_eq!(visible_lines(&ed), vec![1, 3]);
+        ed.execute_prefix(2, &PrefixCommand::Show(None)).unwrap();
+        assert!(ed.execute_prefix(2, &PrefixCommand::Delete).is_err());
+        assert!(!ed.is_modified());
+    }
+
+    #[cfg(feature = "rexx")]
+    #[test]
+    fn prefix_macro_gets_line_and_operand() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("nt.xedit"),
+            "parse arg line operand\n':'line\n'INPUT note' operand\n",
+        )
+        .unwrap();
+        let mut ed = editor_with_lines(&["a", "b", "c"]);
+        ed.set_macro_path(vec![dir.path().to_path_buf()]);
+
+        let cmd = ed.parse_prefix("nt7").unwrap();
+        ed.execute_prefix(2, &cmd).unwrap();
+        assert_eq!(texts(&ed), vec!["a", "b", "note 7", "c"]);
+
+        // Without a macro by that name, a typo is not a prefix command
+        assert_eq!(ed.parse_prefix("qq"), None);
+        assert_eq!(ed.parse_prefix("dd2x"), None);
+    }
+
+    #[cfg(feature = "rexx")]
+    #[test]
+    fn prefix_block_macro_copies_to_end() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("ccx.xedit"),
+            "parse arg line .\n\
+             if pending.0 > 0 & pending.2 = 'CCX' then do\n\
+               'SET PENDING OFF'\n\
+               first = min(line, pending.1)\n\
+               'EXTRACT /SIZE/'\n\
+               ':'first\n\
+               'COPY' max(line, pending.1) - first + 1 ':'size.1\n\
+             end\n\
+             else 'SET PENDING BLOCK CCX'\n",
+        )
+        .unwrap();
+        let mut ed = editor_with_lines(&["a", "b", "c", "d"]);
+        ed.set_macro_path(vec![dir.path().to_path_buf()]);
+
+        let cmd = ed.parse_prefix("CCX").unwrap();
+        ed.execute_prefix(3, &cmd).unwrap();
+        assert_eq!(
+            ed.pending_block().unwrap().command,
+            BlockType::User("CCX".into())
+        );
+        ed.execute_prefix(2, &cmd).unwrap();
+        assert_eq!(texts(&ed), vec!["a", "b", "c", "d", "b", "c"]);
+        assert!(!ed.has_pending_block());
+    }
+
+    #[cfg(feature = "rexx")]
+    #[test]
+    fn prefix_macro_block_uses_set_pending() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("zz.xedit"),
+            "parse arg line .\n\
+             if pending.0 > 0 & pending.2 = 'ZZ' then do\n\
+               'SET PENDING OFF'\n\
+               first = min(line, pending.1)\n\
+               ':'first\n\
+               'DELETE' max(line, pending.1) - first + 1\n\
+             end\n\
+             else 'SET PENDING BLOCK ZZ'\n",
+        )
+        .unwrap();
+        let mut ed = editor_with_lines(&["a", "b", "c", "d", "e"]);
+        ed.set_macro_path(vec![dir.path().to_path_buf()]);
+        change(&mut ed, "SET PREFIX SYNONYM q zz").unwrap();
+
+        ed.execute_prefix(4, &ed.parse_prefix("zz").unwrap())
+            .unwrap();
+        let pending = ed.pending_block().unwrap();
+        assert_eq!(pending.command, BlockType::User("ZZ".into()));
+        assert_eq!(pending.start_line, 4);
+        assert_eq!(ed.extract("/PENDING/").unwrap()[0].1, vec!["4", "ZZ"]);
+
+        // A built-in block marker does not close the macro's block
+        assert!(ed.execute_prefix(1, &PrefixCommand::DeleteBlock).is_err());
+
+        ed.execute_prefix(2, &ed.parse_prefix("Q").unwrap())
+            .unwrap();
+        assert_eq!(texts(&ed), vec!["a", "e"]);
+        assert!(!ed.has_pending_block());
+        assert!(ed.extract("/PENDING/").unwrap()[0].1.is_empty());
+    }
+
+    #[test]
+    fn prefix_synonym_for_builtin() {
+        let mut ed = editor_with_lines(&["a", "b", "c"]);
+        change(&mut ed, "SET PREFIX SYNONYM k d").unwrap();
+        let cmd = ed.parse_prefix("k").unwrap();
+        assert_eq!(cmd, PrefixCommand::Delete);
+        ed.execute_prefix(2, &cmd).unwrap();
+        assert_eq!(texts(&ed), vec!["a", "c"]);
+    }
 }
@@ -11,7 +11,7 @@ pub enum XeditError {
     InvalidTarget(String),
     PrefixError(String),
     FileModified,
-    ReadOnly,
+    ReadOnly(String),
     NoFile,
 }
 
@@ -27,7 +27,7 @@ impl fmt::Display for XeditError {
             XeditError::FileModified => {
                 write!(f, "File has been modified; use QQUIT to quit anyway")
             }
-            XeditError::ReadOnly => write!(f, "File is read-only"),
+            XeditError::ReadOnly(cmd) => write!(f, "{} not allowed: file is read-only", cmd),
             XeditError::NoFile => write!(f, "No file in ring"),
         }
     }
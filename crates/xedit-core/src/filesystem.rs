@@ -0,0 +1,124 @@
+//! File access for the editor.
+//!
+//! The editor reads and writes files through the `FileSystem` trait rather
+//! than `std::fs`, so that it can be embedded over CMS minidisks as well as
+//! host directories. `NativeFs` is the host implementation; cms-core provides
+//! one that resolves `fn ft fm` file ids on accessed disks.
+//!
+//! Files are still named by host paths once located: BROWSE indexes the
+//! located path in place, and the SET UNDOFILE sidecar lives beside it.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file as XEDIT knows it: where it lives and its CMS-style identity
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileId {
+    pub path: PathBuf,
+    pub filename: String,
+    pub filetype: String,
+    pub filemode: String,
+}
+
+impl FileId {
+    /// Identify a host file, taking fn and ft from its stem and extension
+    pub fn from_path(path: &Path) -> Self {
+        let part = |s: Option<&std::ffi::OsStr>| {
+            s.map(|s| s.to_string_lossy().to_uppercase())
+                .unwrap_or_default()
+        };
+        FileId {
+            path: path.to_path_buf(),
+            filename: part(path.file_stem()),
+            filetype: part(path.extension()),
+            filemode: "A1".to_string(),
+        }
+    }
+}
+
+/// Where the editor's files, GET input and macros come from
+pub trait FileSystem: fmt::Debug {
+    /// Resolve an XEDIT file operand — `fn ft [fm]` or a single host path —
+    /// to a file. A file that does not exist yet resolves to where it
+    /// would be created.
+    fn locate(&self, fileid: &str) -> io::Result<FileId>;
+
+    /// Read a whole file
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Create or replace a file
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+
+    /// Whether `write` may replace the file at `path`
+    fn is_writable(&self, path: &Path) -> bool;
+
+    /// Find a macro kept by the file system itself (on CMS, `name XEDIT *`),
+    /// for macros not found on the macro path
+    fn find_macro(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// The host file system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NativeFs;
+
+impl FileSystem for NativeFs {
+    /// A single operand is used as a path; `fn ft [fm]` becomes `fn.ft`,
+    /// preferring an existing file with that exact case, then lowercase.
+    fn locate(&self, fileid: &str) -> io::Result<FileId> {
+        let words: Vec<&str> = fileid.split_whitespace().collect();
+        let path = match words.as_slice() {
+            [] => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "No file specified",
+                ))
+            }
+            [_] => PathBuf::from(fileid.trim()),
+            [fname, ftype, ..] => {
+                let exact = PathBuf::from(format!("{}.{}", fname, ftype));
+                if exact.exists() {
+                    exact
+                } else {
+                    PathBuf::from(format!("{}.{}", fname, ftype).to_lowercase())
+                }
+            }
+        };
+        Ok(FileId::from_path(&path))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        fs::write(path, data)
+    }
+
+    /// A file that does not exist yet is writable
+    fn is_writable(&self, path: &Path) -> bool {
+        fs::metadata(path).map_or(true, |m| !m.permissions().readonly())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_locate() {
+        let id = NativeFs.locate("notes/todo.txt").unwrap();
+        assert_eq!(id.path, PathBuf::from("notes/todo.txt"));
+        assert_eq!(id.filename, "TODO");
+        assert_eq!(id.filetype, "TXT");
+        assert_eq!(id.filemode, "A1");
+
+        let id = NativeFs.locate("NOSUCH FILE A").unwrap();
+        assert_eq!(id.path, PathBuf::from("nosuch.file"));
+        assert_eq!(id.filename, "NOSUCH");
+        assert!(NativeFs.locate("  ").is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! The system the editor runs under.
+//!
+//! On VM, a macro's `ADDRESS CMS` commands go to CMS. A program that
+//! embeds the editor alongside a CMS gives it a `Host` for those commands;
+//! without one they go to the shell, as other environments do. cms-core
+//! implements `Host` for its command processor.
+
+use std::fmt;
+
+/// Runs the commands macros address to CMS
+pub trait Host: fmt::Debug {
+    /// Run a command line, giving its output lines and return code.
+    /// `vars` are the calling macro's variables, for commands that read
+    /// or set them (on CMS, GLOBALV GET and PUT).
+    fn command(&mut self, line: &str, vars: &mut dyn Variables) -> (Vec<String>, i32);
+}
+
+/// The variables of a running REXX program
+pub trait Variables {
+    /// A variable's value, or `None` if the program has not set it
+    fn get(&self, name: &str) -> Option<String>;
+
+    fn set(&mut self, name: &str, value: &str);
+}
+
+#[cfg(feature = "rexx")]
+impl Variables for patch_rexx::env::EnvVars<'_> {
+    fn get(&self, name: &str) -> Option<String> {
+        self.is_set(name)
+            .then(|| self.get(name).as_str().to_string())
+    }
+
+    fn set(&mut self, name: &str, value: &str) {
+        self.set(name, patch_rexx::value::RexxValue::new(value));
+    }
+}
@@ -1,9 +1,15 @@
 pub mod buffer;
+pub mod codepage;
 pub mod command;
 pub mod editor;
 pub mod error;
+pub mod filesystem;
+pub mod host;
+pub mod line_index;
 #[cfg(feature = "rexx")]
 pub mod macro_engine;
 pub mod prefix;
 pub mod ring;
+pub mod rope;
 pub mod target;
+pub mod undo;
@@ -0,0 +1,458 @@
+//! Lazily read files for BROWSE.
+//!
+//! `LineIndex` scans a file once, recording the byte offset of every
+//! block of lines, and reads blocks from disk only when they are looked
+//! at. Blocks handed out by reference stay cached until `trim` drops the
+//! ones far from the current line, so a multi-gigabyte file never has to
+//! fit in memory. A block that cannot be read is not cached: its lines are
+//! missing until a later read succeeds, and the error is kept for
+//! `take_error`.
+
+use std::cell::{OnceCell, RefCell};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::buffer::Line;
+use crate::codepage::Codepage;
+
+/// Lines per indexed block
+const BLOCK_LINES: usize = 1024;
+
+/// Cached blocks on either side of the current line's block kept by `trim`
+const KEEP_BLOCKS: usize = 2;
+
+/// Size of each read while indexing
+const SCAN_CHUNK: usize = 1 << 20;
+
+/// How lines are laid out in the file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layout {
+    /// Lines ending in a line terminator
+    Lines,
+    /// Back-to-back fixed-length records of this many bytes
+    Records(usize),
+}
+
+/// Read-only, on-demand view of the lines of a file (0-based indexes)
+#[derive(Debug)]
+pub struct LineIndex {
+    file: File,
+    file_len: u64,
+    codepage: Codepage,
+    layout: Layout,
+    /// Byte offset of the first line of each block (`Layout::Lines` only)
+    block_starts: Vec<u64>,
+    len: usize,
+    /// Longest line, in bytes
+    max_width: usize,
+    /// Shortest line, in bytes
+    min_width: usize,
+    /// Whether any line terminator was found
+    terminated: bool,
+    /// Whether the last line has a terminator too
+    all_terminated: bool,
+    /// Blocks that have been handed out by reference
+    cache: Vec<OnceCell<Box<[Line]>>>,
+    /// The last block read for a search, which is not cached
+    scratch: RefCell<Option<(usize, Box<[Line]>)>>,
+    /// The last read error not yet collected by `take_error`
+    error: RefCell<Option<io::Error>>,
+}
+
+impl LineIndex {
+    /// Index a file of terminated lines: LF (CRLF is accepted) in UTF-8,
+    /// NL (X'15') or LF (X'25') in EBCDIC
+    pub fn open_lines(path: &Path, codepage: Codepage) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let ends = terminators(codepage);
+        let mut block_starts = vec![0];
+        let mut buf = vec![0; SCAN_CHUNK];
+        let mut pos = 0u64;
+        let mut line_start = 0u64;
+        let mut len = 0;
+        let mut max_width = 0;
+        let mut min_width = usize::MAX;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            for (i, &b) in buf[..n].iter().enumerate() {
+                if ends[b as usize] {
+                    let end = pos + i as u64;
+                    let width = (end - line_start) as usize;
+                    max_width = max_width.max(width);
+                    min_width = min_width.min(width);
+                    line_start = end + 1;
+                    len += 1;
+                    if len % BLOCK_LINES == 0 {
+                        block_starts.push(line_start);
+                    }
+                }
+            }
+            pos += n as u64;
+        }
+        let terminated = len > 0;
+        // A last line without a terminator
+        let all_terminated = line_start == pos;
+        if !all_terminated {
+            let width = (pos - line_start) as usize;
+            max_width = max_width.max(width);
+            min_width = min_width.min(width);
+            len += 1;
+        }
+        block_starts.truncate(len.div_ceil(BLOCK_LINES));
+        Ok(Self {
+            file,
+            file_len: pos,
+            codepage,
+            layout: Layout::Lines,
+            block_starts,
+            len,
+            max_width,
+            min_width,
+            terminated,
+            all_terminated,
+            cache: empty_cache(len),
+            scratch: RefCell::new(None),
+            error: RefCell::new(None),
+        })
+    }
+
+    /// Open a file of back-to-back `lrecl`-byte records; no scan is needed
+    pub fn open_records(path: &Path, codepage: Codepage, lrecl: usize) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let len = file_len.div_ceil(lrecl as u64) as usize;
+        Ok(Self {
+            file,
+            file_len,
+            codepage,
+            layout: Layout::Records(lrecl),
+            block_starts: Vec::new(),
+            len,
+            max_width: lrecl,
+            min_width: lrecl,
+            terminated: false,
+            all_terminated: false,
+            cache: empty_cache(len),
+            scratch: RefCell::new(None),
+            error: RefCell::new(None),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Length of the longest line in bytes (the LRECL for records)
+    pub fn max_width(&self) -> usize {
+        self.max_width
+    }
+
+    /// Whether the file contained line terminators (a file without any
+    /// may really be fixed-length records)
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+
+    /// Whether every line is terminated and exactly `width` bytes long,
+    /// as in a fixed-record text file
+    pub fn has_uniform_lines(&self, width: usize) -> bool {
+        self.all_terminated && self.min_width == width && self.max_width == width
+    }
+
+    /// Length of the file in bytes
+    pub fn file_len(&self) -> u64 {
+        self.file_len
+    }
+
+    /// Get a line, reading and caching its block if needed.
+    /// A line whose block cannot be read is `None`.
+    pub fn get(&self, index: usize) -> Option<&Line> {
+        if index >= self.len {
+            return None;
+        }
+        let block = index / BLOCK_LINES;
+        let cell = &self.cache[block];
+        if cell.get().is_none() {
+            let lines = self.read_or_note(block)?;
+            // Not reentrant, so the cell is still empty
+            let _ = cell.set(lines);
+        }
+        cell.get()?.get(index % BLOCK_LINES)
+    }
+
+    /// Look at a line's text without caching its block, for searches that
+    /// run through the whole file. Not reentrant.
+    pub fn with_text<R>(&self, index: usize, f: impl FnOnce(&str) -> R) -> Option<R> {
+        if index >= self.len {
+            return None;
+        }
+        let block = index / BLOCK_LINES;
+        let offset = index % BLOCK_LINES;
+        if let Some(lines) = self.cache[block].get() {
+            return lines.get(offset).map(|l| f(l.text()));
+        }
+        let mut scratch = self.scratch.borrow_mut();
+        if scratch.as_ref().is_none_or(|(b, _)| *b != block) {
+            *scratch = Some((block, self.read_or_note(block)?));
+        }
+        let (_, lines) = scratch.as_ref()?;
+        lines.get(offset).map(|l| f(l.text()))
+    }
+
+    /// Every line, in order; reads the whole file
+    pub fn read_all(&self) -> io::Result<Vec<Line>> {
+        let mut lines = Vec::with_capacity(self.len);
+        for block in 0..self.cache.len() {
+            match self.cache[block].get() {
+                Some(cached) => lines.extend_from_slice(cached),
+                None => lines.extend(self.read_block(block)?),
+            }
+        }
+        Ok(lines)
+    }
+
+    /// The last error reading the file since the previous call, if any
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error.borrow_mut().take()
+    }
+
+    /// Drop cached blocks that are not near line `index`
+    pub fn trim(&mut self, index: usize) {
+        let keep = index / BLOCK_LINES;
+        for (block, cell) in self.cache.iter_mut().enumerate() {
+            if block.abs_diff(keep) > KEEP_BLOCKS {
+                cell.take();
+            }
+        }
+    }
+
+    /// Number of blocks currently cached
+    pub fn cached_blocks(&self) -> usize {
+        self.cache.iter().filter(|c| c.get().is_some()).count()
+    }
+
+    /// Read a block, keeping any error for `take_error`
+    fn read_or_note(&self, block: usize) -> Option<Box<[Line]>> {
+        self.read_block(block)
+            .map_err(|e| *self.error.borrow_mut() = Some(e))
+            .ok()
+    }
+
+    fn read_block(&self, block: usize) -> io::Result<Box<[Line]>> {
+        let first = block * BLOCK_LINES;
+        let count = BLOCK_LINES.min(self.len - first);
+        let (start, end) = match self.layout {
+            Layout::Lines => (
+                self.block_starts[block],
+                self.block_starts
+                    .get(block + 1)
+                    .copied()
+                    .unwrap_or(self.file_len),
+            ),
+            Layout::Records(lrecl) => {
+                let start = (first * lrecl) as u64;
+                (start, (start + (count * lrecl) as u64).min(self.file_len))
+            }
+        };
+        let mut bytes = vec![0; (end - start) as usize];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut bytes)?;
+
+        let cp = self.codepage;
+        let lines = match self.layout {
+            Layout::Lines => {
+                let ends = terminators(cp);
+                let mut data = bytes.as_slice();
+                if data.last().is_some_and(|&b| ends[b as usize]) {
+                    data = &data[..data.len() - 1];
+                }
+                data.split(|&b| ends[b as usize])
+                    .take(count)
+                    .map(|raw| {
+                        let mut text = if cp.is_ebcdic() {
+                            cp.decode(raw)
+                        } else {
+                            String::from_utf8_lossy(raw).into_owned()
+                        };
+                        if text.ends_with('\r') {
+                            text.pop();
+                        }
+                        Line::new(text)
+                    })
+                    .collect()
+            }
+            Layout::Records(lrecl) => bytes
+                .chunks(lrecl)
+                .map(|rec| Line::new(cp.decode(rec).trim_end_matches(' ')))
+                .collect(),
+        };
+        Ok(lines)
+    }
+}
+
+/// One empty cache slot per block of `len` lines
+fn empty_cache(len: usize) -> Vec<OnceCell<Box<[Line]>>> {
+    (0..len.div_ceil(BLOCK_LINES))
+        .map(|_| OnceCell::new())
+        .collect()
+}
+
+/// Bytes that end a line in the code page
+fn terminators(codepage: Codepage) -> [bool; 256] {
+    let mut ends = [false; 256];
+    for b in 0..=255u8 {
+        ends[b as usize] = if codepage.is_ebcdic() {
+            matches!(codepage.decode_byte(b), '\n' | '\u{85}')
+        } else {
+            b == b'\n'
+        };
+    }
+    ends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_file(dir: &tempfile::TempDir, bytes: &[u8]) -> std::path::PathBuf {
+        let path = dir.path().join("data");
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+        path
+    }
+
+    fn texts(index: &LineIndex) -> Vec<String> {
+        (0..index.len())
+            .map(|i| index.get(i).unwrap().text().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn lines_across_blocks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let content: String = (0..2 * BLOCK_LINES + 3)
+            .map(|i| format!("line {}\n", i))
+            .collect();
+        let path = write_file(&dir, content.as_bytes());
+        let index = LineIndex::open_lines(&path, Codepage::Utf8).unwrap();
+        assert_eq!(index.len(), 2 * BLOCK_LINES + 3);
+        assert!(index.is_terminated());
+        assert_eq!(index.cached_blocks(), 0);
+
+        let last = 2 * BLOCK_LINES + 2;
+        assert_eq!(index.get(last).unwrap().text(), format!("line {}", last));
+        assert_eq!(
+            index.with_text(BLOCK_LINES, str::to_string).unwrap(),
+            format!("line {}", BLOCK_LINES)
+        );
+        assert_eq!(index.cached_blocks(), 1);
+        assert!(index.get(last + 1).is_none());
+        assert_eq!(index.max_width(), format!("line {}", last).len());
+    }
+
+    #[test]
+    fn unreadable_block_is_reported_and_not_cached() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let content: String = (0..2 * BLOCK_LINES).map(|i| format!("{}\n", i)).collect();
+        let path = write_file(&dir, content.as_bytes());
+        let index = LineIndex::open_lines(&path, Codepage::Utf8).unwrap();
+
+        // The file shrinks behind the index's back
+        std::fs::write(&path, "0\n").unwrap();
+        assert!(index.get(BLOCK_LINES).is_none());
+        assert!(index.with_text(BLOCK_LINES, str::len).is_none());
+        assert!(index.read_all().is_err());
+        assert!(index.take_error().is_some());
+        assert!(index.take_error().is_none());
+        assert_eq!(index.cached_blocks(), 0);
+
+        // A later read sees the lines again
+        std::fs::write(&path, content).unwrap();
+        let expected = BLOCK_LINES.to_string();
+        assert_eq!(index.get(BLOCK_LINES).unwrap().text(), expected);
+    }
+
+    #[test]
+    fn exact_block_multiple_and_crlf() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let content = "x\r\n".repeat(BLOCK_LINES);
+        let path = write_file(&dir, content.as_bytes());
+        let index = LineIndex::open_lines(&path, Codepage::Utf8).unwrap();
+        assert_eq!(index.len(), BLOCK_LINES);
+        assert_eq!(index.block_starts.len(), 1);
+        assert!(texts(&index).iter().all(|t| t == "x"));
+    }
+
+    #[test]
+    fn unterminated_last_line() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = write_file(&dir, b"one\n\ntwo");
+        let index = LineIndex::open_lines(&path, Codepage::Utf8).unwrap();
+        assert_eq!(texts(&index), ["one", "", "two"]);
+
+        assert!(!index.has_uniform_lines(3));
+
+        let path = write_file(&dir, b"");
+        let index = LineIndex::open_lines(&path, Codepage::Utf8).unwrap();
+        assert!(index.is_empty());
+        assert!(!index.is_terminated());
+    }
+
+    #[test]
+    fn uniform_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = write_file(
+            &dir,
+            b"abc
+def
+",
+        );
+        let index = LineIndex::open_lines(&path, Codepage::Utf8).unwrap();
+        assert!(index.has_uniform_lines(3));
+        assert!(!index.has_uniform_lines(4));
+
+        let path = write_file(
+            &dir, b"abc
+de
+",
+        );
+        let index = LineIndex::open_lines(&path, Codepage::Utf8).unwrap();
+        assert!(!index.has_uniform_lines(3));
+    }
+
+    #[test]
+    fn ebcdic_lines_and_records() {
+        let dir = tempfile::TempDir::new().unwrap();
+        // "AB" NL "C" LF in CP037
+        let path = write_file(&dir, &[0xC1, 0xC2, 0x15, 0xC3, 0x25]);
+        let index = LineIndex::open_lines(&path, Codepage::Cp037).unwrap();
+        assert_eq!(texts(&index), ["AB", "C"]);
+
+        let path = write_file(&dir, &[0xC1, 0x40, 0x40, 0xC2, 0xC3]);
+        let index = LineIndex::open_records(&path, Codepage::Cp037, 3).unwrap();
+        assert_eq!(texts(&index), ["A", "BC"]);
+        assert_eq!(index.max_width(), 3);
+    }
+
+    #[test]
+    fn trim_keeps_nearby_blocks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let content = "y\n".repeat(10 * BLOCK_LINES);
+        let path = write_file(&dir, content.as_bytes());
+        let mut index = LineIndex::open_lines(&path, Codepage::Utf8).unwrap();
+        assert_eq!(texts(&index).len(), 10 * BLOCK_LINES);
+        assert_eq!(index.cached_blocks(), 10);
+        index.trim(5 * BLOCK_LINES);
+        assert_eq!(index.cached_blocks(), 2 * KEEP_BLOCKS + 1);
+        assert_eq!(index.read_all().unwrap().len(), 10 * BLOCK_LINES);
+    }
+}
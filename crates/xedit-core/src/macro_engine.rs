@@ -1,8 +1,9 @@
 //! REXX macro engine for XEDIT.
 //!
 //! Macros are REXX programs that can:
-//! - Query editor state via pre-populated EXTRACT variables
+//! - Query editor state via EXTRACT stem variables
 //! - Execute XEDIT commands via ADDRESS XEDIT (bare string expressions)
+//! - Run CMS commands via ADDRESS CMS, when the editor has a `Host`
 //! - Return a result code
 //!
 //! # Example macro
@@ -17,14 +18,13 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use patch_rexx::env::Environment;
+use patch_rexx::env::{EnvVars, Environment};
 use patch_rexx::eval::Evaluator;
 use patch_rexx::lexer::Lexer;
 use patch_rexx::parser::Parser;
 use patch_rexx::value::RexxValue;
 
-use crate::buffer::RecordFormat;
-use crate::command::parse_command;
+use crate::command::{parse_command, Command};
 use crate::editor::Editor;
 use crate::error::{Result, XeditError};
 
@@ -32,7 +32,8 @@ use crate::error::{Result, XeditError};
 ///
 /// The macro executes with ADDRESS set to XEDIT. Bare string expressions
 /// (like `'LOCATE /foo/'`) are dispatched to the editor as XEDIT commands.
-/// EXTRACT-style stem variables are pre-populated before execution.
+/// EXTRACT-style stem variables are pre-populated before execution, and
+/// an `'EXTRACT /item/'` issued by the macro refreshes the stems it names.
 pub fn run_macro(editor: &mut Editor, source: &str, args: &str) -> Result<()> {
     // Parse the REXX source
     let mut lexer = Lexer::new(source);
@@ -52,6 +53,8 @@ pub fn run_macro(editor: &mut Editor, source: &str, args: &str) -> Result<()> {
     // Pre-populate EXTRACT variables
     populate_extract_vars(&mut rexx_env, editor);
 
+    let host = editor.host().cloned();
+
     // Temporarily move editor state into an Rc<RefCell> so the command handler
     // closure (which must be 'static) can access it. We swap in an empty editor,
     // run the macro, then swap back.
@@ -68,8 +71,16 @@ pub fn run_macro(editor: &mut Editor, source: &str, args: &str) -> Result<()> {
 
     // The command handler intercepts ADDRESS XEDIT commands
     let editor_handle = Rc::clone(&shared_editor);
-    let handler = move |addr_env: &str, command: &str| -> Option<i32> {
+    let handler = move |addr_env: &str, command: &str, vars: &mut EnvVars<'_>| -> Option<i32> {
         let addr_upper = addr_env.to_uppercase();
+        if addr_upper == "CMS" {
+            // Without a host, CMS falls through to the shell
+            let (lines, rc) = host.as_ref()?.borrow_mut().command(command, vars);
+            if let Some(line) = lines.last() {
+                editor_handle.borrow_mut().set_message(line.clone());
+            }
+            return Some(rc);
+        }
         if addr_upper != "XEDIT" && addr_upper != "COMMAND" {
             return None; // fall through to shell for other environments
         }
@@ -87,36 +98,34 @@ pub fn run_macro(editor: &mut Editor, source: &str, args: &str) -> Result<()> {
             return Some(0);
         }
 
-        // Handle EXTRACT specially — all variables are pre-populated before
-        // execution, so EXTRACT commands are acknowledged without re-parsing.
-        // This is a simplification: IBM XEDIT EXTRACT selectively populates
-        // only the requested stems. Here all stems are always available.
-        if cmd_text.to_uppercase().starts_with("EXTRACT ") {
-            return Some(0);
-        }
-
         // Parse and execute the XEDIT command
         // RC codes follow IBM XEDIT conventions:
         //   0 = success
         //   1 = general error
         //   2 = target not found
-        //   3 = command not recognized
+        //   3 = command not recognized, or data truncated
         //   5 = file not found / I/O error
         let mut ed = editor_handle.borrow_mut();
-        match parse_command(cmd_text) {
-            Ok(cmd) => match ed.execute(&cmd) {
-                Ok(_result) => Some(0),
-                Err(ref e) => Some(match e {
-                    XeditError::TargetNotFound(_) => 2,
-                    XeditError::FileNotFound(_) | XeditError::Io(_) => 5,
-                    _ => 1,
-                }),
-            },
-            Err(_) => Some(3),
-        }
+        let result = match parse_command(cmd_text) {
+            // EXTRACT writes its values into the macro's stems
+            Ok(Command::Extract(items)) => ed.extract(&items).map(|items| {
+                set_extract_stems(items, |stem, tail, value| {
+                    vars.set_compound(stem, tail, value)
+                });
+                0
+            }),
+            Ok(cmd) => ed.execute(&cmd).map(|result| result.rc),
+            Err(_) => return Some(3),
+        };
+        Some(match result {
+            Ok(rc) => rc,
+            Err(XeditError::TargetNotFound(_)) => 2,
+            Err(XeditError::FileNotFound(_) | XeditError::Io(_)) => 5,
+            Err(_) => 1,
+        })
     };
 
-    evaluator.set_command_handler(Box::new(handler));
+    evaluator.set_command_handler_with_env(Box::new(handler));
 
     // Execute the macro
     let result = evaluator
@@ -138,185 +147,37 @@ pub fn run_macro(editor: &mut Editor, source: &str, args: &str) -> Result<()> {
     Ok(())
 }
 
+/// Every EXTRACT item, in the order stems are pre-populated.
+const ALL_EXTRACT_ITEMS: &str = "/CURLINE/SIZE/LINE/COLUMN/FNAME/FTYPE/FMODE/TRUNC/ALT/TOF/EOF/\
+MODIFIED/LRECL/RECFM/NUMBER/PREFIX/SCALE/CASE/WRAP/HEX/STAY/SHADOW/VERIFY/LASTMSG/ZONE/ARBCHAR/CODEPAGE/UNDO/SPILL/POINT/SELECT/DISPLAY/SCOPE/PENDING/";
+
 /// Pre-populate REXX environment with EXTRACT-style stem variables.
 ///
 /// This follows the IBM XEDIT EXTRACT convention where each item
 /// sets stem variables: `item.0` = count, `item.1` = first value, etc.
-///
-/// **Note:** These variables are a static snapshot taken before macro
-/// execution begins. Changes made by XEDIT commands within the macro
-/// (e.g., moving the cursor, modifying lines) are NOT reflected in the
-/// EXTRACT variables mid-execution. Macros that need fresh state after
-/// a command should use `QUERY` and parse the resulting message.
-// TODO: patch-rexx enhancement for dynamic EXTRACT refresh —
-// the command handler closure cannot access the REXX Environment
-// (it is mutably borrowed by the Evaluator). A post-command callback
-// or extended handler return type in patch-rexx would enable this.
+/// Values come from `Editor::extract`, the same code that executes
+/// an `'EXTRACT /item/'` command; the stems describe the editor as it
+/// was when the macro started, until the macro extracts them again.
 fn populate_extract_vars(env: &mut Environment, editor: &Editor) {
-    // CURLINE: current line number and text
-    let curline_num = editor.current_line().to_string();
-    let curline_text = editor.current_line_text().to_string();
-    env.set_compound("CURLINE", "0", RexxValue::new("3"));
-    env.set_compound("CURLINE", "1", RexxValue::new(&curline_num));
-    env.set_compound(
-        "CURLINE",
-        "2",
-        RexxValue::new(if editor.at_tof() { "ON" } else { "OFF" }),
-    );
-    env.set_compound("CURLINE", "3", RexxValue::new(&curline_text));
-
-    // SIZE: number of lines in the file
-    let size = editor.buffer().len().to_string();
-    env.set_compound("SIZE", "0", RexxValue::new("1"));
-    env.set_compound("SIZE", "1", RexxValue::new(&size));
-
-    // LINE: current line number
-    env.set_compound("LINE", "0", RexxValue::new("1"));
-    env.set_compound("LINE", "1", RexxValue::new(&curline_num));
-
-    // COLUMN: current column
-    let col = editor.current_col().to_string();
-    env.set_compound("COLUMN", "0", RexxValue::new("1"));
-    env.set_compound("COLUMN", "1", RexxValue::new(&col));
-
-    // FNAME: filename
-    env.set_compound("FNAME", "0", RexxValue::new("1"));
-    env.set_compound("FNAME", "1", RexxValue::new(editor.filename()));
-
-    // FTYPE: filetype
-    env.set_compound("FTYPE", "0", RexxValue::new("1"));
-    env.set_compound("FTYPE", "1", RexxValue::new(editor.filetype()));
-
-    // FMODE: filemode
-    env.set_compound("FMODE", "0", RexxValue::new("1"));
-    env.set_compound("FMODE", "1", RexxValue::new(editor.filemode()));
-
-    // TRUNC: truncation column
-    let trunc = editor.trunc().to_string();
-    env.set_compound("TRUNC", "0", RexxValue::new("1"));
-    env.set_compound("TRUNC", "1", RexxValue::new(&trunc));
-
-    // ALT: alteration count
-    let alt = editor.alt_count().to_string();
-    env.set_compound("ALT", "0", RexxValue::new("1"));
-    env.set_compound("ALT", "1", RexxValue::new(&alt));
-
-    // TOF: whether at top of file
-    env.set_compound("TOF", "0", RexxValue::new("1"));
-    env.set_compound(
-        "TOF",
-        "1",
-        RexxValue::new(if editor.at_tof() { "ON" } else { "OFF" }),
-    );
-
-    // EOF: whether at end of file
-    env.set_compound("EOF", "0", RexxValue::new("1"));
-    env.set_compound(
-        "EOF",
-        "1",
-        RexxValue::new(if editor.at_eof() { "ON" } else { "OFF" }),
-    );
-
-    // MODIFIED: whether file has been changed
-    env.set_compound("MODIFIED", "0", RexxValue::new("1"));
-    env.set_compound(
-        "MODIFIED",
-        "1",
-        RexxValue::new(if editor.is_modified() { "ON" } else { "OFF" }),
-    );
-
-    // LRECL: logical record length
-    let lrecl = editor.buffer().lrecl().to_string();
-    env.set_compound("LRECL", "0", RexxValue::new("1"));
-    env.set_compound("LRECL", "1", RexxValue::new(&lrecl));
-
-    // RECFM: record format
-    let recfm = match editor.buffer().recfm() {
-        RecordFormat::Variable => "V",
-        RecordFormat::Fixed => "F",
-    };
-    env.set_compound("RECFM", "0", RexxValue::new("1"));
-    env.set_compound("RECFM", "1", RexxValue::new(recfm));
-
-    // NUMBER: line number display
-    env.set_compound("NUMBER", "0", RexxValue::new("1"));
-    env.set_compound(
-        "NUMBER",
-        "1",
-        RexxValue::new(if editor.show_number() { "ON" } else { "OFF" }),
-    );
-
-    // PREFIX: prefix area display
-    env.set_compound("PREFIX", "0", RexxValue::new("1"));
-    env.set_compound(
-        "PREFIX",
-        "1",
-        RexxValue::new(if editor.show_prefix() { "ON" } else { "OFF" }),
-    );
-
-    // SCALE: scale line display
-    env.set_compound("SCALE", "0", RexxValue::new("1"));
-    env.set_compound(
-        "SCALE",
-        "1",
-        RexxValue::new(if editor.show_scale() { "ON" } else { "OFF" }),
-    );
-
-    // CASE: case sensitivity setting
-    env.set_compound("CASE", "0", RexxValue::new("1"));
-    env.set_compound(
-        "CASE",
-        "1",
-        RexxValue::new(if editor.case_respect() {
-            "RESPECT"
-        } else {
-            "IGNORE"
-        }),
-    );
-
-    // WRAP: wrap setting
-    env.set_compound("WRAP", "0", RexxValue::new("1"));
-    env.set_compound(
-        "WRAP",
-        "1",
-        RexxValue::new(if editor.wrap() { "ON" } else { "OFF" }),
-    );
-
-    // HEX: hex display setting
-    env.set_compound("HEX", "0", RexxValue::new("1"));
-    env.set_compound(
-        "HEX",
-        "1",
-        RexxValue::new(if editor.hex() { "ON" } else { "OFF" }),
-    );
-
-    // STAY: stay setting
-    env.set_compound("STAY", "0", RexxValue::new("1"));
-    env.set_compound(
-        "STAY",
-        "1",
-        RexxValue::new(if editor.stay() { "ON" } else { "OFF" }),
-    );
-
-    // SHADOW: shadow line display
-    env.set_compound("SHADOW", "0", RexxValue::new("1"));
-    env.set_compound(
-        "SHADOW",
-        "1",
-        RexxValue::new(if editor.show_shadow() { "ON" } else { "OFF" }),
-    );
-
-    // VERIFY: verify columns (start and end)
-    let verify_start = editor.verify_start().to_string();
-    let verify_end = editor.verify_end().to_string();
-    env.set_compound("VERIFY", "0", RexxValue::new("2"));
-    env.set_compound("VERIFY", "1", RexxValue::new(&verify_start));
-    env.set_compound("VERIFY", "2", RexxValue::new(&verify_end));
-
-    // LASTMSG: last message text
-    let lastmsg = editor.last_message().unwrap_or("");
-    env.set_compound("LASTMSG", "0", RexxValue::new("1"));
-    env.set_compound("LASTMSG", "1", RexxValue::new(lastmsg));
+    let items = editor
+        .extract(ALL_EXTRACT_ITEMS)
+        .expect("built-in EXTRACT item list is valid");
+    set_extract_stems(items, |stem, tail, value| {
+        env.set_compound(stem, tail, value)
+    });
+}
+
+/// Store EXTRACT results as `item.0` (the count) and `item.1`, `item.2`, ...
+fn set_extract_stems(
+    items: Vec<(String, Vec<String>)>,
+    mut set: impl FnMut(&str, &str, RexxValue),
+) {
+    for (stem, values) in items {
+        set(&stem, "0", RexxValue::new(values.len().to_string()));
+        for (i, value) in values.iter().enumerate() {
+            set(&stem, &(i + 1).to_string(), RexxValue::new(value));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -327,7 +188,7 @@ mod tests {
         let mut ed = Editor::new();
         // Use input_line to add lines since we can't access buffer directly
         for line in lines {
-            ed.input_line(line);
+            ed.input_line(line).unwrap();
         }
         // Reset to line 1
         ed.set_current_line(1);
@@ -360,6 +221,18 @@ mod tests {
         assert_eq!(ed.buffer().line_text(2), Some("hi there"));
     }
 
+    #[test]
+    fn macro_replace_line() {
+        let mut ed = editor_with_lines(&["hello", "world"]);
+
+        let source = r#"
+            text = 'centered'
+            'COMMAND REPLACE' copies(' ', 2) || text
+        "#;
+        run_macro(&mut ed, source, "").unwrap();
+        assert_eq!(ed.buffer().line_text(1), Some("  centered"));
+    }
+
     #[test]
     fn macro_with_rexx_logic() {
         let mut ed = editor_with_lines(&["aaa", "bbb", "ccc"]);
@@ -479,8 +352,54 @@ mod tests {
         assert_eq!(ed.current_line(), 2);
     }
 
+    #[test]
+    fn macro_rc_three_on_truncation() {
+        let mut ed = editor_with_lines(&["alpha", "beta"]);
+
+        // Text typed past TRUNC is cut off with RC=3
+        let source = r#"
+            'SET TRUNC 5'
+            'INPUT abcdefgh'
+            if rc = 3 then
+                'BOTTOM'
+        "#;
+        run_macro(&mut ed, source, "").unwrap();
+        assert_eq!(ed.buffer().line_text(2), Some("abcde"));
+        assert_eq!(ed.current_line(), 3);
+    }
+
     // -- EXTRACT variable tests --
 
+    #[test]
+    fn extract_command_refreshes_stems() {
+        let mut ed = editor_with_lines(&["alpha", "beta", "gamma"]);
+        ed.execute(&parse_command(":1").unwrap()).unwrap();
+
+        // The stems follow the editor after the macro moves and extracts
+        let source = r#"
+            'DOWN 1'
+            'EXTRACT /CURLINE/LINE/'
+            if curline.3 = 'beta' & line.1 = 2 then 'DOWN 1'
+        "#;
+        run_macro(&mut ed, source, "").unwrap();
+        assert_eq!(ed.current_line(), 3);
+    }
+
+    #[test]
+    fn extract_command_rc() {
+        let mut ed = editor_with_lines(&["alpha", "beta", "gamma"]);
+
+        // Valid items give RC 0; an unknown item is rejected with a nonzero RC
+        let source = r#"
+            'EXTRACT /CURLINE/SIZE/'
+            if rc = 0 then 'DOWN 1'
+            'EXTRACT /NOSUCHITEM/'
+            if rc \= 0 then 'DOWN 1'
+        "#;
+        run_macro(&mut ed, source, "").unwrap();
+        assert_eq!(ed.current_line(), 3);
+    }
+
     #[test]
     fn extract_lrecl_and_recfm() {
         let mut ed = editor_with_lines(&["hello", "world"]);
@@ -510,15 +429,16 @@ mod tests {
 
     #[test]
     fn extract_case_setting() {
-        let mut ed = editor_with_lines(&["test"]);
+        let mut ed = editor_with_lines(&["test", "more"]);
+        ed.execute(&parse_command(":1").unwrap()).unwrap();
 
-        // Default: case_respect = false, so CASE.1 = "IGNORE"
+        // Defaults: CASE.1 = MIXED, CASE.2 = IGNORE
         let source = r#"
-            if case.1 = 'IGNORE' then
+            if case.1 = 'MIXED' & case.2 = 'IGNORE' then
                 'BOTTOM'
         "#;
         run_macro(&mut ed, source, "").unwrap();
-        assert_eq!(ed.current_line(), 1);
+        assert_eq!(ed.current_line(), 2);
     }
 
     #[test]
@@ -546,4 +466,21 @@ mod tests {
         run_macro(&mut ed, source, "").unwrap();
         assert_eq!(ed.current_line(), 1);
     }
+
+    #[test]
+    fn extract_column_after_clocate() {
+        let mut ed = editor_with_lines(&["key=value"]);
+        ed.execute(&parse_command("CLOCATE /=/").unwrap()).unwrap();
+
+        // COLUMN.1 is the column pointer; the macro inserts it at column 1
+        let source = r#"
+            if column.0 = 1 then do
+                'CLOCATE :1'
+                'CINSERT' column.1
+            end
+        "#;
+        run_macro(&mut ed, source, "").unwrap();
+        assert_eq!(ed.buffer().line_text(1), Some("4key=value"));
+        assert_eq!(ed.current_col(), 1);
+    }
 }
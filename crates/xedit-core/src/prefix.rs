@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+
+use crate::target::parse_point_name;
+
 /// Prefix area commands — typed into the line number area
 #[derive(Debug, Clone, PartialEq)]
 pub enum PrefixCommand {
@@ -31,6 +35,17 @@ pub enum PrefixCommand {
     ShiftRight(usize),
     /// `<[n]` — Shift line left n columns (default 2)
     ShiftLeft(usize),
+    /// `.name` — Name this line (SET POINT)
+    Point(String),
+    /// `x[n]` — Exclude n lines from the display (default 1)
+    Exclude(usize),
+    /// `xx` — Start/end block exclude
+    ExcludeBlock,
+    /// `s`, `s[n]`, `s-n` — On a shadow line: show all of its hidden lines,
+    /// the first n, or the last n
+    Show(Option<i64>),
+    /// Any other name: a prefix macro, run with the line number and operand
+    User { name: String, operand: String },
 }
 
 /// Types of block operations
@@ -40,6 +55,9 @@ pub enum BlockType {
     Copy,
     Move,
     Duplicate,
+    Exclude,
+    /// Marked by a prefix macro with SET PENDING BLOCK name
+    User(String),
 }
 
 /// A pending block operation waiting for the closing marker
@@ -87,7 +105,13 @@ impl PrefixCommand {
             ">" => Some(PrefixCommand::ShiftRight(2)),
             "<" => Some(PrefixCommand::ShiftLeft(2)),
             "i" | "a" => Some(PrefixCommand::Insert(1)),
+            "x" => Some(PrefixCommand::Exclude(1)),
+            "xx" => Some(PrefixCommand::ExcludeBlock),
+            "s" => Some(PrefixCommand::Show(None)),
             _ => {
+                if input.starts_with('.') {
+                    return parse_point_name(input).ok().map(PrefixCommand::Point);
+                }
                 if let Some(rest) = lower.strip_prefix('i') {
                     if let Ok(n) = rest.parse::<usize>() {
                         return Some(PrefixCommand::Insert(n));
@@ -98,6 +122,16 @@ impl PrefixCommand {
                         return Some(PrefixCommand::Add(n));
                     }
                 }
+                if let Some(rest) = lower.strip_prefix('x') {
+                    if let Ok(n) = rest.parse::<usize>() {
+                        return Some(PrefixCommand::Exclude(n));
+                    }
+                }
+                if let Some(rest) = lower.strip_prefix('s') {
+                    if let Ok(n) = rest.parse::<i64>() {
+                        return Some(PrefixCommand::Show(Some(n)));
+                    }
+                }
                 if let Some(rest) = lower.strip_prefix('"') {
                     if let Ok(n) = rest.parse::<usize>() {
                         return Some(PrefixCommand::Duplicate(n));
@@ -118,6 +152,32 @@ impl PrefixCommand {
         }
     }
 
+    /// Parse prefix area text, first replacing a SET PREFIX SYNONYM name
+    /// with the name it stands for. Names that are not built in are taken
+    /// as prefix macros if `is_macro` finds one by that name.
+    pub fn parse_with_synonyms(
+        input: &str,
+        synonyms: &HashMap<String, String>,
+        is_macro: impl Fn(&str) -> bool,
+    ) -> Option<Self> {
+        let input = input.trim();
+        if input.starts_with('.') {
+            return Self::parse(input);
+        }
+        let (name, operand) = split_operand(input);
+        if name.is_empty() {
+            return None;
+        }
+        let name = name.to_lowercase();
+        let name = synonyms.get(&name).cloned().unwrap_or(name);
+        Self::parse(&format!("{}{}", name, operand)).or_else(|| {
+            is_macro(&name).then(|| PrefixCommand::User {
+                name,
+                operand: operand.to_string(),
+            })
+        })
+    }
+
     pub fn is_block_marker(&self) -> bool {
         matches!(
             self,
@@ -125,6 +185,7 @@ impl PrefixCommand {
                 | PrefixCommand::CopyBlock
                 | PrefixCommand::MoveBlock
                 | PrefixCommand::DuplicateBlock
+                | PrefixCommand::ExcludeBlock
         )
     }
 
@@ -134,11 +195,29 @@ impl PrefixCommand {
             PrefixCommand::CopyBlock => Some(BlockType::Copy),
             PrefixCommand::MoveBlock => Some(BlockType::Move),
             PrefixCommand::DuplicateBlock => Some(BlockType::Duplicate),
+            PrefixCommand::ExcludeBlock => Some(BlockType::Exclude),
             _ => None,
         }
     }
 }
 
+/// Split prefix text into a name and its number operand, which may come
+/// after the name (`i5`, `s-2`) or before it (`5i`)
+fn split_operand(input: &str) -> (&str, &str) {
+    let lead = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    if lead > 0 {
+        return (&input[lead..], &input[..lead]);
+    }
+    let name = input.trim_end_matches(|c: char| c.is_ascii_digit());
+    let name = match name.strip_suffix('-') {
+        Some(rest) if !rest.is_empty() && name.len() < input.len() => rest,
+        _ => name,
+    };
+    (name, &input[name.len()..])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -167,6 +246,66 @@ mod tests {
         assert_eq!(PrefixCommand::parse("<"), Some(PrefixCommand::ShiftLeft(2)));
     }
 
+    #[test]
+    fn parse_point() {
+        assert_eq!(
+            PrefixCommand::parse(".ab1"),
+            Some(PrefixCommand::Point("AB1".into()))
+        );
+        assert_eq!(PrefixCommand::parse(".toolongname"), None);
+    }
+
+    #[test]
+    fn parse_exclude_and_show() {
+        assert_eq!(PrefixCommand::parse("x"), Some(PrefixCommand::Exclude(1)));
+        assert_eq!(PrefixCommand::parse("X3"), Some(PrefixCommand::Exclude(3)));
+        assert_eq!(
+            PrefixCommand::parse("xx"),
+            Some(PrefixCommand::ExcludeBlock)
+        );
+        assert_eq!(PrefixCommand::parse("s"), Some(PrefixCommand::Show(None)));
+        assert_eq!(
+            PrefixCommand::parse("s2"),
+            Some(PrefixCommand::Show(Some(2)))
+        );
+        assert_eq!(
+            PrefixCommand::parse("s-4"),
+            Some(PrefixCommand::Show(Some(-4)))
+        );
+        assert_eq!(PrefixCommand::parse("sx"), None);
+    }
+
+    #[test]
+    fn parse_synonyms_and_user_commands() {
+        let synonyms = HashMap::from([
+            ("k".to_string(), "d".to_string()),
+            ("q".to_string(), "zz".to_string()),
+        ]);
+        let is_macro = |name: &str| name == "zz" || name == "ccx";
+        let parse = |text| PrefixCommand::parse_with_synonyms(text, &synonyms, is_macro);
+        assert_eq!(parse("K"), Some(PrefixCommand::Delete));
+        assert_eq!(parse("5i"), Some(PrefixCommand::Insert(5)));
+        assert_eq!(parse("s-2"), Some(PrefixCommand::Show(Some(-2))));
+        assert_eq!(parse(".top"), Some(PrefixCommand::Point("TOP".into())));
+        assert_eq!(
+            parse("q3"),
+            Some(PrefixCommand::User {
+                name: "zz".into(),
+                operand: "3".into()
+            })
+        );
+        assert_eq!(
+            parse("ccx"),
+            Some(PrefixCommand::User {
+                name: "ccx".into(),
+                operand: String::new()
+            })
+        );
+        assert_eq!(parse("dd2x"), None);
+        assert_eq!(parse("42"), None);
+        assert_eq!(parse(""), None);
+    }
+
     #[test]
     fn parse_empty() {
         assert_eq!(PrefixCommand::parse(""), None);
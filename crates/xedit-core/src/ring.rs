@@ -1,6 +1,10 @@
-use crate::editor::Editor;
+use crate::editor::{Editor, LoadOptions};
 use crate::error::{Result, XeditError};
+use crate::filesystem::{FileId, FileSystem, NativeFs};
+use crate::host::Host;
+use std::cell::RefCell;
 use std::path::Path;
+use std::rc::Rc;
 
 /// The file ring — XEDIT's model for multiple open files.
 ///
@@ -10,30 +14,78 @@ use std::path::Path;
 pub struct Ring {
     editors: Vec<Editor>,
     current: usize,
+    /// Shared by every editor in the ring
+    fs: Rc<dyn FileSystem>,
+    /// Given to every editor in the ring
+    host: Option<Rc<RefCell<dyn Host>>>,
 }
 
 impl Ring {
     pub fn new() -> Self {
+        Self::with_filesystem(Rc::new(NativeFs))
+    }
+
+    /// A ring whose files are located, read and saved through `fs`
+    pub fn with_filesystem(fs: Rc<dyn FileSystem>) -> Self {
         Self {
             editors: Vec::new(),
             current: 0,
+            fs,
+            host: None,
         }
     }
 
-    /// Add a new empty editor to the ring
-    pub fn add_empty(&mut self) -> &mut Editor {
-        self.editors.push(Editor::new());
+    /// Resolve an XEDIT file operand (`fn ft [fm]` or a path)
+    pub fn locate(&self, fileid: &str) -> Result<FileId> {
+        Ok(self.fs.locate(fileid)?)
+    }
+
+    /// Send every file's macros' ADDRESS CMS commands to `host`
+    pub fn set_host(&mut self, host: Rc<RefCell<dyn Host>>) {
+        for editor in &mut self.editors {
+            editor.set_host(Rc::clone(&host));
+        }
+        self.host = Some(host);
+    }
+
+    fn new_editor(&self) -> Editor {
+        let mut editor = Editor::new();
+        editor.set_filesystem(Rc::clone(&self.fs));
+        if let Some(host) = &self.host {
+            editor.set_host(Rc::clone(host));
+        }
+        editor
+    }
+
+    fn push(&mut self, editor: Editor) -> &mut Editor {
+        self.editors.push(editor);
         self.current = self.editors.len() - 1;
         &mut self.editors[self.current]
     }
 
+    /// Add a new empty editor to the ring
+    pub fn add_empty(&mut self) -> &mut Editor {
+        let editor = self.new_editor();
+        self.push(editor)
+    }
+
     /// Add a file to the ring
     pub fn add_file(&mut self, path: &Path) -> Result<&mut Editor> {
-        let mut editor = Editor::new();
-        editor.load_file(path)?;
-        self.editors.push(editor);
-        self.current = self.editors.len() - 1;
-        Ok(&mut self.editors[self.current])
+        self.add_file_with(path, &LoadOptions::default())
+    }
+
+    /// Add a file to the ring, loading it with record format overrides
+    pub fn add_file_with(&mut self, path: &Path, opts: &LoadOptions) -> Result<&mut Editor> {
+        let mut editor = self.new_editor();
+        editor.load_file_with(path, opts)?;
+        Ok(self.push(editor))
+    }
+
+    /// Add a located file to the ring under its own fn ft fm
+    pub fn open_file(&mut self, id: &FileId, opts: &LoadOptions) -> Result<&mut Editor> {
+        let mut editor = self.new_editor();
+        editor.open_file(id, opts)?;
+        Ok(self.push(editor))
     }
 
     /// Get the current editor
@@ -68,6 +120,24 @@ impl Ring {
         Ok(())
     }
 
+    /// Make the editor at `index` the current one
+    pub fn switch_to(&mut self, index: usize) -> Result<()> {
+        if index >= self.editors.len() {
+            return Err(XeditError::NoFile);
+        }
+        self.current = index;
+        Ok(())
+    }
+
+    /// Find the ring index of an editor holding the given file
+    pub fn find_file(&self, path: &Path) -> Option<usize> {
+        let wanted = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.editors.iter().position(|ed| {
+            ed.filepath()
+                .is_some_and(|p| p.canonicalize().unwrap_or_else(|_| p.to_path_buf()) == wanted)
+        })
+    }
+
     /// Remove the current editor from the ring
     pub fn remove_current(&mut self) {
         if !self.editors.is_empty() {
@@ -144,7 +214,7 @@ mod tests {
         assert_eq!(ring.len(), 1);
 
         let editor = ring.current().unwrap();
-        assert_eq!(editor.buffer().lines().len(), 2);
+        assert_eq!(editor.buffer().len(), 2);
     }
 
     #[test]
@@ -250,6 +320,34 @@ mod tests {
         assert!(empty_ring.current_mut().is_none());
     }
 
+    #[test]
+    fn switch_to_index() {
+        let mut ring = Ring::new();
+        ring.add_empty();
+        ring.add_empty();
+        ring.switch_to(0).unwrap();
+        assert_eq!(ring.current_index(), 0);
+        assert!(ring.switch_to(2).is_err());
+        assert_eq!(ring.current_index(), 0);
+    }
+
+    #[test]
+    fn find_file_in_ring() {
+        let mut first = NamedTempFile::new().unwrap();
+        writeln!(first, "one").unwrap();
+        let mut second = NamedTempFile::new().unwrap();
+        writeln!(second, "two").unwrap();
+
+        let mut ring = Ring::new();
+        ring.add_file(first.path()).unwrap();
+        ring.add_file(second.path()).unwrap();
+        ring.add_empty();
+
+        assert_eq!(ring.find_file(first.path()), Some(0));
+        assert_eq!(ring.find_file(second.path()), Some(1));
+        assert_eq!(ring.find_file(Path::new("/tmp/not_in_ring.txt")), None);
+    }
+
     #[test]
     fn remove_all_then_add() {
         let mut ring = Ring::new();
@@ -0,0 +1,293 @@
+//! Line storage for large files.
+//!
+//! `LineRope` keeps lines in a sequence of small chunks, with the index of
+//! each chunk's first line alongside. Finding a line is a binary search over
+//! the chunk starts, and inserting or deleting lines only shifts the lines
+//! of the chunks involved instead of the whole file. Chunks split as they
+//! grow and merge with a neighbour when deletes leave them small.
+
+use std::ops::Range;
+
+use crate::buffer::Line;
+
+/// A chunk that grows past this many lines is split
+const CHUNK_MAX: usize = 1024;
+
+/// Lines per chunk when a file is loaded or a chunk is split
+const CHUNK_FILL: usize = CHUNK_MAX / 2;
+
+/// A chunk that shrinks below this many lines is merged with a neighbour
+const CHUNK_MIN: usize = CHUNK_MAX / 4;
+
+/// Chunked line sequence with 0-based indexes
+#[derive(Debug, Default)]
+pub struct LineRope {
+    chunks: Vec<Vec<Line>>,
+    /// `starts[i]` is the index of the first line of `chunks[i]`
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineRope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_lines(lines: Vec<Line>) -> Self {
+        let mut rope = Self::new();
+        rope.len = lines.len();
+        rope.chunks = split_chunks(lines);
+        rope.rebuild_starts(0);
+        rope
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Line> {
+        let (chunk, offset) = self.locate(index)?;
+        self.chunks[chunk].get(offset)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Line> {
+        let (chunk, offset) = self.locate(index)?;
+        self.chunks[chunk].get_mut(offset)
+    }
+
+    /// Insert `lines` so the first of them ends up at `index` (clamped to the end)
+    pub fn insert(&mut self, index: usize, lines: Vec<Line>) {
+        if lines.is_empty() {
+            return;
+        }
+        let index = index.min(self.len);
+        let count = lines.len();
+        let (chunk, offset) = match self.locate(index) {
+            Some(found) => found,
+            None if self.chunks.is_empty() => {
+                self.chunks.push(Vec::new());
+                (0, 0)
+            }
+            // Appending: extend the last chunk
+            None => {
+                let last = self.chunks.len() - 1;
+                (last, self.chunks[last].len())
+            }
+        };
+        let target = &mut self.chunks[chunk];
+        target.splice(offset..offset, lines);
+        if target.len() > CHUNK_MAX {
+            let pieces = split_chunks(std::mem::take(target));
+            self.chunks.splice(chunk..=chunk, pieces);
+        }
+        self.len += count;
+        self.rebuild_starts(chunk);
+    }
+
+    /// Remove the lines in `range` (clamped to the end) and return them
+    pub fn remove(&mut self, range: Range<usize>) -> Vec<Line> {
+        let end = range.end.min(self.len);
+        let Some((first, offset)) = self.locate(range.start).filter(|_| range.start < end) else {
+            return Vec::new();
+        };
+        let mut removed = Vec::with_capacity(end - range.start);
+        let mut chunk = first;
+        let mut from = offset;
+        let mut left = end - range.start;
+        while left > 0 {
+            let lines = &mut self.chunks[chunk];
+            let take = left.min(lines.len() - from);
+            removed.extend(lines.drain(from..from + take));
+            left -= take;
+            chunk += 1;
+            from = 0;
+        }
+        self.len -= removed.len();
+        // Drop chunks emptied by the removal
+        let touched: Vec<Vec<Line>> = self
+            .chunks
+            .drain(first..chunk)
+            .filter(|lines| !lines.is_empty())
+            .collect();
+        self.chunks.splice(first..first, touched);
+        // What is left of the last and first chunks touched
+        self.merge_if_small(first + 1);
+        self.merge_if_small(first);
+        self.rebuild_starts(first.saturating_sub(1));
+        removed
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Line> {
+        self.chunks.iter().flatten()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Line> {
+        self.chunks.iter_mut().flatten()
+    }
+
+    /// Chunk and offset of the line at `index`
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        let chunk = self.starts.partition_point(|&start| start <= index) - 1;
+        Some((chunk, index - self.starts[chunk]))
+    }
+
+    /// Join the chunk at `chunk` to its next (or, for the last chunk,
+    /// previous) neighbour if it has fewer than `CHUNK_MIN` lines. Only
+    /// chunks from `chunk - 1` on change; their starts must be rebuilt.
+    fn merge_if_small(&mut self, chunk: usize) {
+        if self.chunks.len() < 2
+            || chunk >= self.chunks.len()
+            || self.chunks[chunk].len() >= CHUNK_MIN
+        {
+            return;
+        }
+        let left = if chunk + 1 < self.chunks.len() {
+            chunk
+        } else {
+            chunk - 1
+        };
+        let right = self.chunks.remove(left + 1);
+        let joined = &mut self.chunks[left];
+        joined.extend(right);
+        // At most CHUNK_MAX + CHUNK_MIN lines, so both halves stay large
+        if joined.len() > CHUNK_MAX {
+            let half = joined.split_off(joined.len() / 2);
+            self.chunks.insert(left + 1, half);
+        }
+    }
+
+    /// Recompute chunk starts from `chunk` onwards
+    fn rebuild_starts(&mut self, chunk: usize) {
+        self.starts.truncate(chunk);
+        let mut start = match chunk {
+            0 => 0,
+            n => self.starts[n - 1] + self.chunks[n - 1].len(),
+        };
+        for lines in &self.chunks[chunk..] {
+            self.starts.push(start);
+            start += lines.len();
+        }
+    }
+}
+
+/// Cut lines into chunks of `CHUNK_FILL`, leaving room for inserts
+fn split_chunks(lines: Vec<Line>) -> Vec<Vec<Line>> {
+    let mut chunks = Vec::with_capacity(lines.len().div_ceil(CHUNK_FILL));
+    let mut lines = lines.into_iter();
+    loop {
+        let chunk: Vec<Line> = lines.by_ref().take(CHUNK_FILL).collect();
+        if chunk.is_empty() {
+            return chunks;
+        }
+        chunks.push(chunk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rope_of(n: usize) -> LineRope {
+        LineRope::from_lines((0..n).map(|i| Line::new(i.to_string())).collect())
+    }
+
+    fn texts(rope: &LineRope) -> Vec<String> {
+        rope.iter().map(|l| l.text().to_string()).collect()
+    }
+
+    fn check_starts(rope: &LineRope) {
+        let mut start = 0;
+        for (chunk, lines) in rope.chunks.iter().enumerate() {
+            assert!(!lines.is_empty(), "empty chunk {}", chunk);
+            assert_eq!(rope.starts[chunk], start);
+            start += lines.len();
+        }
+        assert_eq!(start, rope.len());
+    }
+
+    #[test]
+    fn lookup_across_chunks() {
+        let rope = rope_of(3 * CHUNK_FILL + 7);
+        check_starts(&rope);
+        assert_eq!(rope.chunks.len(), 4);
+        for i in [0, CHUNK_FILL - 1, CHUNK_FILL, 3 * CHUNK_FILL + 6] {
+            assert_eq!(rope.get(i).unwrap().text(), i.to_string());
+        }
+        assert!(rope.get(3 * CHUNK_FILL + 7).is_none());
+    }
+
+    #[test]
+    fn insert_splits_large_chunks() {
+        let mut rope = rope_of(10);
+        let many = (0..CHUNK_MAX * 2).map(|_| Line::new("x")).collect();
+        rope.insert(5, many);
+        check_starts(&rope);
+        assert_eq!(rope.len(), 10 + CHUNK_MAX * 2);
+        assert_eq!(rope.get(4).unwrap().text(), "4");
+        assert_eq!(rope.get(5).unwrap().text(), "x");
+        assert_eq!(rope.get(5 + CHUNK_MAX * 2).unwrap().text(), "5");
+
+        rope.insert(usize::MAX, vec![Line::new("end")]);
+        assert_eq!(rope.iter().last().unwrap().text(), "end");
+        check_starts(&rope);
+    }
+
+    #[test]
+    fn remove_across_chunks() {
+        let mut rope = rope_of(4 * CHUNK_FILL);
+        let removed = rope.remove(CHUNK_FILL - 2..3 * CHUNK_FILL + 2);
+        check_starts(&rope);
+        assert_eq!(removed.len(), 2 * CHUNK_FILL + 4);
+        assert_eq!(removed[0].text(), (CHUNK_FILL - 2).to_string());
+        assert_eq!(rope.len(), 2 * CHUNK_FILL - 4);
+        assert_eq!(
+            rope.get(CHUNK_FILL - 2).unwrap().text(),
+            (3 * CHUNK_FILL + 2).to_string()
+        );
+
+        let removed = rope.remove(0..usize::MAX);
+        assert_eq!(removed.len(), 2 * CHUNK_FILL - 4);
+        assert!(rope.is_empty());
+        check_starts(&rope);
+        rope.insert(0, vec![Line::new("again")]);
+        assert_eq!(texts(&rope), ["again"]);
+    }
+
+    #[test]
+    fn deletes_merge_small_chunks() {
+        let mut rope = rope_of(4 * CHUNK_FILL);
+        // Delete most of the second chunk a line at a time
+        for _ in 0..CHUNK_FILL - 1 {
+            rope.remove(CHUNK_FILL..CHUNK_FILL + 1);
+            check_starts(&rope);
+        }
+        assert_eq!(rope.chunks.len(), 3);
+        assert!(rope.chunks.iter().all(|c| c.len() >= CHUNK_MIN));
+        assert_eq!(
+            rope.get(CHUNK_FILL).unwrap().text(),
+            (2 * CHUNK_FILL - 1).to_string()
+        );
+
+        // The last chunk merges backwards
+        let len = rope.len();
+        rope.remove(CHUNK_FILL + 1..len - 1);
+        check_starts(&rope);
+        assert_eq!(rope.chunks.len(), 1);
+        assert_eq!(rope.len(), CHUNK_FILL + 2);
+    }
+
+    #[test]
+    fn remove_whole_first_chunk() {
+        let mut rope = rope_of(2 * CHUNK_FILL);
+        rope.remove(0..CHUNK_FILL);
+        check_starts(&rope);
+        assert_eq!(rope.get(0).unwrap().text(), CHUNK_FILL.to_string());
+    }
+}
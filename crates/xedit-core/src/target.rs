@@ -1,8 +1,19 @@
+use std::borrow::Cow;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::codepage::{hex_literal, Codepage};
+
 /// A target specifies a location in XEDIT's addressing system.
 ///
 /// Targets are one of XEDIT's most distinctive features, allowing
 /// precise addressing by line number, relative offset, or string search.
 /// Compound targets use `&` (AND) and `|` (OR) to combine conditions.
+/// A string target prefixed with `RE` is a regular expression (`RE/a+b/`)
+/// and one prefixed with `WORD` a whole-word match (`WORD/cat/`); text
+/// between the delimiters is always literal, so `/re:x/` finds "re:x".
+/// A leading `~` (or `¬`) negates a string target.
+/// `.name` addresses a line named with SET POINT.
 #[derive(Debug, Clone)]
 pub enum Target {
     /// Absolute line number `:n`
@@ -13,8 +24,20 @@ pub enum Target {
     StringForward(String),
     /// String search backward `-/string/`
     StringBackward(String),
+    /// Regular expression search forward `RE/pattern/`
+    RegexForward(RegexTarget),
+    /// Regular expression search backward `-RE/pattern/`
+    RegexBackward(RegexTarget),
+    /// Whole-word search forward `WORD/text/`
+    WordForward(String),
+    /// Whole-word search backward `-WORD/text/`
+    WordBackward(String),
+    /// Line that does not match `~/string/`; searches in the inner direction
+    Not(Box<Target>),
     /// All remaining lines `*`
     Star,
+    /// Line named with SET POINT `.name` (name kept in uppercase)
+    Point(String),
     /// Both targets must match the line
     And(Box<Target>, Box<Target>),
     /// Either target must match the line
@@ -36,22 +59,18 @@ impl Target {
     /// This is used by ALL filtering and compound target resolution.
     /// Only string-based targets (forward/backward search, And, Or) are meaningful;
     /// positional targets (Absolute, Relative, Star) always return false.
-    pub fn matches_line(&self, case_respect: bool, line_text: &str) -> bool {
+    pub fn matches_line(&self, opts: &MatchOptions, line_text: &str) -> bool {
         match self {
             Target::StringForward(s) | Target::StringBackward(s) => {
-                let (needle, haystack) = if case_respect {
-                    (s.as_str().to_string(), line_text.to_string())
-                } else {
-                    (s.to_uppercase(), line_text.to_uppercase())
-                };
-                haystack.contains(&needle)
-            }
-            Target::And(a, b) => {
-                a.matches_line(case_respect, line_text) && b.matches_line(case_respect, line_text)
+                opts.find(line_text, s).is_some()
             }
-            Target::Or(a, b) => {
-                a.matches_line(case_respect, line_text) || b.matches_line(case_respect, line_text)
+            Target::RegexForward(re) | Target::RegexBackward(re) => re.is_match(opts, line_text),
+            Target::WordForward(w) | Target::WordBackward(w) => {
+                opts.find_word(line_text, w).is_some()
             }
+            Target::Not(inner) => !inner.matches_line(opts, line_text),
+            Target::And(a, b) => a.matches_line(opts, line_text) && b.matches_line(opts, line_text),
+            Target::Or(a, b) => a.matches_line(opts, line_text) || b.matches_line(opts, line_text),
             // Positional targets don't match by content
             _ => false,
         }
@@ -64,9 +83,10 @@ impl Target {
         &self,
         current_line: usize,
         buffer_len: usize,
-        case_respect: bool,
+        opts: &MatchOptions,
         line_text_fn: &dyn Fn(usize) -> Option<String>,
     ) -> Option<usize> {
+        let matches = |i: usize| line_text_fn(i).is_some_and(|text| self.matches_line(opts, &text));
         match self {
             Target::Absolute(n) => {
                 if *n <= buffer_len {
@@ -83,61 +103,306 @@ impl Target {
                     None
                 }
             }
-            Target::StringForward(s) => {
-                let needle = if case_respect {
-                    s.clone()
-                } else {
-                    s.to_uppercase()
-                };
-                for i in (current_line + 1)..=buffer_len {
-                    if let Some(text) = line_text_fn(i) {
-                        let haystack = if case_respect {
-                            text
-                        } else {
-                            text.to_uppercase()
-                        };
-                        if haystack.contains(&needle) {
-                            return Some(i);
-                        }
-                    }
-                }
-                None
+            Target::Star => Some(buffer_len),
+            // Named lines live in the buffer; the editor looks them up
+            Target::Point(_) => None,
+            _ if self.is_backward() => (1..current_line).rev().find(|&i| matches(i)),
+            // String and compound conditions search forward
+            _ => ((current_line + 1)..=buffer_len).find(|&i| matches(i)),
+        }
+    }
+
+    /// Resolve with SET WRAP ON semantics.
+    ///
+    /// A content search that reaches the end of the file continues from the
+    /// top down to the current line (backward searches continue from the
+    /// bottom up to it). Returns the line and whether the search wrapped.
+    pub fn resolve_wrapped(
+        &self,
+        current_line: usize,
+        buffer_len: usize,
+        opts: &MatchOptions,
+        line_text_fn: &dyn Fn(usize) -> Option<String>,
+    ) -> Option<(usize, bool)> {
+        if let Some(line) = self.resolve(current_line, buffer_len, opts, line_text_fn) {
+            return Some((line, false));
+        }
+        if matches!(
+            self,
+            Target::Absolute(_) | Target::Relative(_) | Target::Star | Target::Point(_)
+        ) {
+            return None;
+        }
+        let matches = |i: usize| line_text_fn(i).is_some_and(|text| self.matches_line(opts, &text));
+        let current = current_line.min(buffer_len);
+        let found = if self.is_backward() {
+            (current.max(1)..=buffer_len).rev().find(|&i| matches(i))
+        } else {
+            (1..=current).find(|&i| matches(i))
+        };
+        found.map(|line| (line, true))
+    }
+
+    /// Whether this content target searches toward the top of the file
+    pub fn is_backward(&self) -> bool {
+        match self {
+            Target::StringBackward(_) | Target::RegexBackward(_) | Target::WordBackward(_) => true,
+            Target::Not(inner) => inner.is_backward(),
+            _ => false,
+        }
+    }
+
+    /// Turn a forward content target into its backward form (`-` prefix)
+    fn into_backward(self) -> Result<Self, String> {
+        match self {
+            Target::StringForward(s) => Ok(Target::StringBackward(s)),
+            Target::RegexForward(re) => Ok(Target::RegexBackward(re)),
+            Target::WordForward(w) => Ok(Target::WordBackward(w)),
+            Target::Not(inner) => Ok(Target::Not(Box::new(inner.into_backward()?))),
+            other => Err(format!("Invalid backward target: {:?}", other)),
+        }
+    }
+}
+
+/// A compiled regular-expression target.
+///
+/// Both case variants are compiled up front so that SET CASE can change
+/// between searches without recompiling.
+#[derive(Debug, Clone)]
+pub struct RegexTarget {
+    exact: Regex,
+    caseless: Regex,
+}
+
+impl RegexTarget {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let build = |caseless: bool| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(caseless)
+                .build()
+                .map_err(|e| format!("Invalid regular expression: {}", e))
+        };
+        Ok(Self {
+            exact: build(false)?,
+            caseless: build(true)?,
+        })
+    }
+
+    /// The pattern source text
+    pub fn as_str(&self) -> &str {
+        self.exact.as_str()
+    }
+
+    fn regex(&self, opts: &MatchOptions) -> &Regex {
+        if opts.case_respect {
+            &self.exact
+        } else {
+            &self.caseless
+        }
+    }
+
+    /// Whether the pattern matches inside the zone of `text`
+    pub fn is_match(&self, opts: &MatchOptions, text: &str) -> bool {
+        let (start, end) = opts.zone_bounds(text);
+        self.regex(opts).is_match(&text[start..end])
+    }
+}
+
+/// Editor settings that control how string targets match line text.
+#[derive(Debug, Clone)]
+pub struct MatchOptions {
+    /// SET CASE RESPECT: compare case exactly
+    pub case_respect: bool,
+    /// SET ZONE: 1-based inclusive columns searched (right may be `usize::MAX`)
+    pub zone: (usize, usize),
+    /// SET ARBCHAR: when ON, this character stands for any run of characters
+    pub arbchar: Option<char>,
+    /// SET HEX: when ON, `X'..'` strings are bytes in this code page
+    pub hex: Option<Codepage>,
+}
+
+/// A successful string match: the byte range matched, plus the byte range
+/// covered by each arbitrary character in the search string, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringMatch {
+    pub start: usize,
+    pub end: usize,
+    pub spans: Vec<(usize, usize)>,
+}
+
+impl MatchOptions {
+    /// Byte range of `text` that lies inside the zone
+    pub fn zone_bounds(&self, text: &str) -> (usize, usize) {
+        let (left, right) = self.zone;
+        let byte_at = |col: usize| {
+            text.char_indices()
+                .nth(col)
+                .map(|(i, _)| i)
+                .unwrap_or(text.len())
+        };
+        let start = byte_at(left.saturating_sub(1));
+        let end = if right == usize::MAX {
+            text.len()
+        } else {
+            byte_at(right).max(start)
+        };
+        (start, end)
+    }
+
+    /// Find `needle` inside the zone of `text`.
+    ///
+    /// Returns the byte range of the first match. Comparison is per character,
+    /// so case folding never shifts byte offsets in the original text.
+    pub fn find(&self, text: &str, needle: &str) -> Option<(usize, usize)> {
+        self.find_from(text, needle, 0)
+    }
+
+    /// Like `find`, starting at byte offset `from` (clamped to the zone)
+    pub fn find_from(&self, text: &str, needle: &str, from: usize) -> Option<(usize, usize)> {
+        self.find_match(text, needle, from)
+            .map(|m| (m.start, m.end))
+    }
+
+    /// Like `find_from`, also reporting what each arbitrary character matched.
+    ///
+    /// An arbitrary character matches the shortest run that lets the rest of
+    /// the string match, except at the end of the string where it runs to the
+    /// end of the zone.
+    pub fn find_match(&self, text: &str, needle: &str, from: usize) -> Option<StringMatch> {
+        let needle = self.decode_hex(needle);
+        let needle = needle.as_ref();
+        let (zone_start, zone_end) = self.zone_bounds(text);
+        let start = from.max(zone_start);
+        let pieces: Vec<&str> = match self.arbchar {
+            Some(arb) if needle.contains(arb) => needle.split(arb).collect(),
+            _ => {
+                return self
+                    .find_literal(text, needle, start, zone_end)
+                    .map(|(start, end)| StringMatch {
+                        start,
+                        end,
+                        spans: Vec::new(),
+                    })
             }
-            Target::StringBackward(s) => {
-                if current_line == 0 {
-                    return None;
-                }
-                let needle = if case_respect {
-                    s.clone()
-                } else {
-                    s.to_uppercase()
-                };
-                for i in (1..current_line).rev() {
-                    if let Some(text) = line_text_fn(i) {
-                        let haystack = if case_respect {
-                            text
-                        } else {
-                            text.to_uppercase()
-                        };
-                        if haystack.contains(&needle) {
-                            return Some(i);
-                        }
-                    }
-                }
-                None
+        };
+
+        let (first, rest) = pieces.split_first()?;
+        let (match_start, mut cursor) = self.find_literal(text, first, start, zone_end)?;
+        let mut spans = Vec::new();
+        for (i, piece) in rest.iter().enumerate() {
+            if piece.is_empty() && i == rest.len() - 1 {
+                spans.push((cursor, zone_end));
+                cursor = zone_end;
+            } else {
+                let (s, e) = self.find_literal(text, piece, cursor, zone_end)?;
+                spans.push((cursor, s));
+                cursor = e;
             }
-            Target::Star => Some(buffer_len),
-            Target::And(_, _) | Target::Or(_, _) => {
-                // Search forward for first line satisfying compound condition
-                for i in (current_line + 1)..=buffer_len {
-                    if let Some(text) = line_text_fn(i) {
-                        if self.matches_line(case_respect, &text) {
-                            return Some(i);
-                        }
+        }
+        Some(StringMatch {
+            start: match_start,
+            end: cursor,
+            spans,
+        })
+    }
+
+    /// With SET HEX ON, turn an `X'..'` string into the characters it encodes
+    pub fn decode_hex<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        match (self.hex, hex_literal(s)) {
+            (Some(cp), Some(bytes)) => Cow::Owned(cp.decode(&bytes)),
+            _ => Cow::Borrowed(s),
+        }
+    }
+
+    /// Literal search for `needle` in `text[start..zone_end]`
+    fn find_literal(
+        &self,
+        text: &str,
+        needle: &str,
+        start: usize,
+        zone_end: usize,
+    ) -> Option<(usize, usize)> {
+        if start > zone_end {
+            return None;
+        }
+        let hay = &text[..zone_end];
+        if needle.is_ascii() && hay.is_ascii() {
+            // Byte comparison gives the same answer for pure ASCII, much faster
+            let (bytes, pattern) = (hay.as_bytes(), needle.as_bytes());
+            if pattern.is_empty() {
+                return Some((start, start));
+            }
+            return bytes[start..]
+                .windows(pattern.len())
+                .position(|w| {
+                    if self.case_respect {
+                        w == pattern
+                    } else {
+                        w.eq_ignore_ascii_case(pattern)
                     }
-                }
-                None
+                })
+                .map(|i| (start + i, start + i + pattern.len()));
+        }
+        hay[start..]
+            .char_indices()
+            .map(|(i, _)| start + i)
+            .chain(std::iter::once(zone_end))
+            .find_map(|pos| {
+                self.match_at(&hay[pos..], needle)
+                    .map(|len| (pos, pos + len))
+            })
+    }
+
+    /// If `needle` matches at the start of `hay`, the byte length matched
+    fn match_at(&self, hay: &str, needle: &str) -> Option<usize> {
+        let mut hay_chars = hay.char_indices();
+        let mut matched = 0;
+        for n in needle.chars() {
+            let (i, h) = hay_chars.next()?;
+            if !self.chars_eq(h, n) {
+                return None;
+            }
+            matched = i + h.len_utf8();
+        }
+        Some(matched)
+    }
+
+    /// Find `word` inside the zone of `text` as a whole word: the characters
+    /// on either side of the match must not be alphanumeric or `_`.
+    pub fn find_word(&self, text: &str, word: &str) -> Option<(usize, usize)> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let mut from = 0;
+        while let Some((start, end)) = self.find_from(text, word, from) {
+            let before = text[..start].chars().next_back();
+            let after = text[end..].chars().next();
+            if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+                return Some((start, end));
             }
+            from = start + text[start..].chars().next().map_or(1, char::len_utf8);
+        }
+        None
+    }
+
+    fn chars_eq(&self, a: char, b: char) -> bool {
+        if a == b {
+            true
+        } else if self.case_respect {
+            false
+        } else if a.is_ascii() && b.is_ascii() {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a.to_uppercase().eq(b.to_uppercase())
+        }
+    }
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            case_respect: false,
+            zone: (1, usize::MAX),
+            arbchar: None,
+            hex: None,
         }
     }
 }
@@ -197,6 +462,11 @@ fn parse_simple(input: &str) -> Result<Target, String> {
             .map_err(|_| format!("Invalid line number: {}", rest));
     }
 
+    // Named line .name
+    if input.starts_with('.') {
+        return parse_point_name(input).map(Target::Point);
+    }
+
     // Relative positive +n
     if let Some(rest) = input.strip_prefix('+') {
         if rest.is_empty() {
@@ -208,11 +478,21 @@ fn parse_simple(input: &str) -> Result<Target, String> {
             .map_err(|_| format!("Invalid offset: +{}", rest));
     }
 
+    // Negated string target ~/string/
+    if let Some(rest) = input.strip_prefix('~').or_else(|| input.strip_prefix('¬')) {
+        let inner = parse_simple(rest)?;
+        return match inner {
+            Target::Absolute(_) | Target::Relative(_) | Target::Star | Target::Point(_) => Err(
+                format!("Invalid target: {} (~ needs a string target)", input),
+            ),
+            inner => Ok(Target::Not(Box::new(inner))),
+        };
+    }
+
     // Negative: relative number or backward search
     if let Some(rest) = input.strip_prefix('-') {
-        if rest.starts_with('/') {
-            let search_str = extract_delimited(rest, '/')?;
-            return Ok(Target::StringBackward(search_str));
+        if rest.starts_with(['/', '~', '¬']) || starts_with_search_keyword(rest) {
+            return parse_simple(rest)?.into_backward();
         }
         return rest
             .parse::<i64>()
@@ -222,8 +502,16 @@ fn parse_simple(input: &str) -> Result<Target, String> {
 
     // Forward string search /string/
     if input.starts_with('/') {
-        let search_str = extract_delimited(input, '/')?;
-        return Ok(Target::StringForward(search_str));
+        return Ok(Target::StringForward(extract_delimited(input, '/')?));
+    }
+
+    // Regular expression RE/pattern/ and whole word WORD/text/
+    if let Some(rest) = strip_keyword(input, "RE") {
+        let pattern = extract_delimited(rest, '/')?;
+        return Ok(Target::RegexForward(RegexTarget::new(&pattern)?));
+    }
+    if let Some(rest) = strip_keyword(input, "WORD") {
+        return Ok(Target::WordForward(extract_delimited(rest, '/')?));
     }
 
     // Plain number = relative forward
@@ -234,6 +522,87 @@ fn parse_simple(input: &str) -> Result<Target, String> {
     Err(format!("Invalid target: {}", input))
 }
 
+/// Strip a search keyword (`RE`, `WORD`) that is followed directly by `/`,
+/// in any case
+fn strip_keyword<'a>(input: &'a str, keyword: &str) -> Option<&'a str> {
+    let (head, rest) = input.split_at_checked(keyword.len())?;
+    (head.eq_ignore_ascii_case(keyword) && rest.starts_with('/')).then_some(rest)
+}
+
+/// Whether `input` starts with a keyword string target (`RE/.../`,
+/// `WORD/.../`)
+pub fn starts_with_search_keyword(input: &str) -> bool {
+    strip_keyword(input, "RE").is_some() || strip_keyword(input, "WORD").is_some()
+}
+
+/// Parse a SET POINT name: a period followed by 1 to 8 letters or digits.
+/// Names are not case sensitive and are returned in uppercase, without
+/// the period.
+pub fn parse_point_name(input: &str) -> Result<String, String> {
+    match input.strip_prefix('.') {
+        Some(name)
+            if (1..=8).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            Ok(name.to_ascii_uppercase())
+        }
+        _ => Err(format!("Invalid point name: {}", input)),
+    }
+}
+
+/// A column target for the C-family commands (CLOCATE, CDELETE).
+///
+/// Columns are counted from 1 and relative to the column pointer; string
+/// targets search the line from the column pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnTarget {
+    /// Absolute column `:n`
+    Absolute(usize),
+    /// Relative column offset `+n`, `-n` or plain `n`
+    Relative(i64),
+    /// String search forward `/string/`
+    StringForward(String),
+    /// String search backward `-/string/`
+    StringBackward(String),
+    /// One past the end of the line `*`
+    Star,
+}
+
+impl ColumnTarget {
+    /// Parse a column target specification from a string
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err("Empty column target".to_string());
+        }
+        if input == "*" {
+            return Ok(ColumnTarget::Star);
+        }
+        if let Some(rest) = input.strip_prefix(':') {
+            return match rest.parse::<usize>() {
+                Ok(n) if n >= 1 => Ok(ColumnTarget::Absolute(n)),
+                _ => Err(format!("Invalid column number: {}", rest)),
+            };
+        }
+        if input.starts_with('/') || input.starts_with("-/") {
+            let backward = input.starts_with('-');
+            let search = extract_delimited(input.trim_start_matches('-'), '/')?;
+            if search.is_empty() {
+                return Err(format!("Invalid column target: {}", input));
+            }
+            return Ok(if backward {
+                ColumnTarget::StringBackward(search)
+            } else {
+                ColumnTarget::StringForward(search)
+            });
+        }
+        let number = input.strip_prefix('+').unwrap_or(input);
+        number
+            .parse::<i64>()
+            .map(ColumnTarget::Relative)
+            .map_err(|_| format!("Invalid column target: {}", input))
+    }
+}
+
 fn extract_delimited(input: &str, delim: char) -> Result<String, String> {
     let rest = &input[delim.len_utf8()..];
     if let Some(end) = rest.find(delim) {
@@ -244,6 +613,7 @@ fn extract_delimited(input: &str, delim: char) -> Result<String, String> {
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
 
@@ -298,16 +668,20 @@ mod tests {
     #[test]
     fn resolve_forward_search() {
         let lines = vec!["alpha", "beta", "gamma", "delta"];
-        let result = Target::StringForward("gamma".into())
-            .resolve(1, 4, false, &|n| lines.get(n - 1).map(|s| s.to_string()));
+        let result =
+            Target::StringForward("gamma".into()).resolve(1, 4, &MatchOptions::default(), &|n| {
+                lines.get(n - 1).map(|s| s.to_string())
+            });
         assert_eq!(result, Some(3));
     }
 
     #[test]
     fn resolve_backward_search() {
         let lines = vec!["alpha", "beta", "gamma", "delta"];
-        let result = Target::StringBackward("alpha".into())
-            .resolve(3, 4, false, &|n| lines.get(n - 1).map(|s| s.to_string()));
+        let result =
+            Target::StringBackward("alpha".into()).resolve(3, 4, &MatchOptions::default(), &|n| {
+                lines.get(n - 1).map(|s| s.to_string())
+            });
         assert_eq!(result, Some(1));
     }
 
@@ -373,7 +747,9 @@ mod tests {
             "hello world again",
         ];
         let target = Target::parse("/hello/&/world/").unwrap();
-        let result = target.resolve(0, 4, false, &|n| lines.get(n - 1).map(|s| s.to_string()));
+        let result = target.resolve(0, 4, &MatchOptions::default(), &|n| {
+            lines.get(n - 1).map(|s| s.to_string())
+        });
         assert_eq!(result, Some(1)); // "hello world" matches both
     }
 
@@ -381,7 +757,224 @@ mod tests {
     fn resolve_or_target() {
         let lines = vec!["alpha", "beta", "gamma"];
         let target = Target::parse("/beta/|/gamma/").unwrap();
-        let result = target.resolve(0, 3, false, &|n| lines.get(n - 1).map(|s| s.to_string()));
+        let result = target.resolve(0, 3, &MatchOptions::default(), &|n| {
+            lines.get(n - 1).map(|s| s.to_string())
+        });
         assert_eq!(result, Some(2)); // "beta" matches first
     }
+
+    #[test]
+    fn find_respects_zone() {
+        let opts = MatchOptions {
+            zone: (3, 6),
+            ..MatchOptions::default()
+        };
+        assert_eq!(opts.find("abcdefgh", "cd"), Some((2, 4)));
+        assert_eq!(opts.find("abcdefgh", "ab"), None);
+        // A match must end inside the zone
+        assert_eq!(opts.find("abcdefgh", "fg"), None);
+        assert_eq!(opts.find("ab", "x"), None);
+    }
+
+    #[test]
+    fn find_ignores_case_without_shifting_offsets() {
+        let opts = MatchOptions::default();
+        assert_eq!(opts.find("Straße X", "x"), Some((8, 9)));
+        let exact = MatchOptions {
+            case_respect: true,
+            ..MatchOptions::default()
+        };
+        assert_eq!(exact.find("Hello", "hello"), None);
+    }
+
+    #[test]
+    fn resolve_search_within_zone() {
+        let lines = ["//STEP1 EXEC", "//SYSIN DD *", "  EXEC PGM"];
+        let opts = MatchOptions {
+            zone: (1, 4),
+            ..MatchOptions::default()
+        };
+        let result = Target::StringForward("EXEC".into())
+            .resolve(0, 3, &opts, &|n| lines.get(n - 1).map(|s| s.to_string()));
+        assert_eq!(result, None);
+        let result = Target::StringForward("SY".into())
+            .resolve(0, 3, &opts, &|n| lines.get(n - 1).map(|s| s.to_string()));
+        assert_eq!(result, Some(2));
+    }
+
+    fn resolve_in(target: &Target, current: usize, lines: &[&str]) -> Option<usize> {
+        target.resolve(current, lines.len(), &MatchOptions::default(), &|n| {
+            lines.get(n - 1).map(|s| s.to_string())
+        })
+    }
+
+    #[test]
+    fn parse_regex_target() {
+        match Target::parse("RE/^ab+c$/").unwrap() {
+            Target::RegexForward(re) => assert_eq!(re.as_str(), "^ab+c$"),
+            other => panic!("Expected RegexForward, got {:?}", other),
+        }
+        match Target::parse("-re/x|y/").unwrap() {
+            Target::RegexBackward(re) => assert_eq!(re.as_str(), "x|y"),
+            other => panic!("Expected RegexBackward, got {:?}", other),
+        }
+        assert!(Target::parse("RE/(unclosed/").is_err());
+    }
+
+    #[test]
+    fn keyword_text_inside_delimiters_is_literal() {
+        let lines = ["foo", "re:foo", "word:cat"];
+        let target = Target::parse("/re:foo/").unwrap();
+        assert_eq!(resolve_in(&target, 0, &lines), Some(2));
+        let target = Target::parse("/word:cat/").unwrap();
+        assert_eq!(resolve_in(&target, 0, &lines), Some(3));
+        assert!(Target::parse("REX/a/").is_err());
+    }
+
+    #[test]
+    fn resolve_wrapped_forward_and_backward() {
+        let lines = ["alpha", "beta", "gamma", "beta"];
+        let get = |n: usize| lines.get(n - 1).map(|s| s.to_string());
+        let opts = MatchOptions::default();
+
+        let target = Target::parse("/alpha/").unwrap();
+        assert_eq!(target.resolve_wrapped(2, 4, &opts, &get), Some((1, true)));
+        let target = Target::parse("/beta/").unwrap();
+        assert_eq!(target.resolve_wrapped(1, 4, &opts, &get), Some((2, false)));
+        let target = Target::parse("-/gamma/").unwrap();
+        assert_eq!(target.resolve_wrapped(2, 4, &opts, &get), Some((3, true)));
+        let target = Target::parse("/gamma/&/x/").unwrap();
+        assert_eq!(target.resolve_wrapped(2, 4, &opts, &get), None);
+        assert_eq!(Target::Absolute(9).resolve_wrapped(2, 4, &opts, &get), None);
+    }
+
+    #[test]
+    fn resolve_regex_target() {
+        let lines = ["alpha 1", "beta", "GAMMA 22"];
+        let target = Target::parse("RE/gamma \\d+/").unwrap();
+        assert_eq!(resolve_in(&target, 0, &lines), Some(3));
+        let target = Target::parse("-RE/^a/").unwrap();
+        assert_eq!(resolve_in(&target, 3, &lines), Some(1));
+    }
+
+    #[test]
+    fn regex_respects_case_and_zone() {
+        let target = Target::parse("RE/^abc/").unwrap();
+        let exact = MatchOptions {
+            case_respect: true,
+            ..MatchOptions::default()
+        };
+        assert!(!target.matches_line(&exact, "ABC"));
+        assert!(target.matches_line(&MatchOptions::default(), "ABC"));
+        let zoned = MatchOptions {
+            case_respect: false,
+            zone: (3, usize::MAX),
+            ..MatchOptions::default()
+        };
+        assert!(target.matches_line(&zoned, "xxabc"));
+    }
+
+    #[test]
+    fn word_target_matches_whole_words() {
+        let lines = ["category", "the cat sat", "cat_food"];
+        let target = Target::parse("WORD/cat/").unwrap();
+        assert_eq!(resolve_in(&target, 0, &lines), Some(2));
+        assert!(!target.matches_line(&MatchOptions::default(), "cat_food"));
+        assert!(target.matches_line(&MatchOptions::default(), "cat"));
+    }
+
+    #[test]
+    fn not_target() {
+        let lines = ["# comment", "# another", "code here"];
+        let target = Target::parse("~/#/").unwrap();
+        assert_eq!(resolve_in(&target, 0, &lines), Some(3));
+        let target = Target::parse("-~/code/").unwrap();
+        assert!(target.is_backward());
+        assert_eq!(resolve_in(&target, 3, &lines), Some(2));
+        assert!(Target::parse("~:5").is_err());
+    }
+
+    #[test]
+    fn not_composes_with_and_or() {
+        let lines = ["foo bar", "foo baz", "qux"];
+        let target = Target::parse("/foo/&~/bar/").unwrap();
+        assert_eq!(resolve_in(&target, 0, &lines), Some(2));
+        let target = Target::parse("RE/^q/|word/bar/").unwrap();
+        assert_eq!(resolve_in(&target, 0, &lines), Some(1));
+    }
+
+    #[test]
+    fn arbchar_matches_any_run() {
+        let opts = MatchOptions {
+            arbchar: Some('$'),
+            ..MatchOptions::default()
+        };
+        let m = opts.find_match("xxaQQbyy", "a$b", 0).unwrap();
+        assert_eq!((m.start, m.end), (2, 6));
+        assert_eq!(m.spans, vec![(3, 5)]);
+        // Shortest run wins; a trailing arbchar runs to the end of the zone
+        assert_eq!(opts.find("ab ab", "a$b"), Some((0, 2)));
+        assert_eq!(opts.find("key=value", "=$"), Some((3, 9)));
+        assert_eq!(opts.find("abc", "a$z"), None);
+        // With ARBCHAR OFF the character is literal
+        assert_eq!(MatchOptions::default().find("a$b", "a$b"), Some((0, 3)));
+    }
+
+    #[test]
+    fn arbchar_in_string_target() {
+        let lines = ["alpha", "if x then", "end"];
+        let opts = MatchOptions {
+            arbchar: Some('$'),
+            ..MatchOptions::default()
+        };
+        let result = Target::parse("/if$then/")
+            .unwrap()
+            .resolve(0, 3, &opts, &|n| lines.get(n - 1).map(|s| s.to_string()));
+        assert_eq!(result, Some(2));
+    }
+
+    #[test]
+    fn hex_strings_need_hex_on() {
+        let opts = MatchOptions {
+            hex: Some(Codepage::Cp037),
+            ..MatchOptions::default()
+        };
+        assert_eq!(opts.find("xxAB", "X'C1C2'"), Some((2, 4)));
+        assert_eq!(MatchOptions::default().find("xxAB", "X'C1C2'"), None);
+        assert_eq!(MatchOptions::default().find("X'C1'", "X'C1'"), Some((0, 5)));
+    }
+
+    #[test]
+    fn parse_point_target() {
+        match Target::parse(".Label1").unwrap() {
+            Target::Point(name) => assert_eq!(name, "LABEL1"),
+            other => panic!("Expected Point, got {:?}", other),
+        }
+        assert!(Target::parse(".").is_err());
+        assert!(Target::parse(".a-b").is_err());
+        assert!(Target::parse("~.a").is_err());
+        // The editor resolves names; on its own a point matches nothing
+        let lines = ["a"];
+        assert_eq!(resolve_in(&Target::parse(".a").unwrap(), 0, &lines), None);
+    }
+
+    #[test]
+    fn parse_column_targets() {
+        assert_eq!(ColumnTarget::parse(":12"), Ok(ColumnTarget::Absolute(12)));
+        assert_eq!(ColumnTarget::parse("+3"), Ok(ColumnTarget::Relative(3)));
+        assert_eq!(ColumnTarget::parse("4"), Ok(ColumnTarget::Relative(4)));
+        assert_eq!(ColumnTarget::parse("-2"), Ok(ColumnTarget::Relative(-2)));
+        assert_eq!(ColumnTarget::parse("*"), Ok(ColumnTarget::Star));
+        assert_eq!(
+            ColumnTarget::parse("/ab c/"),
+            Ok(ColumnTarget::StringForward("ab c".to_string()))
+        );
+        assert_eq!(
+            ColumnTarget::parse("-/x"),
+            Ok(ColumnTarget::StringBackward("x".to_string()))
+        );
+        assert!(ColumnTarget::parse(":0").is_err());
+        assert!(ColumnTarget::parse("//").is_err());
+        assert!(ColumnTarget::parse("abc").is_err());
+    }
 }
@@ -0,0 +1,456 @@
+//! Delta-based undo/redo log.
+//!
+//! Each modifying command opens an undo group; the buffer records the
+//! individual line inserts, deletes and replacements made while the group
+//! is open, and the group is closed when the command ends. Changes made
+//! while no group is open form a group of their own. Undo reverses a group's deltas and turns the result into a
+//! redo group, so memory grows with the size of each change rather than
+//! the size of the file.
+//!
+//! The undo history can be written to a sidecar file next to the edited
+//! file. It is only reloaded if the file still matches the content it was
+//! saved with.
+
+use std::collections::VecDeque;
+use std::io;
+
+use crate::buffer::{Buffer, Delta, Line};
+
+/// Default number of undo groups kept (SET UNDO)
+pub const DEFAULT_UNDO_LIMIT: usize = 1000;
+
+const SIDECAR_MAGIC: &str = "XEDIT-UNDO 1";
+
+/// Editor position restored along with an undo group
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UndoState {
+    pub line: usize,
+    pub col: usize,
+    pub alt_count: usize,
+}
+
+/// The deltas of one command, and where the editor was before it ran
+#[derive(Debug, Clone, PartialEq)]
+struct UndoGroup {
+    deltas: Vec<Delta>,
+    state: UndoState,
+}
+
+/// Undo and redo stacks of delta groups
+#[derive(Debug)]
+pub struct UndoLog {
+    undo: VecDeque<UndoGroup>,
+    redo: Vec<UndoGroup>,
+    limit: usize,
+    /// Whether the last group still takes the buffer's changes
+    open: bool,
+}
+
+impl UndoLog {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+            open: false,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Change the number of undo groups kept; 0 turns undo off
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    /// Groups available to UNDO
+    pub fn undo_len(&self) -> usize {
+        self.undo.iter().filter(|g| !g.deltas.is_empty()).count()
+    }
+
+    /// Groups available to REDO
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Start a new undo group before a modifying command
+    pub fn begin(&mut self, buffer: &mut Buffer, state: UndoState) {
+        self.collect(buffer, state);
+        match self.undo.back_mut() {
+            // A command that changed nothing leaves its group empty; reuse it
+            Some(group) if group.deltas.is_empty() => group.state = state,
+            _ => {
+                self.undo.push_back(UndoGroup {
+                    deltas: Vec::new(),
+                    state,
+                });
+                self.trim();
+            }
+        }
+        self.open = true;
+    }
+
+    /// Close the open group once its command has finished
+    pub fn end(&mut self, buffer: &mut Buffer, state: UndoState) {
+        self.collect(buffer, state);
+        self.open = false;
+    }
+
+    /// Move the buffer's recorded changes into the open group, or into a
+    /// group of their own restoring `state` if none is open (the last
+    /// command's group, if it changed nothing). New changes
+    /// make the redo stack obsolete.
+    fn collect(&mut self, buffer: &mut Buffer, state: UndoState) {
+        let deltas = buffer.take_deltas();
+        if deltas.is_empty() || self.limit == 0 {
+            return;
+        }
+        self.redo.clear();
+        match self.undo.back_mut() {
+            Some(group) if self.open => group.deltas.extend(deltas),
+            // A closed group whose command changed nothing takes them
+            Some(group) if group.deltas.is_empty() => group.deltas = deltas,
+            _ => self.undo.push_back(UndoGroup { deltas, state }),
+        }
+        self.trim();
+    }
+
+    /// Reverse the most recent group; returns the state to restore
+    pub fn undo(&mut self, buffer: &mut Buffer, state: UndoState) -> Option<UndoState> {
+        self.collect(buffer, state);
+        self.open = false;
+        while self.undo.back().is_some_and(|g| g.deltas.is_empty()) {
+            self.undo.pop_back();
+        }
+        let group = self.undo.pop_back()?;
+        let restore = group.state;
+        self.redo.push(revert_group(buffer, group, state));
+        Some(restore)
+    }
+
+    /// Re-apply the most recently undone group; returns the state to restore
+    pub fn redo(&mut self, buffer: &mut Buffer, state: UndoState) -> Option<UndoState> {
+        self.collect(buffer, state);
+        self.open = false;
+        let group = self.redo.pop()?;
+        let restore = group.state;
+        self.undo.push_back(revert_group(buffer, group, state));
+        self.trim();
+        Some(restore)
+    }
+
+    fn trim(&mut self) {
+        // The group open for the running command does not count yet
+        let open = usize::from(self.undo.back().is_some_and(|g| g.deltas.is_empty()));
+        while self.undo.len() > self.limit + open {
+            self.undo.pop_front();
+        }
+    }
+
+    /// The undo stack as sidecar file content, tagged with the saved file's
+    /// fingerprint
+    pub fn save(&mut self, buffer: &mut Buffer, state: UndoState, fingerprint: u64) -> String {
+        self.collect(buffer, state);
+        let mut out = format!("{}\n{:016x}\n", SIDECAR_MAGIC, fingerprint);
+        for group in self.undo.iter().filter(|g| !g.deltas.is_empty()) {
+            let s = group.state;
+            out.push_str(&format!("G {} {} {}\n", s.line, s.col, s.alt_count));
+            for delta in &group.deltas {
+                match delta {
+                    Delta::Insert { at, count } => out.push_str(&format!("I {} {}\n", at, count)),
+                    Delta::Delete { at, lines, points } => {
+                        out.push_str(&format!("D {} {} {}\n", at, lines.len(), points.len()));
+                        for line in lines {
+                            out.push_str(&escape(line.text()));
+                            out.push('\n');
+                        }
+                        for (offset, name) in points {
+                            out.push_str(&format!("{} {}\n", offset, name));
+                        }
+                    }
+                    Delta::Replace { at, old } => {
+                        out.push_str(&format!("R {}\n{}\n", at, escape(old)));
+                    }
+                    Delta::Renumber { old } => {
+                        out.push_str(&format!("N {}\n", old.len()));
+                        for (name, line) in old {
+                            out.push_str(&format!("{} {}\n", line, name));
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Load an undo stack written by `save`. Returns `Ok(false)` without
+    /// changing anything if the sidecar belongs to different file content.
+    pub fn load(&mut self, content: &str, fingerprint: u64) -> io::Result<bool> {
+        let mut lines = content.lines();
+        if lines.next() != Some(SIDECAR_MAGIC) {
+            return Err(invalid("not an undo file"));
+        }
+        if lines.next() != Some(format!("{:016x}", fingerprint).as_str()) {
+            return Ok(false);
+        }
+
+        let mut groups: VecDeque<UndoGroup> = VecDeque::new();
+        while let Some(line) = lines.next() {
+            let mut fields = line.split(' ');
+            let kind = fields.next().unwrap_or_default();
+            let mut num = || -> io::Result<usize> {
+                fields
+                    .next()
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(|| invalid("bad undo record"))
+            };
+            let delta = match kind {
+                "G" => {
+                    let state = UndoState {
+                        line: num()?,
+                        col: num()?,
+                        alt_count: num()?,
+                    };
+                    groups.push_back(UndoGroup {
+                        deltas: Vec::new(),
+                        state,
+                    });
+                    continue;
+                }
+                "I" => Delta::Insert {
+                    at: num()?,
+                    count: num()?,
+                },
+                "D" => {
+                    let at = num()?;
+                    let count = num()?;
+                    let point_count = num()?;
+                    let removed = (0..count)
+                        .map(|_| lines.next().map(|line| Line::new(unescape(line))))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid("truncated undo file"))?;
+                    let points = (0..point_count)
+                        .map(|_| parse_point(lines.next()))
+                        .collect::<io::Result<Vec<_>>>()?;
+                    Delta::Delete {
+                        at,
+                        lines: removed,
+                        points,
+                    }
+                }
+                "N" => {
+                    let count = num()?;
+                    let old = (0..count)
+                        .map(|_| parse_point(lines.next()).map(|(line, name)| (name, line)))
+                        .collect::<io::Result<Vec<_>>>()?;
+                    Delta::Renumber { old }
+                }
+                "R" => {
+                    let at = num()?;
+                    let old = lines
+                        .next()
+                        .map(unescape)
+                        .ok_or_else(|| invalid("truncated undo file"))?;
+                    Delta::Replace { at, old }
+                }
+                _ => return Err(invalid("bad undo record")),
+            };
+            groups
+                .back_mut()
+                .ok_or_else(|| invalid("undo record outside a group"))?
+                .deltas
+                .push(delta);
+        }
+
+        self.undo = groups;
+        self.redo.clear();
+        self.trim();
+        Ok(true)
+    }
+}
+
+impl Default for UndoLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_UNDO_LIMIT)
+    }
+}
+
+/// Parse a `number name` point record
+fn parse_point(record: Option<&str>) -> io::Result<(usize, String)> {
+    let (number, name) = record
+        .ok_or_else(|| invalid("truncated undo file"))?
+        .split_once(' ')
+        .ok_or_else(|| invalid("bad undo record"))?;
+    let number = number.parse().map_err(|_| invalid("bad undo record"))?;
+    Ok((number, name.to_string()))
+}
+
+/// Reverse a group's deltas (newest first), producing the opposite group
+fn revert_group(buffer: &mut Buffer, group: UndoGroup, state: UndoState) -> UndoGroup {
+    let mut deltas: Vec<Delta> = group
+        .deltas
+        .into_iter()
+        .rev()
+        .map(|d| buffer.revert(d))
+        .collect();
+    deltas.reverse();
+    UndoGroup { deltas, state }
+}
+
+/// FNV-1a hash of file content, stable across runs and Rust versions
+pub fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(buf: &Buffer) -> Vec<&str> {
+        buf.iter().map(|l| l.text()).collect()
+    }
+
+    fn state(line: usize) -> UndoState {
+        UndoState {
+            line,
+            ..UndoState::default()
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_groups() {
+        let mut buf = Buffer::from_lines(vec!["a".into(), "b".into()]);
+        let mut log = UndoLog::default();
+
+        log.begin(&mut buf, state(1));
+        buf.delete(1);
+        log.begin(&mut buf, state(2));
+        buf.insert_after(1, "c");
+        assert_eq!(texts(&buf), ["b", "c"]);
+
+        assert_eq!(log.undo(&mut buf, state(9)), Some(state(2)));
+        assert_eq!(texts(&buf), ["b"]);
+        assert_eq!(log.undo_len(), 1);
+        assert_eq!(log.undo(&mut buf, state(8)), Some(state(1)));
+        assert_eq!(texts(&buf), ["a", "b"]);
+        assert_eq!(log.undo(&mut buf, state(0)), None);
+
+        assert_eq!(log.redo(&mut buf, state(0)), Some(state(8)));
+        assert_eq!(texts(&buf), ["b"]);
+        assert_eq!(log.redo(&mut buf, state(0)), Some(state(9)));
+        assert_eq!(texts(&buf), ["b", "c"]);
+        assert_eq!(log.redo_len(), 0);
+    }
+
+    #[test]
+    fn changes_after_a_closed_group_are_undone_alone() {
+        let mut buf = Buffer::from_lines(vec!["a".into()]);
+        let mut log = UndoLog::default();
+        log.begin(&mut buf, state(1));
+        buf.insert_after(1, "b");
+        log.end(&mut buf, state(2));
+        buf.set_line_text(1, "A");
+
+        assert_eq!(log.undo(&mut buf, state(3)), Some(state(3)));
+        assert_eq!(texts(&buf), ["a", "b"]);
+        assert_eq!(log.undo(&mut buf, state(0)), Some(state(1)));
+        assert_eq!(texts(&buf), ["a"]);
+    }
+
+    #[test]
+    fn new_change_clears_redo() {
+        let mut buf = Buffer::from_lines(vec!["a".into()]);
+        let mut log = UndoLog::default();
+        log.begin(&mut buf, state(1));
+        buf.set_line_text(1, "b");
+        log.undo(&mut buf, state(1));
+        assert_eq!(log.redo_len(), 1);
+
+        log.begin(&mut buf, state(1));
+        buf.set_line_text(1, "c");
+        log.begin(&mut buf, state(1));
+        assert_eq!(log.redo_len(), 0);
+    }
+
+    #[test]
+    fn limit_drops_oldest_groups() {
+        let mut buf = Buffer::new();
+        let mut log = UndoLog::new(2);
+        for text in ["a", "b", "c"] {
+            log.begin(&mut buf, state(0));
+            buf.insert_after(0, text);
+        }
+        log.begin(&mut buf, state(0));
+        assert_eq!(log.undo_len(), 2);
+
+        log.set_limit(0);
+        log.begin(&mut buf, state(0));
+        buf.insert_after(0, "d");
+        assert_eq!(log.undo(&mut buf, state(0)), None);
+    }
+
+    #[test]
+    fn sidecar_round_trip() {
+        let mut buf = Buffer::from_lines(vec!["one".into(), "two\\".into()]);
+        buf.set_point("FIRST", 1);
+        let mut log = UndoLog::default();
+        log.begin(&mut buf, state(2));
+        buf.set_line_text(2, "TWO");
+        buf.delete(1);
+        buf.insert_after(1, "three");
+        buf.set_point("LAST", 1);
+        buf.renumber_points(|line| 3 - line);
+        let sidecar = log.save(&mut buf, state(0), 42);
+
+        let mut other = UndoLog::default();
+        assert!(!other.load(&sidecar, 7).unwrap());
+        assert!(other.load(&sidecar, 42).unwrap());
+        assert_eq!(other.undo(&mut buf, state(0)), Some(state(2)));
+        assert_eq!(texts(&buf), ["one", "two\\"]);
+        assert_eq!(buf.points(), [(1, "FIRST"), (2, "LAST")]);
+
+        // Every deletion record carries its point count
+        let short = format!("{}\n{:016x}\nG 1 0 0\nD 1 1\none\n", SIDECAR_MAGIC, 42);
+        assert!(UndoLog::default().load(&short, 42).is_err());
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        for text in ["plain", "back\\slash", "new\nline", "\\n literal", "cr\r"] {
+            assert_eq!(unescape(&escape(text)), text);
+        }
+    }
+}
@@ -1,6 +1,8 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io;
 use std::path::Path;
+use std::rc::Rc;
 
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
@@ -9,8 +11,11 @@ use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 
 use xedit_core::command::{parse_command, Command, CommandAction};
-use xedit_core::editor::{CursorRequest, Editor};
+use xedit_core::editor::{CursorRequest, Editor, LoadOptions};
+use xedit_core::filesystem::{FileId, FileSystem, NativeFs};
+use xedit_core::host::Host;
 use xedit_core::prefix::PrefixCommand;
+use xedit_core::ring::Ring;
 
 use crate::input::{read_action, Action};
 use crate::screen;
@@ -27,9 +32,22 @@ pub enum CursorFocus {
     FileArea,
 }
 
+/// Screen state saved for a file while another file in the ring is shown
+#[derive(Default)]
+struct FileView {
+    file_line: usize,
+    file_col: usize,
+    prefix_inputs: HashMap<usize, String>,
+}
+
 /// Application state
 pub struct App {
-    editor: Editor,
+    /// Files being edited; the current one is shown on screen
+    ring: Ring,
+    /// Record format, code page and BROWSE for every file opened
+    load_options: LoadOptions,
+    /// Saved screen state, parallel to the ring (the current entry is stale)
+    views: Vec<FileView>,
     focus: CursorFocus,
 
     // Command line state
@@ -62,9 +80,16 @@ pub struct App {
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(load_options: LoadOptions) -> Self {
+        Self::with_filesystem(load_options, Rc::new(NativeFs))
+    }
+
+    /// An editor whose files are located, read and saved through `fs`
+    pub fn with_filesystem(load_options: LoadOptions, fs: Rc<dyn FileSystem>) -> Self {
         Self {
-            editor: Editor::new(),
+            ring: Ring::with_filesystem(fs),
+            load_options,
+            views: Vec::new(),
             focus: CursorFocus::CommandLine,
             command_text: String::new(),
             command_cursor: 0,
@@ -80,16 +105,122 @@ impl App {
         }
     }
 
+    /// Send macros' ADDRESS CMS commands to `host`
+    pub fn set_host(&mut self, host: Rc<RefCell<dyn Host>>) {
+        self.ring.set_host(host);
+    }
+
+    /// Add a file to the ring and make it the current file
     pub fn load_file(&mut self, path: &Path) -> xedit_core::error::Result<()> {
-        self.editor.load_file(path)?;
+        let previous = self.ring.current_index();
+        self.ring.add_file_with(path, &self.load_options)?;
+        // The view is saved only once the file is in, so a failed load
+        // leaves the current file's screen as it was
+        self.save_view_of(previous);
+        self.file_added();
+        Ok(())
+    }
+
+    /// Add a file located by `Ring::locate` and make it the current file
+    fn open_file(&mut self, id: &FileId) -> xedit_core::error::Result<()> {
+        let previous = self.ring.current_index();
+        self.ring.open_file(id, &self.load_options)?;
+        self.save_view_of(previous);
+        self.file_added();
+        Ok(())
+    }
+
+    /// Give a file just added to the ring its view and run PROFILE XEDIT
+    fn file_added(&mut self) {
+        self.views.push(FileView::default());
         // Run PROFILE XEDIT macro if it exists (customizes settings on file open)
         #[cfg(feature = "rexx")]
-        self.editor.run_profile();
-        self.file_line = self.editor.current_line().max(1);
-        Ok(())
+        self.editor_mut().run_profile();
+        self.file_line = self.editor().current_line().max(1);
+        self.file_col = 7;
+        self.prefix_inputs.clear();
+    }
+
+    fn editor(&self) -> &Editor {
+        self.ring.current().expect("file ring is never empty")
+    }
+
+    fn editor_mut(&mut self) -> &mut Editor {
+        self.ring.current_mut().expect("file ring is never empty")
+    }
+
+    // -- File ring --
+
+    /// Stash the current file's screen state into its view slot
+    fn save_view(&mut self) {
+        self.save_view_of(self.ring.current_index());
+    }
+
+    /// Stash the screen state into the view slot of the file at `index`
+    fn save_view_of(&mut self, index: usize) {
+        if let Some(view) = self.views.get_mut(index) {
+            view.file_line = self.file_line;
+            view.file_col = self.file_col;
+            view.prefix_inputs = std::mem::take(&mut self.prefix_inputs);
+        }
+    }
+
+    /// Restore the current file's screen state from its view slot
+    fn restore_view(&mut self) {
+        if let Some(view) = self.views.get_mut(self.ring.current_index()) {
+            self.file_line = view.file_line;
+            self.file_col = view.file_col;
+            self.prefix_inputs = std::mem::take(&mut view.prefix_inputs);
+        }
+        self.file_area_edited = false;
+    }
+
+    /// XEDIT [fn ft [fm]]: switch to a file already in the ring, or add it.
+    /// With no operands, cycle to the next file.
+    fn edit_file(&mut self, spec: Option<&str>) {
+        let Some(spec) = spec else {
+            self.save_view();
+            let _ = self.ring.cycle_next();
+            self.restore_view();
+            return;
+        };
+
+        let id = match self.ring.locate(spec) {
+            Ok(id) => id,
+            Err(e) => {
+                self.editor_mut().set_message(e.to_string());
+                return;
+            }
+        };
+        if let Some(index) = self.ring.find_file(&id.path) {
+            self.save_view();
+            let _ = self.ring.switch_to(index);
+            self.restore_view();
+        } else if let Err(e) = self.open_file(&id) {
+            self.editor_mut().set_message(e.to_string());
+        }
+    }
+
+    /// QUIT/FILE: drop the current file; leave the editor when the ring is empty
+    fn close_current_file(&mut self) {
+        let index = self.ring.current_index();
+        self.ring.remove_current();
+        if index < self.views.len() {
+            self.views.remove(index);
+        }
+        if self.ring.is_empty() {
+            self.should_quit = true;
+        } else {
+            self.restore_view();
+        }
     }
 
     pub fn run(&mut self) -> io::Result<()> {
+        if self.ring.is_empty() {
+            self.ring.add_empty();
+            self.views.push(FileView::default());
+        }
+
         enable_raw_mode()?;
         let mut stdout = io::stdout();
         crossterm::execute!(stdout, EnterAlternateScreen)?;
@@ -111,13 +242,14 @@ impl App {
     ) -> io::Result<()> {
         loop {
             let size = terminal.size()?;
-            self.editor
+            self.editor_mut()
                 .set_page_size(size.height.saturating_sub(3) as usize);
 
             terminal.draw(|frame| {
                 screen::render(
                     frame,
-                    &self.editor,
+                    self.editor(),
+                    (self.ring.current_index() + 1, self.ring.len()),
                     &self.command_text,
                     self.command_cursor,
                     &self.focus,
@@ -169,6 +301,9 @@ impl App {
 
     // -- Command line handling --
 
+    // A key's conditions stay inside its arm, so a key never falls through
+    // to a later arm
+    #[allow(clippy::collapsible_match)]
     fn handle_command_line(&mut self, action: Action) {
         match action {
             Action::Char(c) => {
@@ -203,30 +338,30 @@ impl App {
                 }
             }
             Action::ArrowUp => {
-                if self.editor.history_len() > 0 {
+                if self.editor().history_len() > 0 {
                     // Browse history backward (older)
                     let new_idx = match self.history_index {
                         Some(idx) => idx.saturating_sub(1),
-                        None => self.editor.history_len().saturating_sub(1),
+                        None => self.editor().history_len().saturating_sub(1),
                     };
                     self.history_index = Some(new_idx);
-                    if let Some(cmd) = self.editor.history_get(new_idx) {
+                    if let Some(cmd) = self.editor().history_get(new_idx) {
                         self.command_text = cmd.to_string();
                         self.command_cursor = self.command_text.len();
                     }
                 } else {
-                    let _ = self.editor.execute(&Command::Up(1));
+                    let _ = self.editor_mut().execute(&Command::Up(1));
                     self.sync_file_cursor_to_editor();
                 }
             }
             Action::ArrowDown => {
                 if let Some(idx) = self.history_index {
                     // Browse history forward (newer)
-                    let max_idx = self.editor.history_len().saturating_sub(1);
+                    let max_idx = self.editor().history_len().saturating_sub(1);
                     if idx < max_idx {
                         let new_idx = idx + 1;
                         self.history_index = Some(new_idx);
-                        if let Some(cmd) = self.editor.history_get(new_idx) {
+                        if let Some(cmd) = self.editor().history_get(new_idx) {
                             self.command_text = cmd.to_string();
                             self.command_cursor = self.command_text.len();
                         }
@@ -237,7 +372,7 @@ impl App {
                         self.command_cursor = 0;
                     }
                 } else {
-                    let _ = self.editor.execute(&Command::Down(1));
+                    let _ = self.editor_mut().execute(&Command::Down(1));
                     self.sync_file_cursor_to_editor();
                 }
             }
@@ -251,15 +386,15 @@ impl App {
                 // Switch to file area
                 self.focus = CursorFocus::FileArea;
                 // Position cursor on current line, in data area
-                self.file_line = self.editor.current_line().max(1);
+                self.file_line = self.editor().current_line().max(1);
                 self.file_col = 7; // first data column
             }
             Action::PageUp => {
-                let _ = self.editor.execute(&Command::Backward(1));
+                let _ = self.editor_mut().execute(&Command::Backward(1));
                 self.sync_file_cursor_to_editor();
             }
             Action::PageDown => {
-                let _ = self.editor.execute(&Command::Forward(1));
+                let _ = self.editor_mut().execute(&Command::Forward(1));
                 self.sync_file_cursor_to_editor();
             }
             Action::Escape => {
@@ -272,9 +407,10 @@ impl App {
 
     // -- File area handling --
 
+    #[allow(clippy::collapsible_match)]
     fn handle_file_area(&mut self, action: Action) {
         let in_prefix = self.file_col >= 1 && self.file_col <= 5;
-        let buf_len = self.editor.buffer().len();
+        let buf_len = self.editor().buffer().len();
 
         match action {
             Action::Char(c) => {
@@ -292,10 +428,18 @@ impl App {
                 }
             }
             Action::Delete => {
-                if !in_prefix && self.file_line >= 1 && self.file_line <= buf_len {
+                if !in_prefix
+                    && self.file_line >= 1
+                    && self.file_line <= buf_len
+                    && self.editor().is_line_visible(self.file_line)
+                {
                     self.ensure_screen_edit_snapshot();
-                    let data_col = self.file_col.saturating_sub(PREFIX_COLS + 1);
-                    self.editor.delete_char(self.file_line, data_col);
+                    let line = self.file_line;
+                    let mut data_col = self.file_col.saturating_sub(PREFIX_COLS + 1);
+                    if self.editor().hex() {
+                        data_col /= 2;
+                    }
+                    self.editor_mut().delete_char(line, data_col);
                 }
             }
             Action::Enter => {
@@ -322,14 +466,20 @@ impl App {
             }
             Action::ArrowUp => {
                 if self.file_line > 1 {
-                    self.file_line -= 1;
-                    self.editor.set_current_line(self.file_line);
+                    let prev = match self.editor().shadow_group(self.file_line) {
+                        Some((first, _)) => first - 1,
+                        None => self.file_line - 1,
+                    };
+                    self.move_file_cursor(prev, false);
                 }
             }
             Action::ArrowDown => {
                 if self.file_line < buf_len {
-                    self.file_line += 1;
-                    self.editor.set_current_line(self.file_line);
+                    let next = match self.editor().shadow_group(self.file_line) {
+                        Some((_, last)) => last + 1,
+                        None => self.file_line + 1,
+                    };
+                    self.move_file_cursor(next, true);
                 }
             }
             Action::ArrowLeft => {
@@ -358,16 +508,17 @@ impl App {
             Action::End => {
                 if in_prefix {
                     self.file_col = 5;
-                } else if let Some(text) = self.editor.buffer().line_text(self.file_line) {
-                    self.file_col = PREFIX_COLS + 1 + text.len();
+                } else if let Some(text) = self.editor().buffer().line_text(self.file_line) {
+                    let width = if self.editor().hex() { 2 } else { 1 };
+                    self.file_col = PREFIX_COLS + 1 + text.chars().count() * width;
                 }
             }
             Action::PageUp => {
-                let _ = self.editor.execute(&Command::Backward(1));
+                let _ = self.editor_mut().execute(&Command::Backward(1));
                 self.sync_file_cursor_to_editor();
             }
             Action::PageDown => {
-                let _ = self.editor.execute(&Command::Forward(1));
+                let _ = self.editor_mut().execute(&Command::Forward(1));
                 self.sync_file_cursor_to_editor();
             }
             Action::InsertToggle => {
@@ -377,7 +528,7 @@ impl App {
                 } else {
                     "Overtype mode"
                 };
-                self.editor.set_message(mode);
+                self.editor_mut().set_message(mode);
             }
             Action::Escape => {
                 // Escape in file area: return to command line, clear pending prefixes
@@ -389,11 +540,32 @@ impl App {
         }
     }
 
+    /// Put the file area cursor on `line`. A hidden line stands for its
+    /// shadow line, or is skipped (in the direction of travel) when SET
+    /// SHADOW is OFF. The current line follows the cursor onto file lines.
+    fn move_file_cursor(&mut self, line: usize, down: bool) {
+        let buf_len = self.editor().buffer().len();
+        let line = match self.editor().shadow_group(line) {
+            Some((first, _)) if self.editor().show_shadow() => first,
+            Some((_, last)) if down && last < buf_len => last + 1,
+            Some((first, _)) if !down && first > 1 => first - 1,
+            Some(_) => return,
+            None => line,
+        };
+        if line == 0 || line > buf_len {
+            return;
+        }
+        self.file_line = line;
+        if self.editor().is_line_visible(line) {
+            self.editor_mut().set_current_line(line);
+        }
+    }
+
     // -- Prefix area editing --
 
     fn type_in_prefix(&mut self, c: char) {
         let line = self.file_line;
-        if line == 0 || line > self.editor.buffer().len() {
+        if line == 0 || line > self.editor().buffer().len() {
             return;
         }
 
@@ -449,27 +621,35 @@ impl App {
     /// Snapshot once per file-area editing session (first keystroke)
     fn ensure_screen_edit_snapshot(&mut self) {
         if !self.file_area_edited {
-            self.editor.snapshot_for_undo();
+            self.editor_mut().snapshot_for_undo();
             self.file_area_edited = true;
         }
     }
 
     fn type_in_data(&mut self, c: char) {
         let line = self.file_line;
-        if line == 0 || line > self.editor.buffer().len() {
+        if line == 0 || line > self.editor().buffer().len() || !self.editor().is_line_visible(line)
+        {
             return;
         }
 
         self.ensure_screen_edit_snapshot();
         let data_col = self.file_col.saturating_sub(PREFIX_COLS + 1);
 
-        if self.insert_mode {
-            self.editor.insert_char(line, data_col, c);
+        let typed = if self.editor().hex() {
+            // Hex display: two screen columns per character, one per digit
+            self.editor_mut()
+                .overtype_hex_digit(line, data_col / 2, data_col.is_multiple_of(2), c)
+        } else if self.insert_mode {
+            self.editor_mut().insert_char(line, data_col, c)
         } else {
-            self.editor.overtype_char(line, data_col, c);
-        }
+            self.editor_mut().overtype_char(line, data_col, c)
+        };
 
-        self.file_col += 1;
+        match typed {
+            Ok(()) => self.file_col += 1,
+            Err(e) => self.editor_mut().set_message(e.to_string()),
+        }
     }
 
     fn backspace_in_data(&mut self) {
@@ -478,27 +658,36 @@ impl App {
             // At start of data area — don't cross into prefix
             return;
         }
+        if !self.editor().is_line_visible(line) {
+            // A shadow line has no data to edit
+            return;
+        }
 
-        self.ensure_screen_edit_snapshot();
         self.file_col -= 1;
+        if self.editor().hex() {
+            // Hex digits are overtyped in place; backspace only moves back
+            return;
+        }
+        self.ensure_screen_edit_snapshot();
         let data_col = self.file_col.saturating_sub(PREFIX_COLS + 1);
-        self.editor.delete_char(line, data_col);
+        self.editor_mut().delete_char(line, data_col);
     }
 
     // -- Enter processing (batch commit) --
 
     fn process_enter(&mut self) {
         // Collect and parse prefix commands
-        let mut parsed: Vec<(usize, PrefixCommand)> = self
-            .prefix_inputs
-            .drain()
-            .filter_map(|(line, text)| PrefixCommand::parse(&text).map(|cmd| (line, cmd)))
+        let inputs: Vec<(usize, String)> = self.prefix_inputs.drain().collect();
+        let mut parsed: Vec<(usize, PrefixCommand)> = inputs
+            .into_iter()
+            .filter_map(|(line, text)| self.editor().parse_prefix(&text).map(|cmd| (line, cmd)))
             .collect();
 
         // Sort by priority, then by line number.
         // Priority ordering ensures correct IBM XEDIT semantics:
         //   0: SetCurrent (/) — non-modifying, safe first
-        //   1: Block markers (dd, cc, mm, "") — pair up before singles shift lines
+        //   1: Block markers (dd, cc, mm, "") and prefix macros, which may
+        //      work on blocks — pair up before singles shift lines
         //   2: Single-line modifying (d, i, a, ", >, <) — descending line order to
         //      avoid line-number shifting
         //   3: Pending copy/move (c, m) — sets up pending operation
@@ -518,14 +707,14 @@ impl App {
 
         // Execute in priority order
         for (line_num, cmd) in &parsed {
-            match self.editor.execute_prefix(*line_num, cmd) {
+            match self.editor_mut().execute_prefix(*line_num, cmd) {
                 Ok(result) => {
                     if let Some(msg) = result.message {
-                        self.editor.set_message(msg);
+                        self.editor_mut().set_message(msg);
                     }
                 }
                 Err(e) => {
-                    self.editor.set_message(e.to_string());
+                    self.editor_mut().set_message(e.to_string());
                 }
             }
         }
@@ -558,9 +747,13 @@ impl App {
                     self.in_input_mode = false;
                 } else {
                     let text = self.input_text.clone();
-                    self.input_text.clear();
-                    self.editor.input_line(&text);
-                    self.sync_file_cursor_to_editor();
+                    match self.editor_mut().input_line(&text) {
+                        Ok(()) => {
+                            self.input_text.clear();
+                            self.sync_file_cursor_to_editor();
+                        }
+                        Err(e) => self.editor_mut().set_message(e.to_string()),
+                    }
                 }
             }
             Action::Escape => {
@@ -585,11 +778,12 @@ impl App {
             self.process_enter();
         }
 
-        if let Some(cmd_text) = self.editor.pf_key(num) {
+        if let Some(cmd_text) = self.editor().pf_key(num) {
             let cmd_text = cmd_text.to_string();
             self.execute_command_text(&cmd_text);
         } else {
-            self.editor.set_message(format!("PF{} is not defined", num));
+            self.editor_mut()
+                .set_message(format!("PF{} is not defined", num));
         }
     }
 
@@ -600,46 +794,47 @@ impl App {
 
         // `?` (PF6): recall last command into command line
         if trimmed == "?" {
-            if let Some(last) = self.editor.last_command() {
+            if let Some(last) = self.editor().last_command() {
                 self.command_text = last.to_string();
                 self.command_cursor = self.command_text.len();
             } else {
-                self.editor.set_message("No commands in history");
+                self.editor_mut().set_message("No commands in history");
             }
             return;
         }
 
         // `=` (PF9): re-execute last command
         if trimmed == "=" {
-            if let Some(last) = self.editor.last_command() {
+            if let Some(last) = self.editor().last_command() {
                 let last = last.to_string();
                 self.execute_command_text(&last);
             } else {
-                self.editor.set_message("No commands in history");
+                self.editor_mut().set_message("No commands in history");
             }
             return;
         }
 
         // Record in history before executing
-        self.editor.push_history(trimmed);
+        self.editor_mut().push_history(trimmed);
         self.history_index = None;
 
         match parse_command(text) {
-            Ok(cmd) => match self.editor.execute(&cmd) {
+            Ok(cmd) => match self.editor_mut().execute(&cmd) {
                 Ok(result) => match result.action {
-                    CommandAction::Quit => self.should_quit = true,
+                    CommandAction::Quit => self.close_current_file(),
                     CommandAction::EnterInput => {
                         self.in_input_mode = true;
                         self.input_text.clear();
                     }
+                    CommandAction::Edit(spec) => self.edit_file(spec.as_deref()),
                     CommandAction::Refresh | CommandAction::Continue => {}
                 },
                 Err(e) => {
-                    self.editor.set_message(e.to_string());
+                    self.editor_mut().set_message(e.to_string());
                 }
             },
             Err(e) => {
-                self.editor.set_message(e);
+                self.editor_mut().set_message(e);
             }
         }
         self.sync_file_cursor_to_editor();
@@ -647,7 +842,7 @@ impl App {
     }
 
     fn apply_cursor_request(&mut self) {
-        if let Some(req) = self.editor.take_cursor_request() {
+        if let Some(req) = self.editor_mut().take_cursor_request() {
             match req {
                 CursorRequest::Home => {
                     self.focus = CursorFocus::CommandLine;
@@ -656,9 +851,10 @@ impl App {
                 }
                 CursorRequest::File { line, col } => {
                     self.focus = CursorFocus::FileArea;
-                    self.file_line = line.max(1).min(self.editor.buffer().len());
+                    self.file_line = line.max(1).min(self.editor().buffer().len());
                     self.file_col = PREFIX_COLS + 1 + col.saturating_sub(1);
-                    self.editor.set_current_line(self.file_line);
+                    let line = self.file_line;
+                    self.editor_mut().set_current_line(line);
                 }
             }
         }
@@ -666,11 +862,11 @@ impl App {
 
     /// Keep the file area cursor in sync with the editor's current line
     fn sync_file_cursor_to_editor(&mut self) {
-        let current = self.editor.current_line();
+        let current = self.editor().current_line();
         if current > 0 {
             self.file_line = current;
         } else {
-            self.file_line = 1.min(self.editor.buffer().len());
+            self.file_line = 1.min(self.editor().buffer().len());
         }
     }
 }
@@ -679,11 +875,18 @@ impl App {
 /// Lower numbers execute first. See `process_enter()` for rationale.
 fn prefix_priority(cmd: &PrefixCommand) -> u8 {
     match cmd {
-        PrefixCommand::SetCurrent => 0,
+        // Names and exclusions are set before edits so they move with their
+        // lines, and a shadow line's `s` still finds the lines it hides
+        PrefixCommand::SetCurrent
+        | PrefixCommand::Point(_)
+        | PrefixCommand::Exclude(_)
+        | PrefixCommand::Show(_) => 0,
         PrefixCommand::DeleteBlock
         | PrefixCommand::CopyBlock
         | PrefixCommand::MoveBlock
-        | PrefixCommand::DuplicateBlock => 1,
+        | PrefixCommand::DuplicateBlock
+        | PrefixCommand::ExcludeBlock
+        | PrefixCommand::User { .. } => 1,
         PrefixCommand::Delete
         | PrefixCommand::Insert(_)
         | PrefixCommand::Add(_)
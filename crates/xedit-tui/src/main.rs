@@ -2,16 +2,84 @@ mod app;
 mod input;
 mod screen;
 
-use std::path::Path;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::rc::Rc;
+
+use cms_core::xedit::SharedDisks;
+use cms_core::{CmsFileSystem, CommandProcessor};
+
+use xedit_core::buffer::RecordFormat;
+use xedit_core::codepage::Codepage;
+use xedit_core::editor::LoadOptions;
+
+const USAGE: &str =
+    "Usage: xedit [--browse] [--recfm F|V] [--lrecl n] [--codepage 037|1047|500] [file ...]";
+
+/// Split the command line into record format options and file names
+fn parse_args(args: &[String]) -> Result<(LoadOptions, Vec<PathBuf>), String> {
+    let mut opts = LoadOptions::default();
+    let mut files = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--recfm" => {
+                let value = iter.next().ok_or("--recfm requires F or V")?;
+                opts.recfm = Some(match value.to_uppercase().as_str() {
+                    "F" => RecordFormat::Fixed,
+                    "V" => RecordFormat::Variable,
+                    _ => return Err(format!("Invalid RECFM: {}", value)),
+                });
+            }
+            "--lrecl" => {
+                let value = iter.next().ok_or("--lrecl requires a record length")?;
+                match value.parse::<usize>() {
+                    Ok(n) if n > 0 => opts.lrecl = Some(n),
+                    _ => return Err(format!("Invalid LRECL: {}", value)),
+                }
+            }
+            "--browse" => opts.browse = true,
+            "--codepage" => {
+                let value = iter.next().ok_or("--codepage requires a code page")?;
+                opts.codepage = Codepage::parse(value)
+                    .ok_or_else(|| format!("Unknown code page: {}", value))?;
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    Ok((opts, files))
+}
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
+    let (opts, files) = parse_args(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    });
 
-    let mut app = app::App::new();
+    // With $CMS_HOME set, XEDIT fn ft fm finds files on that CMS's disks
+    // and macros' ADDRESS CMS commands run on it
+    let mut app = match std::env::var_os("CMS_HOME") {
+        Some(home) => {
+            let fs = CmsFileSystem::with_default_disk(Path::new(&home)).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            });
+            // One set of disks: a macro's ACCESS and RELEASE change XEDIT's
+            let cms = Rc::new(RefCell::new(CommandProcessor::new(fs)));
+            let disks = SharedDisks::new(cms.clone());
+            let mut app = app::App::with_filesystem(opts, Rc::new(disks));
+            app.set_host(cms);
+            app
+        }
+        None => app::App::new(opts),
+    };
 
-    if args.len() > 1 {
-        let path = Path::new(&args[1]);
+    // Every file named on the command line goes into the ring
+    for file in &files {
+        let path = Path::new(file);
         if let Err(e) = app.load_file(path) {
             eprintln!("Error: {}", e);
             process::exit(1);
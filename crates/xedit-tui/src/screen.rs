@@ -6,6 +6,7 @@ use ratatui::text::{Line, Span};
 use ratatui::widgets::Paragraph;
 use ratatui::Frame;
 
+use xedit_core::buffer::RecordFormat;
 use xedit_core::command::CurLinePosition;
 use xedit_core::editor::Editor;
 
@@ -57,6 +58,7 @@ const INPUT_MODE_FG: Color = Color::Red;
 pub fn render(
     frame: &mut Frame,
     editor: &Editor,
+    ring_position: (usize, usize),
     command_text: &str,
     command_cursor: usize,
     focus: &CursorFocus,
@@ -81,6 +83,7 @@ pub fn render(
         frame,
         chunks[0],
         editor,
+        ring_position,
         insert_mode,
         resolve_color(editor, "IdLine", ID_LINE_FG),
         resolve_color(editor, "IdLine", ID_LINE_BG),
@@ -123,14 +126,16 @@ pub fn render(
 
 /// Info about what's visible in the file area, for cursor positioning
 struct VisibleRange {
-    first_display_idx: usize,
-    // Maps display_idx to screen row (relative to file area)
+    /// What each row of the file area shows: a line number (0 for TOF),
+    /// or for a shadow line the first line it hides
+    row_lines: Vec<Option<usize>>,
 }
 
 fn render_id_line(
     frame: &mut Frame,
     area: Rect,
     editor: &Editor,
+    ring_position: (usize, usize),
     insert_mode: bool,
     fg: Color,
     bg: Color,
@@ -146,18 +151,38 @@ fn render_id_line(
         editor.filetype()
     };
 
-    let mode = if insert_mode { "Ins" } else { "Ovr" };
+    let mode = if editor.is_readonly() {
+        "R/O"
+    } else if insert_mode {
+        "Ins"
+    } else {
+        "Ovr"
+    };
+    let recfm = match editor.buffer().recfm() {
+        RecordFormat::Fixed => "F",
+        RecordFormat::Variable => "V",
+    };
+    let codepage = if editor.codepage().is_ebcdic() {
+        format!(" {}", editor.codepage().name())
+    } else {
+        String::new()
+    };
 
     let id_text = format!(
-        " {:<8} {:<8} {}  Trunc={} Size={} Line={} Col={} Alt={} [{}]",
+        " {:<8} {:<8} {}  {} {}{}  Trunc={} Size={} Line={} Col={} Alt={} File {} of {} [{}]",
         filename,
         filetype,
         editor.filemode(),
+        recfm,
+        editor.buffer().lrecl(),
+        codepage,
         editor.trunc(),
         editor.buffer().len(),
         editor.current_line(),
         editor.current_col(),
         editor.alt_count(),
+        ring_position.0,
+        ring_position.1,
         mode,
     );
 
@@ -172,37 +197,47 @@ fn render_id_line(
 /// An item in the display list
 enum DisplayItem {
     Tof,
-    FileLine(usize), // 1-based line number
-    Shadow(usize),   // count of hidden lines
+    FileLine(usize),      // 1-based line number
+    Shadow(usize, usize), // first hidden line, count of hidden lines
     Eof,
 }
 
-/// Build the display list, collapsing hidden lines into shadow entries
-fn build_display_list(editor: &Editor) -> Vec<DisplayItem> {
+/// Build the display list, collapsing hidden lines into shadow entries.
+///
+/// Without ALL every line is displayed, so only the items within `height`
+/// rows of the current line are listed; a browsed file can have far more
+/// lines than are worth walking on every redraw.
+fn build_display_list(editor: &Editor, height: usize) -> Vec<DisplayItem> {
     let buf_len = editor.buffer().len();
-    let mut items = Vec::with_capacity(buf_len + 2);
-    items.push(DisplayItem::Tof);
 
-    if editor.all_filter_active() {
-        let mut i = 1;
-        while i <= buf_len {
-            if editor.is_line_visible(i) {
-                items.push(DisplayItem::FileLine(i));
-                i += 1;
-            } else {
-                let shadow = editor.shadow_count_after(i - 1);
-                if shadow > 0 && editor.show_shadow() {
-                    items.push(DisplayItem::Shadow(shadow));
-                }
-                i += shadow;
-            }
-        }
-    } else {
-        for i in 1..=buf_len {
+    if !editor.selection_active() {
+        let current = editor.current_line();
+        let first = current.saturating_sub(height);
+        let last = (current + height).min(buf_len + 1);
+        return (first..=last)
+            .map(|i| match i {
+                0 => DisplayItem::Tof,
+                i if i > buf_len => DisplayItem::Eof,
+                i => DisplayItem::FileLine(i),
+            })
+            .collect();
+    }
+
+    let mut items = Vec::new();
+    items.push(DisplayItem::Tof);
+    let mut i = 1;
+    while i <= buf_len {
+        if editor.is_line_visible(i) {
             items.push(DisplayItem::FileLine(i));
+            i += 1;
+        } else {
+            let shadow = editor.shadow_count_after(i - 1);
+            if shadow > 0 && editor.show_shadow() {
+                items.push(DisplayItem::Shadow(i, shadow));
+            }
+            i += shadow;
         }
     }
-
     items.push(DisplayItem::Eof);
     items
 }
@@ -217,7 +252,7 @@ fn render_file_area(
     let current = editor.current_line();
     let width = area.width as usize;
 
-    let display_list = build_display_list(editor);
+    let display_list = build_display_list(editor, height);
 
     let curline_row = match editor.curline_position() {
         CurLinePosition::Middle => height / 2,
@@ -236,13 +271,6 @@ fn render_file_area(
 
     let first_item = current_item_idx.saturating_sub(curline_row);
 
-    // The first_display_idx for cursor positioning: we need to map back to line numbers
-    let first_display_idx = match display_list.get(first_item) {
-        Some(DisplayItem::Tof) => 0,
-        Some(DisplayItem::FileLine(n)) => *n,
-        _ => 0,
-    };
-
     let _data_fg = resolve_color(editor, "FileArea", DATA_FG);
     let _prefix_fg = resolve_color(editor, "Prefix", PREFIX_FG);
     let _curline_fg = resolve_color(editor, "CurLine", CURRENT_LINE_FG);
@@ -250,9 +278,21 @@ fn render_file_area(
     let shadow_fg = resolve_color(editor, "Shadow", SHADOW_FG);
 
     let mut lines: Vec<Line> = Vec::with_capacity(height);
+    let mut row_lines = Vec::with_capacity(height);
     let mut reserved_offset = 0usize;
+    let mut scale_pending = false;
 
     for row in 0..height {
+        // SET SCALE ON puts the scale, marking the column pointer, under
+        // the current line
+        if scale_pending {
+            lines.push(make_scale_line(editor.current_col(), width));
+            row_lines.push(None);
+            reserved_offset += 1;
+            scale_pending = false;
+            continue;
+        }
+
         // Check for reserved lines (1-based row in file area)
         if let Some(reserved_text) = editor.reserved_line(row + 1) {
             let padded = format!("{:<width$}", reserved_text, width = width);
@@ -260,6 +300,7 @@ fn render_file_area(
                 padded,
                 Style::default().fg(Color::White).bg(Color::Blue),
             )));
+            row_lines.push(None);
             reserved_offset += 1;
             continue;
         }
@@ -276,6 +317,14 @@ fn render_file_area(
                 let is_current = *line_num == current;
                 let prefix_text = prefix_inputs.get(line_num);
                 if let Some(text) = editor.buffer().line_text(*line_num) {
+                    // SET HEX ON shows each character as its two hex digits
+                    let hex_text;
+                    let text = if editor.hex() {
+                        hex_text = editor.codepage().to_hex(text);
+                        hex_text.as_str()
+                    } else {
+                        text
+                    };
                     make_data_line(
                         *line_num,
                         text,
@@ -288,8 +337,11 @@ fn render_file_area(
                     make_empty_row(width)
                 }
             }
-            Some(DisplayItem::Shadow(count)) => {
-                let text = format!("      --- {} line(s) not displayed ---", count);
+            Some(DisplayItem::Shadow(first, count)) => {
+                // Prefix commands typed on a shadow line are kept under
+                // the first line it hides
+                let prefix = prefix_inputs.get(first).map_or("", |input| input.as_str());
+                let text = format!("{:<5.5} --- {} line(s) not displayed ---", prefix, count);
                 let padded = format!("{:<width$}", text, width = width);
                 Line::from(Span::styled(padded, Style::default().fg(shadow_fg)))
             }
@@ -297,12 +349,44 @@ fn render_file_area(
             None => make_empty_row(width),
         };
 
+        scale_pending = editor.show_scale()
+            && match item {
+                Some(DisplayItem::Tof) => current == 0,
+                Some(DisplayItem::FileLine(n)) => *n == current,
+                _ => false,
+            };
+        row_lines.push(match item {
+            Some(DisplayItem::Tof) => Some(0),
+            Some(DisplayItem::FileLine(n)) | Some(DisplayItem::Shadow(n, _)) => Some(*n),
+            _ => None,
+        });
         lines.push(line);
     }
 
     frame.render_widget(Paragraph::new(lines), area);
 
-    VisibleRange { first_display_idx }
+    VisibleRange { row_lines }
+}
+
+/// The scale line: `+` every five columns, the tens digit every ten, and
+/// `|` at the column pointer
+fn make_scale_line(column: usize, width: usize) -> Line<'static> {
+    let data_width = width.saturating_sub(PREFIX_WIDTH);
+    let scale: String = (1..=data_width)
+        .map(|col| {
+            if col == column {
+                '|'
+            } else if col.is_multiple_of(10) {
+                char::from_digit((col / 10 % 10) as u32, 10).unwrap_or('.')
+            } else if col.is_multiple_of(5) {
+                '+'
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    let text = format!("{:<pw$}{}", "", scale, pw = PREFIX_WIDTH);
+    Line::from(Span::styled(text, Style::default().fg(MARKER_FG)))
 }
 
 fn make_marker_line(marker: &str, is_current: bool, width: usize) -> Line<'static> {
@@ -493,9 +577,11 @@ fn position_cursor(
         }
         CursorFocus::FileArea => {
             // Find which screen row the file_line maps to
-            let display_idx = if file_line == 0 { 0 } else { file_line };
-            if display_idx >= visible.first_display_idx {
-                let row = display_idx - visible.first_display_idx;
+            let row = visible
+                .row_lines
+                .iter()
+                .position(|line| *line == Some(file_line));
+            if let Some(row) = row {
                 if row < file_area.height as usize {
                     let screen_y = file_area.y + row as u16;
                     // file_col is 1-based; screen column is 0-based from area.x